@@ -0,0 +1,149 @@
+//! Buffered client API.
+//!
+//! These calls never block on the network, and the client reconnects
+//! by itself when the server restarts.
+
+use libc::{c_char, size_t};
+
+use crate::client::{
+    process_c_string, RESULT_ERR_INVALID_ARGUMENT, RESULT_ERR_INVALID_CLIENT_PTR, RESULT_OK,
+};
+use lognplot::net::BufferedClient;
+
+#[no_mangle]
+pub extern "C" fn lognplot_buffered_client_new(address: *const c_char) -> *mut BufferedClient {
+    if address.is_null() {
+        println!("Error: address was NULL");
+        std::ptr::null_mut()
+    } else {
+        let addr = process_c_string(address);
+        Box::into_raw(Box::new(BufferedClient::new(addr)))
+    }
+}
+
+/// Send the remaining data, and free the client.
+#[no_mangle]
+pub extern "C" fn lognplot_buffered_client_close(client_ptr: *mut BufferedClient) -> u32 {
+    if client_ptr.is_null() {
+        RESULT_ERR_INVALID_CLIENT_PTR
+    } else {
+        let client = unsafe { Box::from_raw(client_ptr) };
+        client.close();
+        RESULT_OK
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn lognplot_buffered_client_flush(client_ptr: *mut BufferedClient) -> u32 {
+    if client_ptr.is_null() {
+        RESULT_ERR_INVALID_CLIENT_PTR
+    } else {
+        process_client(client_ptr).flush();
+        RESULT_OK
+    }
+}
+
+/// Amount of samples and events dropped because the queue was full.
+#[no_mangle]
+pub extern "C" fn lognplot_buffered_client_dropped(client_ptr: *mut BufferedClient) -> size_t {
+    if client_ptr.is_null() {
+        0
+    } else {
+        process_client(client_ptr).dropped()
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn lognplot_buffered_client_send_sample(
+    client_ptr: *mut BufferedClient,
+    name: *const c_char,
+    t: f64,
+    value: f64,
+) -> u32 {
+    if client_ptr.is_null() {
+        RESULT_ERR_INVALID_CLIENT_PTR
+    } else if name.is_null() {
+        RESULT_ERR_INVALID_ARGUMENT
+    } else {
+        let client = process_client(client_ptr);
+        let name = process_c_string(name);
+        client.send_sample(name, t, value);
+        RESULT_OK
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn lognplot_buffered_client_send_samples(
+    client_ptr: *mut BufferedClient,
+    name: *const c_char,
+    count: size_t,
+    times: *const f64,
+    values: *const f64,
+) -> u32 {
+    if client_ptr.is_null() {
+        RESULT_ERR_INVALID_CLIENT_PTR
+    } else if name.is_null() {
+        RESULT_ERR_INVALID_ARGUMENT
+    } else {
+        let client = process_client(client_ptr);
+        let name = process_c_string(name);
+
+        let samples = {
+            let times = unsafe { std::slice::from_raw_parts(times, count) };
+            let values = unsafe { std::slice::from_raw_parts(values, count) };
+            times.iter().cloned().zip(values.iter().cloned()).collect()
+        };
+
+        client.send_samples(name, samples);
+        RESULT_OK
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn lognplot_buffered_client_send_sampled_samples(
+    client_ptr: *mut BufferedClient,
+    name: *const c_char,
+    t0: f64,
+    dt: f64,
+    count: size_t,
+    values: *const f64,
+) -> u32 {
+    if client_ptr.is_null() {
+        RESULT_ERR_INVALID_CLIENT_PTR
+    } else if name.is_null() {
+        RESULT_ERR_INVALID_ARGUMENT
+    } else {
+        let client = process_client(client_ptr);
+        let name = process_c_string(name);
+        let values: Vec<f64> = unsafe { std::slice::from_raw_parts(values, count) }.to_vec();
+        client.send_sampled_samples(name, t0, dt, values);
+        RESULT_OK
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn lognplot_buffered_client_send_text(
+    client_ptr: *mut BufferedClient,
+    name: *const c_char,
+    t: f64,
+    text: *const c_char,
+) -> u32 {
+    if client_ptr.is_null() {
+        RESULT_ERR_INVALID_CLIENT_PTR
+    } else if name.is_null() || text.is_null() {
+        RESULT_ERR_INVALID_ARGUMENT
+    } else {
+        let client = process_client(client_ptr);
+        let name = process_c_string(name);
+        let text = process_c_string(text);
+        client.send_text(name, t, text.to_owned());
+        RESULT_OK
+    }
+}
+
+fn process_client<'a>(client_ptr: *mut BufferedClient) -> &'a BufferedClient {
+    unsafe {
+        assert!(!client_ptr.is_null());
+        &*client_ptr
+    }
+}
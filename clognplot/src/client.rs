@@ -5,10 +5,10 @@ use std::ffi::CStr;
 
 use lognplot::net::TcpClient;
 
-const RESULT_OK: u32 = 0;
-const RESULT_ERR_OTHER: u32 = 1;
-const RESULT_ERR_INVALID_CLIENT_PTR: u32 = 2;
-const RESULT_ERR_INVALID_ARGUMENT: u32 = 3;
+pub(crate) const RESULT_OK: u32 = 0;
+pub(crate) const RESULT_ERR_OTHER: u32 = 1;
+pub(crate) const RESULT_ERR_INVALID_CLIENT_PTR: u32 = 2;
+pub(crate) const RESULT_ERR_INVALID_ARGUMENT: u32 = 3;
 
 #[no_mangle]
 pub extern "C" fn lognplot_client_new(address: *const c_char) -> *mut TcpClient {
@@ -32,6 +32,33 @@ pub extern "C" fn lognplot_client_new(address: *const c_char) -> *mut TcpClient
     }
 }
 
+/// Introduce the client to the server, which must support the handshake.
+#[no_mangle]
+pub extern "C" fn lognplot_client_hello(client_ptr: *mut TcpClient, name: *const c_char) -> u32 {
+    if client_ptr.is_null() {
+        RESULT_ERR_INVALID_CLIENT_PTR
+    } else if name.is_null() {
+        RESULT_ERR_INVALID_ARGUMENT
+    } else {
+        let client = process_client(client_ptr);
+        let name = process_c_string(name);
+
+        match client.hello(name) {
+            Ok(session) => {
+                println!(
+                    "Speaking protocol version {} with {}",
+                    session.version, session.peer
+                );
+                RESULT_OK
+            }
+            Err(err) => {
+                println!("Error: {:?}", err);
+                RESULT_ERR_OTHER
+            }
+        }
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn lognplot_client_close(client_ptr: *mut TcpClient) -> u32 {
     if client_ptr.is_null() {
@@ -166,7 +193,7 @@ pub extern "C" fn lognplot_client_send_text(
     }
 }
 
-fn process_c_string<'a>(s: *const c_char) -> &'a str {
+pub(crate) fn process_c_string<'a>(s: *const c_char) -> &'a str {
     unsafe {
         assert!(!s.is_null());
         CStr::from_ptr(s)
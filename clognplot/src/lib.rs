@@ -4,5 +4,7 @@
 //! http://jakegoulding.com/rust-ffi-omnibus/slice_arguments/
 //!
 
+mod buffered_client;
 mod client;
 mod tsdb;
+mod udp_client;
@@ -0,0 +1,125 @@
+//! UDP client API.
+
+use libc::{c_char, size_t};
+
+use crate::client::{
+    process_c_string, RESULT_ERR_INVALID_ARGUMENT, RESULT_ERR_INVALID_CLIENT_PTR, RESULT_ERR_OTHER,
+    RESULT_OK,
+};
+use lognplot::net::UdpClient;
+
+#[no_mangle]
+pub extern "C" fn lognplot_udp_client_new(address: *const c_char) -> *mut UdpClient {
+    if address.is_null() {
+        println!("Error: address was NULL");
+        std::ptr::null_mut()
+    } else {
+        let addr = process_c_string(address);
+
+        match UdpClient::new(addr) {
+            Ok(client) => Box::into_raw(Box::new(client)),
+            Err(err) => {
+                println!("Error: {:?}", err);
+                std::ptr::null_mut()
+            }
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn lognplot_udp_client_close(client_ptr: *mut UdpClient) -> u32 {
+    if client_ptr.is_null() {
+        RESULT_ERR_INVALID_CLIENT_PTR
+    } else {
+        // Drop client:
+        drop(unsafe { Box::from_raw(client_ptr) });
+        RESULT_OK
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn lognplot_udp_client_send_sample(
+    client_ptr: *mut UdpClient,
+    name: *const c_char,
+    t: f64,
+    value: f64,
+) -> u32 {
+    if client_ptr.is_null() {
+        RESULT_ERR_INVALID_CLIENT_PTR
+    } else if name.is_null() {
+        RESULT_ERR_INVALID_ARGUMENT
+    } else {
+        let client = process_client(client_ptr);
+        let name = process_c_string(name);
+
+        if let Err(err) = client.send_sample(name, t, value) {
+            println!("Error: {:?}", err);
+            RESULT_ERR_OTHER
+        } else {
+            RESULT_OK
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn lognplot_udp_client_send_samples(
+    client_ptr: *mut UdpClient,
+    name: *const c_char,
+    count: size_t,
+    times: *const f64,
+    values: *const f64,
+) -> u32 {
+    if client_ptr.is_null() {
+        RESULT_ERR_INVALID_CLIENT_PTR
+    } else if name.is_null() {
+        RESULT_ERR_INVALID_ARGUMENT
+    } else {
+        let client = process_client(client_ptr);
+        let name = process_c_string(name);
+
+        let samples = {
+            let times = unsafe { std::slice::from_raw_parts(times, count) };
+            let values = unsafe { std::slice::from_raw_parts(values, count) };
+            times.iter().cloned().zip(values.iter().cloned()).collect()
+        };
+
+        if let Err(err) = client.send_samples(name, samples) {
+            println!("Error: {:?}", err);
+            RESULT_ERR_OTHER
+        } else {
+            RESULT_OK
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn lognplot_udp_client_send_text(
+    client_ptr: *mut UdpClient,
+    name: *const c_char,
+    t: f64,
+    text: *const c_char,
+) -> u32 {
+    if client_ptr.is_null() {
+        RESULT_ERR_INVALID_CLIENT_PTR
+    } else if name.is_null() || text.is_null() {
+        RESULT_ERR_INVALID_ARGUMENT
+    } else {
+        let client = process_client(client_ptr);
+        let name = process_c_string(name);
+        let text = process_c_string(text);
+
+        if let Err(err) = client.send_text(name, t, text.to_owned()) {
+            println!("Error: {:?}", err);
+            RESULT_ERR_OTHER
+        } else {
+            RESULT_OK
+        }
+    }
+}
+
+fn process_client<'a>(client_ptr: *mut UdpClient) -> &'a mut UdpClient {
+    unsafe {
+        assert!(!client_ptr.is_null());
+        &mut *client_ptr
+    }
+}
@@ -17,6 +17,14 @@ void TcpClient::Connect(const char* address)
     }
 }
 
+void TcpClient::Hello(const char* name)
+{
+    lognplot_result_t result = lognplot_client_hello(this->handle, name);
+    if (result != LOGNPLOT_RESULT_OK) {
+        throw ClientException("Handshake with lognplot GUI failed");
+    }
+}
+
 void TcpClient::Disconnect()
 {
     lognplot_client_close(this->handle);
@@ -14,6 +14,7 @@ class TcpClient {
     public:
         TcpClient();
         void Connect(const char* address);
+        void Hello(const char* name);
         void Disconnect();
         void SendSample(const char* name, const double timestamp, const double value);
         void SendSamples(const char* name, const size_t count, double* timestamps, double* values);
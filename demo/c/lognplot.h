@@ -23,10 +23,22 @@ typedef uint32_t lognplot_result_t;
 /*
     Create a new client and connect to the server.
 
-    \param address the address of the server.
+    \param address the address of the server, or unix:/path/to/socket
+           for a server on the same host listening on a unix domain socket.
 */
 lognplot_client_t* lognplot_client_new(const char* address);
 
+/*
+    Introduce the client to the server, and agree on a protocol version.
+
+    Only use this with servers which support the handshake, older
+    servers never answer.
+
+    \param client the client pointer.
+    \param name the name of the client program
+*/
+lognplot_result_t lognplot_client_hello(lognplot_client_t* client, const char* name);
+
 /*
   Close client connection gracefully.
  */
@@ -108,4 +120,117 @@ lognplot_result_t lognplot_client_send_text(
     const char* text
 );
 
+typedef int* lognplot_buffered_client_t;
+
+/*
+    Create a client which batches data, and sends it from a background thread.
+
+    The client connects in the background, and reconnects when the
+    connection is lost. The send functions never block on the network.
+    When the queue is full, the oldest data is dropped.
+
+    \param address the address of the server.
+*/
+lognplot_buffered_client_t* lognplot_buffered_client_new(const char* address);
+
+/*
+    Send the remaining data, stop the background thread and free the client.
+*/
+lognplot_result_t lognplot_buffered_client_close(lognplot_buffered_client_t* client);
+
+/*
+    Send all held back data now.
+*/
+lognplot_result_t lognplot_buffered_client_flush(lognplot_buffered_client_t* client);
+
+/*
+    Get the amount of data dropped because the queue was full.
+*/
+size_t lognplot_buffered_client_dropped(lognplot_buffered_client_t* client);
+
+lognplot_result_t lognplot_buffered_client_send_sample(
+    lognplot_buffered_client_t* client,
+    const char* name,
+    double timestamp,
+    double value
+);
+
+lognplot_result_t lognplot_buffered_client_send_samples(
+    lognplot_buffered_client_t* client,
+    const char* name,
+    const size_t count,
+    double* timestamps,
+    double* values
+);
+
+lognplot_result_t lognplot_buffered_client_send_sampled_samples(
+    lognplot_buffered_client_t* client,
+    const char* name,
+    double t0,
+    double dt,
+    const size_t size,
+    double* values
+);
+
+lognplot_result_t lognplot_buffered_client_send_text(
+    lognplot_buffered_client_t* client,
+    const char* name,
+    double timestamp,
+    const char* text
+);
+
+typedef int* lognplot_udp_client_t;
+
+/*
+    Create a new client which sends data in UDP datagrams.
+
+    Datagrams may get lost, and are not retransmitted.
+
+    \param address the address of the server, which must listen for UDP.
+*/
+lognplot_udp_client_t* lognplot_udp_client_new(const char* address);
+
+/*
+  Close the UDP client.
+*/
+lognplot_result_t lognplot_udp_client_close(lognplot_udp_client_t* client);
+
+/*
+    Send a single sample in a datagram.
+
+    \param client the UDP client pointer.
+    \param name the name of the signal
+    \param timestamp the timestamp of the sample
+    \param value the value of the sample
+*/
+lognplot_result_t lognplot_udp_client_send_sample(
+    lognplot_udp_client_t* client,
+    const char* name,
+    double timestamp,
+    double value
+);
+
+/*
+    Send a batch of samples in a single datagram.
+
+    The batch must fit in a datagram.
+*/
+lognplot_result_t lognplot_udp_client_send_samples(
+    lognplot_udp_client_t* client,
+    const char* name,
+    const size_t count,
+    double* timestamps,
+    double* values
+);
+
+/*
+    Send a text event in a datagram.
+*/
+lognplot_result_t lognplot_udp_client_send_text(
+    lognplot_udp_client_t* client,
+    const char* name,
+    double timestamp,
+    const char* text
+);
+
 #endif
@@ -18,6 +18,14 @@ namespace Lognplot
             }
         }
 
+        public void Hello(string name)
+        {
+            if (lognplot_client_hello(handle, name) != 0)
+            {
+                throw new Exception("Handshake with lognplot GUI failed");
+            }
+        }
+
         public void Disconnect()
         {
             lognplot_client_close(handle);
@@ -59,6 +67,9 @@ namespace Lognplot
         [DllImport("clognplot.dll")]
         private static extern IntPtr lognplot_client_new(string address);
 
+        [DllImport("clognplot.dll")]
+        private static extern uint lognplot_client_hello(IntPtr handle, string name);
+
         [DllImport("clognplot.dll")]
         private static extern IntPtr lognplot_client_close(IntPtr handle);
 
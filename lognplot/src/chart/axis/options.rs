@@ -6,6 +6,10 @@ pub struct AxisOptions {
 
     /// Draw minor tick markers
     pub minor_ticks: bool,
+
+    /// The axis values are the base 10 logarithm of the
+    /// shown quantity, ticks are labelled accordingly.
+    pub log_scale: bool,
 }
 
 /// Implement sensible default axis options.
@@ -14,6 +18,7 @@ impl Default for AxisOptions {
         AxisOptions {
             major_ticks: true,
             minor_ticks: false,
+            log_scale: false,
         }
     }
 }
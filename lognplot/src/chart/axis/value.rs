@@ -104,6 +104,10 @@ impl ValueAxis {
 
     /// Retrieve a good value for a cursor label!
     pub fn get_cursor_label(&self, ts: &TimeStamp) -> String {
+        if self.options.log_scale {
+            return format_log_value(ts.amount);
+        }
+
         let domain = self.range.end() - self.range.begin();
         let scale = get_scale(domain);
         let t = ts.amount;
@@ -120,6 +124,10 @@ impl ValueAxis {
     /// and a set of tick labels.
     pub fn calc_date_tiks(&self, n_ticks: usize) -> (Option<String>, TickLabels) {
         let begin = self.range.begin();
+        if self.options.log_scale {
+            return (None, calc_log_tiks(begin, self.range.end(), n_ticks));
+        }
+
         // If time in some range between 1973 and 2096, use data time stuff:
         if 1.0e8 < begin && begin < 4.0e9 {
             let (prefix, labels) = calc_date_ticks(begin, self.range.end(), n_ticks);
@@ -153,6 +161,38 @@ fn calc_tiks(begin: f64, end: f64, n_ticks: usize) -> TickLabels {
     res
 }
 
+/// Calculate ticks for a logarithmic axis.
+///
+/// Ticks are placed at powers of ten, when the range
+/// spans too few decades, regular ticks are used.
+fn calc_log_tiks(begin: f64, end: f64, n_ticks: usize) -> TickLabels {
+    let first = begin.ceil() as i32;
+    let last = end.floor() as i32;
+    if last > first {
+        let decades = (last - first + 1) as usize;
+        let step = (decades / n_ticks.max(1)).max(1);
+        (first..=last)
+            .step_by(step)
+            .map(|d| (d as f64, format_log_value(d as f64)))
+            .collect()
+    } else {
+        calc_tiks(begin, end, n_ticks)
+            .into_iter()
+            .map(|(x, _)| (x, format_log_value(x)))
+            .collect()
+    }
+}
+
+/// Format the value of which x is the logarithm.
+fn format_log_value(x: f64) -> String {
+    let value = 10.0_f64.powf(x);
+    if x.fract() == 0.0 {
+        format!("{}", value)
+    } else {
+        format!("{:.3}", value)
+    }
+}
+
 fn create_points(start: f64, end: f64, step: f64) -> Vec<f64> {
     let mut res = vec![];
     let mut x = start;
@@ -187,6 +227,23 @@ mod tests {
         compare_ticks(expected_ticks, ticks);
     }
 
+    #[test]
+    fn log_tick_calculation() {
+        let mut axis = ValueAxis::default();
+        axis.options.log_scale = true;
+        axis.set_limits(-0.5, 3.2);
+        let (prefix, ticks) = axis.calc_date_tiks(7);
+        assert!(prefix.is_none());
+
+        let expected_ticks = vec![
+            (0.0, "1".to_string()),
+            (1.0, "10".to_string()),
+            (2.0, "100".to_string()),
+            (3.0, "1000".to_string()),
+        ];
+        compare_ticks(expected_ticks, ticks);
+    }
+
     #[test]
     fn tick_calculation_negative() {
         // Set axis to -44 to 46 and expect ticks at -40, -30, -20, -10, 0, 10, 20, 30 and 40.
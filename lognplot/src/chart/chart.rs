@@ -5,7 +5,7 @@ use super::curve::Curve;
 use super::Cursor;
 use crate::geometry::Range;
 use crate::time::{TimeSpan, TimeStamp};
-use crate::tsdb::Summary;
+use crate::tsdb::{Annotation, SearchDirection, SpectrumOptions, Summary, TsDbHandle};
 
 /// A single 2D-chart
 pub struct Chart {
@@ -29,6 +29,9 @@ pub struct Chart {
 
     /// Horizontal cursor 2 for measuring
     pub cursor2: Option<TimeStamp>,
+
+    /// Database from which annotations are shown, if any.
+    annotations: Option<TsDbHandle>,
 }
 
 impl Default for Chart {
@@ -42,6 +45,7 @@ impl Default for Chart {
             cursor: None,
             cursor1: None,
             cursor2: None,
+            annotations: None,
         }
     }
 }
@@ -60,6 +64,20 @@ impl Chart {
         self.y_axis.label = Some(label.to_string());
     }
 
+    /// Show the annotations of the given database in this chart.
+    pub fn show_annotations(&mut self, db: TsDbHandle) {
+        self.annotations = Some(db);
+    }
+
+    /// Retrieve the annotations which are in view.
+    pub fn get_annotations(&self) -> Vec<Annotation> {
+        if let Some(db) = &self.annotations {
+            db.get_annotations(Some(&self.x_axis.timespan()))
+        } else {
+            vec![]
+        }
+    }
+
     /// Drop a new curve into the mix!
     pub fn add_curve(&mut self, curve: Curve) {
         self.curves.push(curve);
@@ -84,6 +102,81 @@ impl Chart {
         }
     }
 
+    /// Move cursor 1 to the next or previous crossing of the level under the mouse cursor.
+    ///
+    /// The search starts at cursor 1, or at the mouse cursor if cursor 1 is not
+    /// set, and looks at the first curve in the chart.
+    /// Returns true when a crossing was found.
+    pub fn jump_to_crossing(&mut self, direction: SearchDirection) -> bool {
+        let (cursor_timestamp, level) = if let Some(cursor) = &self.cursor {
+            cursor.clone()
+        } else {
+            return false;
+        };
+        let start = self.cursor1.clone().unwrap_or(cursor_timestamp);
+
+        let data_span = if let Some(summary) = self.data_summary(None) {
+            summary.timespan
+        } else {
+            return false;
+        };
+
+        // Stay clear of a crossing right at the start point:
+        let margin = self.x_axis.domain() * 1.0e-6;
+        let timespan = match direction {
+            SearchDirection::Forward => TimeSpan::new(start + margin, data_span.end),
+            SearchDirection::Backward => TimeSpan::new(data_span.start, start - margin),
+        };
+        if timespan.start > timespan.end {
+            return false;
+        }
+
+        // The cursor level is in displayed units, search in raw units:
+        let found = self.curves.first().and_then(|curve| {
+            let level = curve.data.raw_level(level)?;
+            curve.data.find_crossing(level, &timespan, direction)
+        });
+
+        if let Some(timestamp) = found {
+            self.show_timestamp(&timestamp);
+            self.cursor1 = Some(timestamp);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Place cursor 1 at the minimum and cursor 2 at the maximum
+    /// of the first curve in the visible time range.
+    pub fn mark_extremes(&mut self) {
+        let timespan = self.x_axis.timespan();
+        if let Some(extremes) = self
+            .curves
+            .first()
+            .and_then(|curve| curve.data.find_extremes(&timespan))
+        {
+            self.cursor1 = Some(extremes.min.timestamp);
+            self.cursor2 = Some(extremes.max.timestamp);
+        }
+    }
+
+    /// Show the given moment, and mark it with cursor 1.
+    pub fn jump_to(&mut self, timestamp: TimeStamp) {
+        self.show_timestamp(&timestamp);
+        self.cursor1 = Some(timestamp);
+    }
+
+    /// Pan the x-axis such that the given moment is in view.
+    fn show_timestamp(&mut self, timestamp: &TimeStamp) {
+        if !self.x_axis.contains(timestamp) {
+            let half_domain = self.x_axis.domain() / 2.0;
+            self.x_axis.set_limits(
+                timestamp.amount - half_domain,
+                timestamp.amount + half_domain,
+            );
+        }
+    }
+
     /// Zoom horizontally.
     pub fn zoom_horizontal(&mut self, amount: f64, around: Option<f64>) {
         self.x_axis.zoom(amount, around);
@@ -181,6 +274,29 @@ impl Chart {
         }
     }
 
+    /// Create a chart with the amplitude spectra of the signals in this chart.
+    ///
+    /// The spectra are calculated over the time range in view, and
+    /// plotted against frequency on a logarithmic axis.
+    pub fn spectrum_chart(&self, options: &SpectrumOptions) -> Chart {
+        let mut chart = Chart::default();
+        if let Some(title) = &self.title {
+            chart.set_title(&format!("{} spectrum", title));
+        }
+        chart.x_axis.options.log_scale = true;
+        chart.set_xlabel("Frequency [Hz]");
+        chart.set_ylabel("Amplitude");
+
+        let timespan = self.x_axis.timespan();
+        for curve in &self.curves {
+            if let Some(spectrum) = curve.spectrum(&timespan, options) {
+                chart.add_curve(spectrum);
+            }
+        }
+        chart.autoscale();
+        chart
+    }
+
     pub fn has_signal(&self, name: &str) -> bool {
         self.curves.iter().any(|c| c.name() == name)
     }
@@ -202,6 +318,10 @@ impl ChartDataSummary {
             for summary in summaries {
                 timespan.extend_to_include_span(summary.timespan());
                 if let Summary::Value(value_summary) = summary {
+                    // Signals with only NaN values have no range:
+                    if value_summary.metrics().count == 0 {
+                        continue;
+                    }
                     let min_value = value_summary.metrics().min;
                     let max_value = value_summary.metrics().max;
                     metrics.push((min_value, max_value));
@@ -223,3 +343,42 @@ impl ChartDataSummary {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Chart;
+    use crate::chart::curve::{Curve, CurveData};
+    use crate::time::TimeStamp;
+    use crate::tsdb::observations::{Observation, Sample};
+    use crate::tsdb::{SearchDirection, SignalMetadata, TsDb};
+
+    #[test]
+    fn jump_to_crossing_of_scaled_signal() {
+        let db = TsDb::default().into_handle();
+        let samples = (0..10)
+            .map(|t| Observation::new(TimeStamp::new(t as f64), Sample::new(t as f64)))
+            .collect();
+        db.add_values("foo", samples);
+        let mut metadata = SignalMetadata {
+            scale: 2.0,
+            offset: 1.0,
+            ..SignalMetadata::default()
+        };
+        db.set_metadata("foo", metadata.clone());
+
+        let mut chart = Chart::default();
+        chart.add_curve(Curve::new(CurveData::trace("foo", db.clone()), "blue"));
+
+        // Displayed level 11.0 is raw level 5.0:
+        chart.cursor = Some((TimeStamp::new(0.0), 11.0));
+        assert!(chart.jump_to_crossing(SearchDirection::Forward));
+        assert_eq!(chart.cursor1, Some(TimeStamp::new(5.0)));
+
+        // A signal displayed with zero scale has no crossing to find:
+        metadata.scale = 0.0;
+        db.set_metadata("foo", metadata);
+        chart.cursor1 = None;
+        assert!(!chart.jump_to_crossing(SearchDirection::Forward));
+        assert_eq!(chart.cursor1, None);
+    }
+}
@@ -2,7 +2,8 @@ use crate::geometry::Point;
 use crate::style::{Color, Stroke};
 use crate::time::{TimeSpan, TimeStamp};
 use crate::tsdb::observations::{Aggregation, Observation, Sample, SampleMetrics};
-use crate::tsdb::{Query, QueryResult, RangeQueryResult, Summary, TsDbHandle};
+use crate::tsdb::{CrossingEdge, Extremes, SearchDirection, Spectrum, SpectrumOptions};
+use crate::tsdb::{Query, QueryResult, RangeQueryResult, SignalMetadata, Summary, TsDbHandle};
 use std::str::FromStr;
 
 /// A single curve with some stroke styling.
@@ -25,6 +26,10 @@ pub enum CurveData {
 
     /// Raw points.
     Points(Vec<Point>),
+
+    /// Amplitude spectrum of a signal, with the base 10
+    /// logarithm of the frequency as x value.
+    Spectrum { name: String, points: Vec<Point> },
 }
 
 impl CurveData {
@@ -43,13 +48,46 @@ impl CurveData {
             db,
         }
     }
+
+    /// Create curve data from the spectrum of a signal.
+    ///
+    /// The DC component is left out, since it has no place on a logarithmic axis.
+    pub fn spectrum(name: &str, spectrum: &Spectrum) -> Self {
+        let points = spectrum
+            .bins()
+            .skip(1)
+            .map(|(frequency, magnitude)| Point::new(frequency.log10(), magnitude))
+            .collect();
+        CurveData::Spectrum {
+            name: name.to_string(),
+            points,
+        }
+    }
 }
 
 impl CurveData {
     pub fn name(&self) -> String {
         match self {
             CurveData::Points(..) => "no-name".to_string(),
-            CurveData::Trace { name, .. } => name.clone(),
+            CurveData::Trace { name, .. } | CurveData::Spectrum { name, .. } => name.clone(),
+        }
+    }
+
+    /// Retrieve meta data of the signal, if any.
+    pub fn metadata(&self) -> Option<SignalMetadata> {
+        match self {
+            CurveData::Points(..) | CurveData::Spectrum { .. } => None,
+            CurveData::Trace { name, db } => db.get_metadata(name),
+        }
+    }
+
+    /// Convert a level in displayed units into the raw units of the data.
+    ///
+    /// Returns `None` when the signal is displayed with a scale of zero.
+    pub fn raw_level(&self, level: f64) -> Option<f64> {
+        match self.metadata() {
+            Some(metadata) => metadata.raw_value(level),
+            None => Some(level),
         }
     }
 
@@ -57,7 +95,7 @@ impl CurveData {
     pub fn query(&self, timespan: &TimeSpan, amount: usize) -> Option<QueryResult> {
         match self {
             // In case of raw data, just return them all.
-            CurveData::Points(points) => {
+            CurveData::Points(points) | CurveData::Spectrum { points, .. } => {
                 let observations = points
                     .iter()
                     .map(|p| Observation::new(TimeStamp::new(p.x()), Sample::new(p.y())))
@@ -77,16 +115,57 @@ impl CurveData {
         }
     }
 
+    /// Find a crossing of the given level.
+    ///
+    /// Searching is only supported on database traces.
+    pub fn find_crossing(
+        &self,
+        level: f64,
+        timespan: &TimeSpan,
+        direction: SearchDirection,
+    ) -> Option<TimeStamp> {
+        match self {
+            CurveData::Points(..) | CurveData::Spectrum { .. } => None,
+            CurveData::Trace { name, db } => db
+                .find_crossing(name, level, CrossingEdge::Any, timespan, direction)
+                .map(|o| o.timestamp),
+        }
+    }
+
+    /// Locate minimum and maximum values.
+    pub fn find_extremes(&self, timespan: &TimeSpan) -> Option<Extremes> {
+        match self {
+            CurveData::Points(..) | CurveData::Spectrum { .. } => None,
+            CurveData::Trace { name, db } => db.find_extremes(name, timespan),
+        }
+    }
+
     fn summary(&self, timespan: Option<&TimeSpan>) -> Option<Summary> {
         match &self {
-            CurveData::Points(points) => {
+            CurveData::Points(points) | CurveData::Spectrum { points, .. } => {
                 if timespan.is_some() {
                     None // TODO??
                 } else {
                     point_summary(points)
                 }
             }
-            CurveData::Trace { name, db } => db.summary(name, timespan),
+            CurveData::Trace { name, db } => {
+                // Fit the axis to the values as they are displayed:
+                let summary = db.summary(name, timespan)?;
+                match (summary, db.get_metadata(name)) {
+                    (Summary::Value(aggregation), Some(metadata)) => {
+                        let metrics = aggregation
+                            .metrics()
+                            .scaled(metadata.scale, metadata.offset);
+                        Some(Summary::Value(Aggregation::new(
+                            aggregation.timespan,
+                            metrics,
+                            aggregation.count,
+                        )))
+                    }
+                    (summary, _) => Some(summary),
+                }
+            }
         }
     }
 }
@@ -141,6 +220,22 @@ impl Curve {
         self.data.name()
     }
 
+    /// Calculate the amplitude spectrum of this curve within a timespan.
+    ///
+    /// Only database traces have a spectrum.
+    pub fn spectrum(&self, timespan: &TimeSpan, options: &SpectrumOptions) -> Option<Curve> {
+        match &self.data {
+            CurveData::Trace { name, db } => {
+                let spectrum = db.spectrum(name, timespan, options)?;
+                Some(Curve {
+                    data: CurveData::spectrum(name, &spectrum),
+                    stroke: self.stroke.clone(),
+                })
+            }
+            _ => None,
+        }
+    }
+
     /// Retrieve a data summary of this curve.
     pub fn data_summary(&self, timespan: Option<&TimeSpan>) -> Option<Summary> {
         self.data.summary(timespan)
@@ -0,0 +1,204 @@
+//! Client for use in async code.
+//!
+//! This uses the same framing as the server, so it never blocks the
+//! executor it runs on.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures::{Sink, SinkExt, StreamExt};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+#[cfg(unix)]
+use tokio::net::UnixStream;
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+use super::client::{hello_request, welcome_to_session};
+use super::compression::Compression;
+use super::payload::SampleBatch;
+use super::protocol::Session;
+use crate::tsdb::{Annotation, SignalMetadata};
+
+/// An async client to send logging events.
+///
+/// Next to the send methods, the client is a sink of sample batches,
+/// so a stream of batches can be forwarded to it.
+pub struct AsyncClient<S = TcpStream> {
+    framed: Framed<S, LengthDelimitedCodec>,
+    compression: Option<Compression>,
+    token: Option<String>,
+}
+
+impl AsyncClient<TcpStream> {
+    pub async fn connect(addr: &str) -> std::io::Result<Self> {
+        let stream = TcpStream::connect(addr).await?;
+        Ok(AsyncClient::from_stream(stream))
+    }
+}
+
+#[cfg(unix)]
+impl AsyncClient<UnixStream> {
+    /// Connect to a server via the unix domain socket at the given path.
+    pub async fn connect_unix<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Self> {
+        let stream = UnixStream::connect(path).await?;
+        Ok(AsyncClient::from_stream(stream))
+    }
+}
+
+impl<S> AsyncClient<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Use an already established connection.
+    pub fn from_stream(stream: S) -> Self {
+        AsyncClient {
+            framed: Framed::new(stream, LengthDelimitedCodec::new()),
+            compression: None,
+            token: None,
+        }
+    }
+
+    /// Set the token to present in the hello, for servers which require one.
+    pub fn set_token(&mut self, token: &str) {
+        self.token = Some(token.to_owned());
+    }
+
+    /// Introduce this client to the server, and agree on a protocol version.
+    ///
+    /// Servers from before the handshake never answer, so only call this
+    /// when the server is known to support it.
+    pub async fn hello(&mut self, name: &str) -> std::io::Result<Session> {
+        self.introduce(name, None).await
+    }
+
+    /// Introduce this client, and ask for compression of all further packets.
+    ///
+    /// When the server does not support the compression, or the compression
+    /// feature was not enabled, the connection continues without it.
+    pub async fn hello_compressed(
+        &mut self,
+        name: &str,
+        compression: Compression,
+    ) -> std::io::Result<Session> {
+        self.introduce(name, Some(compression)).await
+    }
+
+    async fn introduce(
+        &mut self,
+        name: &str,
+        compression: Option<Compression>,
+    ) -> std::io::Result<Session> {
+        let hello = hello_request(name, compression, self.token.clone());
+        self.send_blob(hello.to_bytes()).await?;
+
+        while let Some(data) = self.framed.next().await {
+            if let Some(result) = welcome_to_session(&data?) {
+                let session = result?;
+                self.compression = Compression::from_session(&session);
+                return Ok(session);
+            }
+        }
+
+        Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "Connection closed before the welcome",
+        ))
+    }
+
+    /// Transmit a single sample.
+    pub async fn send_sample(
+        &mut self,
+        name: &str,
+        timestamp: f64,
+        value: f64,
+    ) -> std::io::Result<()> {
+        let payload = SampleBatch::new_sample(name.to_owned(), timestamp, value);
+        self.send(payload).await
+    }
+
+    /// Transmit a batch of samples.
+    pub async fn send_samples(
+        &mut self,
+        name: &str,
+        samples: Vec<(f64, f64)>,
+    ) -> std::io::Result<()> {
+        let payload = SampleBatch::new_samples(name.to_owned(), samples);
+        self.send(payload).await
+    }
+
+    /// Send a batch of equally spaced samples.
+    pub async fn send_sampled_samples(
+        &mut self,
+        name: &str,
+        t0: f64,
+        dt: f64,
+        values: Vec<f64>,
+    ) -> std::io::Result<()> {
+        let payload = SampleBatch::new_sampled_data(name.to_owned(), t0, dt, values);
+        self.send(payload).await
+    }
+
+    /// Send a single text event
+    pub async fn send_text(
+        &mut self,
+        name: &str,
+        timestamp: f64,
+        text: String,
+    ) -> std::io::Result<()> {
+        let payload = SampleBatch::new_text(name.to_owned(), timestamp, text);
+        self.send(payload).await
+    }
+
+    /// Send meta data, such as the unit, of a signal.
+    pub async fn send_metadata(
+        &mut self,
+        name: &str,
+        metadata: &SignalMetadata,
+    ) -> std::io::Result<()> {
+        let payload = SampleBatch::new_metadata(name.to_owned(), metadata);
+        self.send(payload).await
+    }
+
+    /// Send an annotation, which marks a moment or a period on the time line.
+    pub async fn send_annotation(&mut self, annotation: &Annotation) -> std::io::Result<()> {
+        let payload = SampleBatch::new_annotation(annotation);
+        self.send(payload).await
+    }
+
+    async fn send_blob(&mut self, data: Vec<u8>) -> std::io::Result<()> {
+        let data = self.encode(data);
+        self.framed.send(data).await
+    }
+
+    fn encode(&self, data: Vec<u8>) -> Bytes {
+        match self.compression {
+            Some(compression) => Bytes::from(compression.compress(&data)),
+            None => Bytes::from(data),
+        }
+    }
+}
+
+impl<S> Sink<SampleBatch> for AsyncClient<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    type Error = std::io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.framed).poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: SampleBatch) -> std::io::Result<()> {
+        let data = self.encode(item.to_bytes());
+        Pin::new(&mut self.framed).start_send(data)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.framed).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.framed).poll_close(cx)
+    }
+}
@@ -0,0 +1,486 @@
+//! A client which sends data from a background thread.
+//!
+//! Samples are queued, coalesced per signal into batches, and written
+//! by a worker thread. When the connection is lost, the worker keeps
+//! reconnecting, while the queue fills up to its limit. The metadata of
+//! signals is sent again on each new connection, since a restarted server
+//! has forgotten it.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::client::TcpClient;
+use super::compression::Compression;
+use super::payload::SampleBatch;
+use crate::tsdb::{Annotation, SignalMetadata};
+
+/// What to do when the queue is full.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DropPolicy {
+    /// Discard the data which is being sent.
+    DropNewest,
+
+    /// Discard the oldest data in the queue, to make room.
+    DropOldest,
+
+    /// Wait until there is room in the queue.
+    Block,
+}
+
+/// Tuning of the buffered client.
+#[derive(Debug, Clone)]
+pub struct BufferedClientOptions {
+    /// Amount of samples of a signal which are sent in a single batch.
+    pub max_batch_size: usize,
+
+    /// Longest time data is held back before it is sent.
+    pub flush_interval: Duration,
+
+    /// Amount of events which can be queued.
+    pub queue_capacity: usize,
+
+    pub drop_policy: DropPolicy,
+
+    /// Time to wait before the first reconnect attempt.
+    pub min_backoff: Duration,
+
+    /// Longest time to wait between reconnect attempts.
+    pub max_backoff: Duration,
+
+    /// Ask the server for compression of the connection.
+    pub compression: Option<Compression>,
+
+    /// Token to present to servers which require one.
+    pub token: Option<String>,
+}
+
+impl Default for BufferedClientOptions {
+    fn default() -> Self {
+        BufferedClientOptions {
+            max_batch_size: 1000,
+            flush_interval: Duration::from_millis(50),
+            queue_capacity: 100_000,
+            drop_policy: DropPolicy::DropOldest,
+            min_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            compression: None,
+            token: None,
+        }
+    }
+}
+
+/// A client which batches data, and sends it from a background thread.
+///
+/// Sending never fails. Instead, data is dropped according to the drop
+/// policy when the queue is full, for example because the server is
+/// gone. The connection is restored automatically.
+pub struct BufferedClient {
+    shared: Arc<Shared>,
+    worker: Option<thread::JoinHandle<()>>,
+}
+
+impl BufferedClient {
+    /// Create a client which connects to the given address in the background.
+    pub fn new(addr: &str) -> Self {
+        BufferedClient::with_options(addr, BufferedClientOptions::default())
+    }
+
+    pub fn with_options(addr: &str, options: BufferedClientOptions) -> Self {
+        let shared = Arc::new(Shared::new(
+            options.queue_capacity.max(1),
+            options.drop_policy,
+        ));
+        let worker = {
+            let shared = shared.clone();
+            let addr = addr.to_owned();
+            thread::Builder::new()
+                .name("lognplot-client".to_owned())
+                .spawn(move || worker_prog(&addr, &options, &shared))
+                .expect("Failed to start client thread")
+        };
+
+        BufferedClient {
+            shared,
+            worker: Some(worker),
+        }
+    }
+
+    pub fn send_sample(&self, name: &str, timestamp: f64, value: f64) {
+        self.shared
+            .enqueue(Event::Sample(name.to_owned(), timestamp, value));
+    }
+
+    pub fn send_samples(&self, name: &str, samples: Vec<(f64, f64)>) {
+        self.shared
+            .enqueue(Event::Samples(name.to_owned(), samples));
+    }
+
+    pub fn send_sampled_samples(&self, name: &str, t0: f64, dt: f64, values: Vec<f64>) {
+        let payload = SampleBatch::new_sampled_data(name.to_owned(), t0, dt, values);
+        self.shared.enqueue(Event::Packet(payload));
+    }
+
+    pub fn send_text(&self, name: &str, timestamp: f64, text: String) {
+        let payload = SampleBatch::new_text(name.to_owned(), timestamp, text);
+        self.shared.enqueue(Event::Packet(payload));
+    }
+
+    pub fn send_metadata(&self, name: &str, metadata: &SignalMetadata) {
+        let payload = SampleBatch::new_metadata(name.to_owned(), metadata);
+        self.shared
+            .lock()
+            .metadata
+            .insert(name.to_owned(), payload.clone());
+        self.shared.enqueue(Event::Packet(payload));
+    }
+
+    pub fn send_annotation(&self, annotation: &Annotation) {
+        let payload = SampleBatch::new_annotation(annotation);
+        self.shared.enqueue(Event::Packet(payload));
+    }
+
+    /// Send a batch as it is, without coalescing it with other data.
+    pub fn send_batch(&self, batch: SampleBatch) {
+        self.shared.enqueue(Event::Packet(batch));
+    }
+
+    /// Send all held back data now, instead of waiting for the flush interval.
+    pub fn flush(&self) {
+        let mut state = self.shared.lock();
+        state.flush = true;
+        self.shared.changed.notify_one();
+    }
+
+    /// Test if the client is connected to the server.
+    pub fn is_connected(&self) -> bool {
+        self.shared.connected.load(Ordering::Relaxed)
+    }
+
+    /// Amount of events dropped because the queue was full.
+    pub fn dropped(&self) -> usize {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Send the remaining data, if connected, and stop the worker thread.
+    pub fn close(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        {
+            let mut state = self.shared.lock();
+            state.stop = true;
+            self.shared.changed.notify_one();
+            self.shared.space.notify_all();
+        }
+        if let Some(worker) = self.worker.take() {
+            if worker.join().is_err() {
+                error!("Client thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for BufferedClient {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+enum Event {
+    Sample(String, f64, f64),
+    Samples(String, Vec<(f64, f64)>),
+
+    /// Anything which is not coalesced.
+    Packet(SampleBatch),
+}
+
+/// State shared between the client and its worker thread.
+struct Shared {
+    state: Mutex<State>,
+
+    /// Signalled when there is something for the worker to do.
+    changed: Condvar,
+
+    /// Signalled when room became available in the queue.
+    space: Condvar,
+
+    capacity: usize,
+    drop_policy: DropPolicy,
+    dropped: AtomicUsize,
+    connected: AtomicBool,
+}
+
+#[derive(Default)]
+struct State {
+    queue: VecDeque<Event>,
+    flush: bool,
+    stop: bool,
+
+    /// The latest metadata of each signal, to send on each new connection.
+    metadata: HashMap<String, SampleBatch>,
+}
+
+impl Shared {
+    fn new(capacity: usize, drop_policy: DropPolicy) -> Self {
+        Shared {
+            state: Mutex::new(State::default()),
+            changed: Condvar::new(),
+            space: Condvar::new(),
+            capacity,
+            drop_policy,
+            dropped: AtomicUsize::new(0),
+            connected: AtomicBool::new(false),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    fn enqueue(&self, event: Event) {
+        let mut state = self.lock();
+        while state.queue.len() >= self.capacity {
+            match self.drop_policy {
+                DropPolicy::DropNewest => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                DropPolicy::DropOldest => {
+                    state.queue.pop_front();
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                DropPolicy::Block => {
+                    if state.stop {
+                        return;
+                    }
+                    state = self.space.wait(state).unwrap();
+                }
+            }
+        }
+
+        // The worker only sleeps on an empty queue:
+        if state.queue.is_empty() {
+            self.changed.notify_one();
+        }
+        state.queue.push_back(event);
+    }
+}
+
+/// Coalesces samples per signal, and keeps the packets ready to be sent.
+struct Batcher {
+    max_batch_size: usize,
+    pending: HashMap<String, Vec<(f64, f64)>>,
+    outgoing: VecDeque<SampleBatch>,
+}
+
+impl Batcher {
+    fn new(max_batch_size: usize) -> Self {
+        Batcher {
+            max_batch_size: max_batch_size.max(1),
+            pending: HashMap::new(),
+            outgoing: VecDeque::new(),
+        }
+    }
+
+    fn push(&mut self, event: Event) {
+        match event {
+            Event::Sample(name, t, value) => {
+                self.add_samples(name, std::iter::once((t, value)));
+            }
+            Event::Samples(name, samples) => {
+                self.add_samples(name, samples);
+            }
+            Event::Packet(packet) => {
+                // Keep the order of events:
+                self.flush();
+                self.outgoing.push_back(packet);
+            }
+        }
+    }
+
+    fn add_samples<I>(&mut self, name: String, samples: I)
+    where
+        I: IntoIterator<Item = (f64, f64)>,
+    {
+        let pending = self.pending.entry(name.clone()).or_default();
+        pending.extend(samples);
+        if pending.len() >= self.max_batch_size {
+            let samples = std::mem::take(pending);
+            self.pending.remove(&name);
+            for chunk in samples.chunks(self.max_batch_size) {
+                self.outgoing
+                    .push_back(SampleBatch::new_samples(name.clone(), chunk.to_vec()));
+            }
+        }
+    }
+
+    /// Turn all held back samples into packets.
+    fn flush(&mut self) {
+        for (name, samples) in self.pending.drain() {
+            self.outgoing
+                .push_back(SampleBatch::new_samples(name, samples));
+        }
+    }
+
+    /// Send the given packets before anything else.
+    fn send_first(&mut self, packets: Vec<SampleBatch>) {
+        for packet in packets.into_iter().rev() {
+            self.outgoing.push_front(packet);
+        }
+    }
+
+    /// Write the outgoing packets. Packets which could not be sent are kept.
+    fn send(&mut self, client: &mut TcpClient) -> std::io::Result<()> {
+        while let Some(packet) = self.outgoing.front() {
+            client.write_blob(packet.to_bytes())?;
+            self.outgoing.pop_front();
+        }
+        Ok(())
+    }
+}
+
+fn connect(addr: &str, options: &BufferedClientOptions) -> std::io::Result<TcpClient> {
+    let mut client = TcpClient::new(addr)?;
+    if let Some(token) = &options.token {
+        client.set_token(token);
+    }
+    let name = "lognplot buffered client";
+    if let Some(compression) = options.compression {
+        client.hello_compressed(name, compression)?;
+    } else if options.token.is_some() {
+        client.hello(name)?;
+    }
+    Ok(client)
+}
+
+fn worker_prog(addr: &str, options: &BufferedClientOptions, shared: &Shared) {
+    let mut batcher = Batcher::new(options.max_batch_size);
+    let mut connection: Option<TcpClient> = None;
+    let mut backoff = options.min_backoff;
+    let mut deadline = Instant::now() + options.flush_interval;
+
+    loop {
+        let mut client = if let Some(client) = connection.take() {
+            client
+        } else {
+            match connect(addr, options) {
+                Ok(client) => {
+                    info!("Connected to {}", addr);
+                    shared.connected.store(true, Ordering::Relaxed);
+                    backoff = options.min_backoff;
+                    let metadata = shared.lock().metadata.values().cloned().collect();
+                    batcher.send_first(metadata);
+                    client
+                }
+                Err(err) => {
+                    debug!("Connecting to {} failed: {}", addr, err);
+                    let state = shared.lock();
+                    if state.stop {
+                        break;
+                    }
+                    let (state, _) = shared.changed.wait_timeout(state, backoff).unwrap();
+                    if state.stop {
+                        break;
+                    }
+                    backoff = (backoff * 2).min(options.max_backoff);
+                    continue;
+                }
+            }
+        };
+
+        let (events, flush, stop) = {
+            let mut state = shared.lock();
+            loop {
+                let now = Instant::now();
+                if !state.queue.is_empty() || state.flush || state.stop || now >= deadline {
+                    break;
+                }
+                state = shared
+                    .changed
+                    .wait_timeout(state, deadline - now)
+                    .unwrap()
+                    .0;
+            }
+            let events: Vec<Event> = state.queue.drain(..).collect();
+            let flush = std::mem::replace(&mut state.flush, false);
+            shared.space.notify_all();
+            (events, flush, state.stop)
+        };
+
+        for event in events {
+            batcher.push(event);
+        }
+
+        if flush || stop || Instant::now() >= deadline {
+            batcher.flush();
+            deadline = Instant::now() + options.flush_interval;
+        }
+
+        if let Err(err) = batcher.send(&mut client) {
+            warn!("Connection to {} lost: {}", addr, err);
+            shared.connected.store(false, Ordering::Relaxed);
+        } else {
+            connection = Some(client);
+        }
+
+        if stop {
+            break;
+        }
+    }
+
+    if let Some(client) = connection {
+        if let Err(err) = client.close() {
+            debug!("Error closing connection: {}", err);
+        }
+    }
+    shared.connected.store(false, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Batcher, DropPolicy, Event, Shared};
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn samples_are_coalesced() {
+        let mut batcher = Batcher::new(3);
+        batcher.push(Event::Sample("a".to_owned(), 1.0, 1.0));
+        batcher.push(Event::Sample("b".to_owned(), 1.0, 1.0));
+        batcher.push(Event::Sample("a".to_owned(), 2.0, 2.0));
+        assert!(batcher.outgoing.is_empty());
+
+        // A full batch is sent right away:
+        batcher.push(Event::Samples("a".to_owned(), vec![(3.0, 3.0), (4.0, 4.0)]));
+        assert_eq!(batcher.outgoing.len(), 2);
+        assert_eq!(batcher.outgoing[0].values().len(), 3);
+        assert_eq!(batcher.outgoing[1].values(), vec![(4.0, 4.0)]);
+
+        batcher.flush();
+        assert_eq!(batcher.outgoing.len(), 3);
+        assert_eq!(batcher.outgoing[2].name(), "b");
+        assert!(batcher.pending.is_empty());
+    }
+
+    #[test]
+    fn drop_policy() {
+        let shared = Shared::new(2, DropPolicy::DropOldest);
+        for t in 0..5 {
+            shared.enqueue(Event::Sample("a".to_owned(), t as f64, 0.0));
+        }
+        assert_eq!(shared.dropped.load(Ordering::Relaxed), 3);
+        let state = shared.lock();
+        assert_eq!(state.queue.len(), 2);
+        assert!(matches!(state.queue[0], Event::Sample(_, t, _) if t == 3.0));
+
+        let shared = Shared::new(2, DropPolicy::DropNewest);
+        for t in 0..5 {
+            shared.enqueue(Event::Sample("a".to_owned(), t as f64, 0.0));
+        }
+        assert_eq!(shared.dropped.load(Ordering::Relaxed), 3);
+        assert!(matches!(shared.lock().queue[1], Event::Sample(_, t, _) if t == 1.0));
+    }
+}
@@ -1,23 +1,135 @@
-use std::io::Write;
+use std::io::{Read, Write};
 use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
 
+use super::compact::SignalIds;
+use super::compression::Compression;
 use super::payload::SampleBatch;
+use super::protocol::{supported_features, Session, PROTOCOL_VERSION};
+use super::request::{Request, Response};
+#[cfg(feature = "tls")]
+use super::tls::{client_config, TlsClientOptions};
+use crate::tsdb::{Annotation, SignalMetadata};
+
+/// Prefix of an address which is the path of a unix domain socket.
+pub const UNIX_ADDRESS_PREFIX: &str = "unix:";
 
 /// A TCP client to send logging events over TCP.
+///
+/// Servers on the same host can also be reached via a unix domain socket,
+/// by giving an address like `unix:/tmp/lognplot.sock`.
 pub struct TcpClient {
-    stream: TcpStream,
+    stream: Stream,
+    signal_ids: SignalIds,
+    compression: Option<Compression>,
+    token: Option<String>,
 }
 
 impl TcpClient {
     pub fn new(addr: &str) -> std::io::Result<Self> {
-        let stream = TcpStream::connect(addr)?;
-        let client = TcpClient { stream };
-        Ok(client)
+        if let Some(path) = addr.strip_prefix(UNIX_ADDRESS_PREFIX) {
+            return TcpClient::connect_unix(path);
+        }
+        let stream = Stream::Tcp(TcpStream::connect(addr)?);
+        Ok(TcpClient::from_stream(stream))
+    }
+
+    /// Connect to a server via the unix domain socket at the given path.
+    #[cfg(unix)]
+    pub fn connect_unix<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Self> {
+        let stream = Stream::Unix(UnixStream::connect(path)?);
+        Ok(TcpClient::from_stream(stream))
+    }
+
+    #[cfg(not(unix))]
+    pub fn connect_unix<P: AsRef<std::path::Path>>(_path: P) -> std::io::Result<Self> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "Unix domain sockets are not supported on this platform",
+        ))
+    }
+
+    /// Connect to a server via an encrypted connection.
+    ///
+    /// The certificate of the server must be valid for the given server name.
+    #[cfg(feature = "tls")]
+    pub fn connect_tls(
+        addr: &str,
+        server_name: &str,
+        options: &TlsClientOptions,
+    ) -> std::io::Result<Self> {
+        let config = client_config(options)?;
+        let server_name = webpki::DNSNameRef::try_from_ascii_str(server_name).map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid server name")
+        })?;
+        let session = rustls::ClientSession::new(&std::sync::Arc::new(config), server_name);
+        let socket = TcpStream::connect(addr)?;
+        let stream = Stream::Tls(Box::new(rustls::StreamOwned::new(session, socket)));
+        Ok(TcpClient::from_stream(stream))
+    }
+
+    fn from_stream(stream: Stream) -> Self {
+        TcpClient {
+            stream,
+            signal_ids: SignalIds::default(),
+            compression: None,
+            token: None,
+        }
+    }
+
+    /// Set the token to present in the hello, for servers which require one.
+    pub fn set_token(&mut self, token: &str) {
+        self.token = Some(token.to_owned());
+    }
+
+    /// Introduce this client to the server, and agree on a protocol version.
+    ///
+    /// Servers from before the handshake never answer, so only call this
+    /// when the server is known to support it.
+    pub fn hello(&mut self, name: &str) -> std::io::Result<Session> {
+        self.introduce(name, None)
+    }
+
+    /// Introduce this client, and ask for compression of all further packets.
+    ///
+    /// When the server does not support the compression, or the compression
+    /// feature was not enabled, the connection continues without it.
+    pub fn hello_compressed(
+        &mut self,
+        name: &str,
+        compression: Compression,
+    ) -> std::io::Result<Session> {
+        self.introduce(name, Some(compression))
+    }
+
+    fn introduce(
+        &mut self,
+        name: &str,
+        compression: Option<Compression>,
+    ) -> std::io::Result<Session> {
+        let hello = hello_request(name, compression, self.token.clone());
+        self.write_blob(hello.to_bytes())?;
+
+        loop {
+            let data = self.read_blob()?;
+            if let Some(result) = welcome_to_session(&data) {
+                let session = result?;
+                self.compression = Compression::from_session(&session);
+                return Ok(session);
+            }
+        }
     }
 
     /// Close the connection gracefully.
     pub fn close(&self) -> std::io::Result<()> {
-        self.stream.shutdown(std::net::Shutdown::Both)
+        match &self.stream {
+            Stream::Tcp(stream) => stream.shutdown(std::net::Shutdown::Both),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.shutdown(std::net::Shutdown::Both),
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => stream.sock.shutdown(std::net::Shutdown::Both),
+        }
     }
 
     /// Transmit a single sample over tha wire.
@@ -52,13 +164,52 @@ impl TcpClient {
         self.write_sample_batch(payload)
     }
 
+    /// Send meta data, such as the unit, of a signal.
+    pub fn send_metadata(&mut self, name: &str, metadata: &SignalMetadata) -> std::io::Result<()> {
+        let payload = SampleBatch::new_metadata(name.to_owned(), metadata);
+        self.write_sample_batch(payload)
+    }
+
+    /// Send an annotation, which marks a moment or a period on the time line.
+    pub fn send_annotation(&mut self, annotation: &Annotation) -> std::io::Result<()> {
+        let payload = SampleBatch::new_annotation(annotation);
+        self.write_sample_batch(payload)
+    }
+
+    /// Set the time which timestamps of frames are encoded relative to.
+    ///
+    /// Choose a time near the samples, such as the start of the program,
+    /// to get small timestamps on the wire.
+    pub fn set_epoch(&mut self, epoch: f64) -> std::io::Result<()> {
+        let packet = self.signal_ids.set_epoch(epoch);
+        self.write_blob(packet.to_bytes())
+    }
+
+    /// Send samples of many signals in a single frame, as (name, timestamp, value).
+    ///
+    /// Signal names are sent only once per connection, afterwards signals
+    /// are referred to by a small id. The server must support the
+    /// `signal_ids` feature.
+    pub fn send_frame(&mut self, samples: &[(&str, f64, f64)]) -> std::io::Result<()> {
+        let mut definitions = vec![];
+        let frame = self.signal_ids.frame(samples, &mut definitions);
+        for definition in definitions {
+            self.write_blob(definition.to_bytes())?;
+        }
+        self.write_blob(frame.to_bytes())
+    }
+
     fn write_sample_batch(&mut self, payload: SampleBatch) -> std::io::Result<()> {
         let data = payload.to_bytes();
         self.write_blob(data)
     }
 
     /// Write a length prefixed blob of data.
-    fn write_blob(&mut self, data: Vec<u8>) -> std::io::Result<()> {
+    pub(crate) fn write_blob(&mut self, data: Vec<u8>) -> std::io::Result<()> {
+        let data = match self.compression {
+            Some(compression) => compression.compress(&data),
+            None => data,
+        };
         let mut header: [u8; 4] = [0; 4];
         let size: u32 = data.len() as u32;
         header[0] = ((size >> 24) & 0xff) as u8;
@@ -72,4 +223,109 @@ impl TcpClient {
     fn write(&mut self, buffer: &[u8]) -> std::io::Result<()> {
         self.stream.write_all(buffer)
     }
+
+    /// Read a length prefixed blob of data, sent by the server.
+    pub(crate) fn read_blob(&mut self) -> std::io::Result<Vec<u8>> {
+        let mut header: [u8; 4] = [0; 4];
+        self.stream.read_exact(&mut header)?;
+        let size = u32::from_be_bytes(header) as usize;
+        let mut data = vec![0; size];
+        self.stream.read_exact(&mut data)?;
+        match self.compression {
+            Some(compression) => compression.decompress(&data),
+            None => Ok(data),
+        }
+    }
+
+    /// Limit the time to wait for data from the server, or wait forever with `None`.
+    pub fn set_read_timeout(&self, timeout: Option<std::time::Duration>) -> std::io::Result<()> {
+        match &self.stream {
+            Stream::Tcp(stream) => stream.set_read_timeout(timeout),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.set_read_timeout(timeout),
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => stream.sock.set_read_timeout(timeout),
+        }
+    }
+}
+
+/// The hello message introducing a client, optionally asking for compression.
+pub(crate) fn hello_request(
+    name: &str,
+    compression: Option<Compression>,
+    token: Option<String>,
+) -> Request {
+    let mut features = supported_features();
+    features.extend(compression.map(|c| c.feature().to_owned()));
+    Request::Hello {
+        version: PROTOCOL_VERSION,
+        client: name.to_owned(),
+        features,
+        token,
+    }
+}
+
+/// Interpret the answer to a hello message.
+///
+/// Returns `None` for packets which are not the answer.
+pub(crate) fn welcome_to_session(data: &[u8]) -> Option<std::io::Result<Session>> {
+    match Response::from_bytes(data) {
+        Ok(Response::Welcome {
+            version,
+            server,
+            features,
+        }) => Some(Ok(Session {
+            version,
+            peer: server,
+            features,
+        })),
+        Ok(Response::Error { message, .. }) => Some(Err(std::io::Error::other(message))),
+        _ => {
+            debug!("Ignoring packet before the welcome");
+            None
+        }
+    }
+}
+
+/// The connection to the server.
+enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<rustls::StreamOwned<rustls::ClientSession, TcpStream>>),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read(buf),
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.write(buf),
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.flush(),
+            #[cfg(feature = "tls")]
+            Stream::Tls(stream) => stream.flush(),
+        }
+    }
 }
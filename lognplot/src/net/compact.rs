@@ -0,0 +1,186 @@
+//! Compact encoding of samples, by signal id.
+//!
+//! Instead of repeating the signal name in every packet, a client
+//! defines a small integer id for a signal once. A frame then carries
+//! samples of many signals as (id, t, value) tuples, where t is relative
+//! to an epoch set per connection. Small relative timestamps are
+//! encoded in fewer bytes by CBOR.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[cfg(feature = "server")]
+use crate::time::TimeStamp;
+#[cfg(feature = "server")]
+use crate::tsdb::observations::{Observation, Sample};
+#[cfg(feature = "server")]
+use crate::tsdb::TsDbHandle;
+
+/// A packet of the compact encoding, sent by a client.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum CompactPacket {
+    /// Define the id of a signal, for the rest of the connection.
+    #[serde(rename = "define")]
+    Define { signal: u32, name: String },
+
+    /// Set the time which timestamps in frames are relative to.
+    #[serde(rename = "epoch")]
+    Epoch { t: f64 },
+
+    /// Samples of any amount of signals, as (signal id, relative t, value).
+    #[serde(rename = "frame")]
+    Frame { samples: Vec<(u32, f64, f64)> },
+}
+
+/// Values of the type field of compact packets.
+#[cfg(feature = "server")]
+pub const COMPACT_TYPES: &[&str] = &["define", "epoch", "frame"];
+
+impl CompactPacket {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = vec![];
+        ciborium::ser::into_writer(self, &mut data).unwrap();
+        data
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, String> {
+        ciborium::de::from_reader(data).map_err(|e| format!("{:?}", e))
+    }
+}
+
+/// The signal ids and epoch of a connection, as known by the server.
+#[cfg(feature = "server")]
+#[derive(Default)]
+pub struct SignalTable {
+    names: HashMap<u32, String>,
+    epoch: f64,
+}
+
+#[cfg(feature = "server")]
+impl SignalTable {
+    /// Process a compact packet, storing the samples of frames in the database.
+    pub fn apply(&mut self, packet: CompactPacket, db: &TsDbHandle) -> Result<(), String> {
+        match packet {
+            CompactPacket::Define { signal, name } => {
+                self.names.insert(signal, name);
+            }
+            CompactPacket::Epoch { t } => {
+                self.epoch = t;
+            }
+            CompactPacket::Frame { samples } => {
+                let mut per_signal: HashMap<u32, Vec<Observation<Sample>>> = HashMap::new();
+                for (signal, t, value) in samples {
+                    let observation =
+                        Observation::new(TimeStamp::new(self.epoch + t), Sample::new(value));
+                    per_signal.entry(signal).or_default().push(observation);
+                }
+
+                let mut unknown = vec![];
+                for (signal, observations) in per_signal {
+                    if let Some(name) = self.names.get(&signal) {
+                        db.add_values(name, observations);
+                    } else {
+                        unknown.push(signal);
+                    }
+                }
+
+                if !unknown.is_empty() {
+                    unknown.sort_unstable();
+                    return Err(format!("Samples of undefined signal ids {:?}", unknown));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The signal ids and epoch of a connection, as known by the client.
+#[derive(Default)]
+pub struct SignalIds {
+    ids: HashMap<String, u32>,
+    epoch: f64,
+}
+
+impl SignalIds {
+    pub fn set_epoch(&mut self, epoch: f64) -> CompactPacket {
+        self.epoch = epoch;
+        CompactPacket::Epoch { t: epoch }
+    }
+
+    /// Get the id of a signal, and the definition to send when it is new.
+    pub fn lookup(&mut self, name: &str) -> (u32, Option<CompactPacket>) {
+        if let Some(signal) = self.ids.get(name) {
+            (*signal, None)
+        } else {
+            let signal = self.ids.len() as u32;
+            self.ids.insert(name.to_owned(), signal);
+            let definition = CompactPacket::Define {
+                signal,
+                name: name.to_owned(),
+            };
+            (signal, Some(definition))
+        }
+    }
+
+    /// Encode samples with absolute timestamps as a frame.
+    ///
+    /// Definitions of new signals are added to the given list, these
+    /// must be sent before the frame.
+    pub fn frame(
+        &mut self,
+        samples: &[(&str, f64, f64)],
+        definitions: &mut Vec<CompactPacket>,
+    ) -> CompactPacket {
+        let samples = samples
+            .iter()
+            .map(|(name, t, value)| {
+                let (signal, definition) = self.lookup(name);
+                definitions.extend(definition);
+                (signal, t - self.epoch, *value)
+            })
+            .collect();
+        CompactPacket::Frame { samples }
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::{CompactPacket, SignalIds, SignalTable};
+    use crate::tsdb::TsDb;
+
+    #[test]
+    fn frames_by_signal_id() {
+        let mut ids = SignalIds::default();
+        let mut packets = vec![ids.set_epoch(1000.0)];
+        let mut definitions = vec![];
+        let frame = ids.frame(
+            &[("a", 1000.5, 1.0), ("b", 1000.5, 2.0), ("a", 1001.0, 3.0)],
+            &mut definitions,
+        );
+        assert_eq!(definitions.len(), 2);
+        assert_eq!(
+            frame,
+            CompactPacket::Frame {
+                samples: vec![(0, 0.5, 1.0), (1, 0.5, 2.0), (0, 1.0, 3.0)]
+            }
+        );
+        packets.extend(definitions);
+        packets.push(frame);
+
+        let db = TsDb::default().into_handle();
+        let mut table = SignalTable::default();
+        for packet in packets {
+            let packet = CompactPacket::from_bytes(&packet.to_bytes()).unwrap();
+            table.apply(packet, &db).unwrap();
+        }
+        assert_eq!(db.summary("a", None).unwrap().count(), 2);
+        let samples = db.get_raw_samples("b").unwrap();
+        assert_eq!(samples[0].timestamp.amount, 1000.5);
+
+        let frame = CompactPacket::Frame {
+            samples: vec![(7, 0.0, 1.0)],
+        };
+        assert!(table.apply(frame, &db).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+//! Compression of packets.
+//!
+//! A client can ask for compression by offering a compression algorithm
+//! as a feature in its hello. When the server supports it too, each
+//! packet after the welcome is compressed on its own, in both directions.
+//! The length prefix of a packet is the compressed size.
+
+use super::protocol::Session;
+
+/// Compression with zstd, which compresses well.
+pub const FEATURE_ZSTD: &str = "zstd";
+
+/// Compression with lz4, which is fast.
+pub const FEATURE_LZ4: &str = "lz4";
+
+/// Largest size of a decompressed packet.
+#[cfg(feature = "compression")]
+const MAX_DECOMPRESSED_SIZE: usize = 64 << 20;
+
+/// A compression algorithm for packets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    Zstd,
+    Lz4,
+}
+
+impl Compression {
+    /// The feature to offer in the hello, to use this compression.
+    pub fn feature(self) -> &'static str {
+        match self {
+            Compression::Zstd => FEATURE_ZSTD,
+            Compression::Lz4 => FEATURE_LZ4,
+        }
+    }
+
+    /// The compression agreed upon in a session, if any.
+    ///
+    /// When both are agreed upon, zstd is used. Without the compression
+    /// feature, compression is never used.
+    pub fn from_session(session: &Session) -> Option<Self> {
+        if !cfg!(feature = "compression") {
+            None
+        } else if session.supports(FEATURE_ZSTD) {
+            Some(Compression::Zstd)
+        } else if session.supports(FEATURE_LZ4) {
+            Some(Compression::Lz4)
+        } else {
+            None
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    pub fn compress(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Compression::Zstd => zstd::bulk::compress(data, 0),
+            Compression::Lz4 => lz4::block::compress(data, None, true),
+        }
+        .expect("Compression of memory cannot fail")
+    }
+
+    #[cfg(feature = "compression")]
+    pub fn decompress(self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        use std::io::Read;
+
+        match self {
+            Compression::Zstd => {
+                let mut decompressed = vec![];
+                zstd::stream::read::Decoder::new(data)?
+                    .take(MAX_DECOMPRESSED_SIZE as u64 + 1)
+                    .read_to_end(&mut decompressed)?;
+                if decompressed.len() > MAX_DECOMPRESSED_SIZE {
+                    return Err(too_large());
+                }
+                Ok(decompressed)
+            }
+            Compression::Lz4 => {
+                // The size of the decompressed data comes first:
+                if data.len() < 4 {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "Truncated lz4 packet",
+                    ));
+                }
+                let size = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+                if size > MAX_DECOMPRESSED_SIZE {
+                    return Err(too_large());
+                }
+                lz4::block::decompress(data, None)
+            }
+        }
+    }
+
+    // Without support for compression, it is never agreed upon:
+    #[cfg(not(feature = "compression"))]
+    pub fn compress(self, _data: &[u8]) -> Vec<u8> {
+        unreachable!("Compression is not supported")
+    }
+
+    #[cfg(not(feature = "compression"))]
+    pub fn decompress(self, _data: &[u8]) -> std::io::Result<Vec<u8>> {
+        unreachable!("Compression is not supported")
+    }
+}
+
+#[cfg(feature = "compression")]
+fn too_large() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        "Decompressed packet is too large",
+    )
+}
+
+#[cfg(all(test, feature = "compression"))]
+mod tests {
+    use super::Compression;
+    use crate::net::protocol::Session;
+
+    #[test]
+    fn round_trip() {
+        let data: Vec<u8> = (0..10_000).map(|i| (i % 7) as u8).collect();
+        for compression in &[Compression::Zstd, Compression::Lz4] {
+            let compressed = compression.compress(&data);
+            assert!(compressed.len() < data.len() / 10);
+            assert_eq!(compression.decompress(&compressed).unwrap(), data);
+            assert!(compression.decompress(&data[..100]).is_err());
+        }
+
+        let features = vec!["lz4".to_owned(), "zstd".to_owned()];
+        let session = Session::negotiate(1, "test", &features);
+        assert_eq!(Compression::from_session(&session), Some(Compression::Zstd));
+    }
+}
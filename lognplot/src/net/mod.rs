@@ -9,17 +9,54 @@
 //! - Read data from file
 //! - Demo data (random values)
 
+mod buffered_client;
 mod client;
+mod compact;
+mod compression;
 mod payload;
+mod protocol;
+mod relay;
+mod remote;
+mod request;
+mod udp_client;
 
+#[cfg(feature = "server")]
+mod async_client;
 #[cfg(feature = "server")]
 mod peer;
 #[cfg(feature = "server")]
 mod peer_processor;
 #[cfg(feature = "server")]
+mod peer_registry;
+#[cfg(feature = "server")]
 mod server;
+#[cfg(all(test, feature = "server"))]
+mod tests;
+#[cfg(feature = "tls")]
+mod tls;
+#[cfg(feature = "server")]
+mod udp;
+#[cfg(all(feature = "server", unix))]
+mod unix;
 
 #[cfg(feature = "server")]
-pub use server::run_server;
+pub use async_client::AsyncClient;
+#[cfg(feature = "server")]
+pub use peer_registry::{PeerId, PeerInfo, PeerRegistry};
+#[cfg(feature = "server")]
+pub use server::{run_server, start_server, ServerHandle, ServerOptions, TlsOptions};
+#[cfg(feature = "server")]
+pub use udp::UdpSourceStatistics;
 
-pub use client::TcpClient;
+pub use buffered_client::{BufferedClient, BufferedClientOptions, DropPolicy};
+pub use client::{TcpClient, UNIX_ADDRESS_PREFIX};
+pub use compact::CompactPacket;
+pub use compression::Compression;
+pub use payload::SampleBatch;
+pub use protocol::{Session, FEATURES, PROTOCOL_VERSION};
+pub use relay::{start_relay, RelayHandle, RelayOptions};
+pub use remote::RemoteClient;
+pub use request::{Request, Response, SignalSummary};
+#[cfg(feature = "tls")]
+pub use tls::TlsClientOptions;
+pub use udp_client::UdpClient;
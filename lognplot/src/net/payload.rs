@@ -2,16 +2,20 @@ use serde::{Deserialize, Serialize};
 
 use std::collections::HashMap;
 
-use crate::time::TimeStamp;
+use crate::time::{TimeSpan, TimeStamp};
 use crate::tsdb::observations::{Observation, ProfileEvent, Sample, Text};
-use crate::tsdb::TsDbHandle;
+use crate::tsdb::{Annotation, NewObservations, SignalMetadata, TsDbHandle};
 
 /// A chunk of data at fixed sample rate.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SampleBatch {
     /// The name of the signal.
     name: String,
 
+    /// Sequence number of datagrams, to detect lost datagrams.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    seq: Option<u64>,
+
     #[serde(flatten)]
     payload: SamplePayload,
 }
@@ -21,6 +25,7 @@ impl SampleBatch {
     pub fn new_sample(name: String, t: f64, value: f64) -> Self {
         SampleBatch {
             name,
+            seq: None,
             payload: SamplePayload::Single { t, value },
         }
     }
@@ -28,6 +33,7 @@ impl SampleBatch {
     pub fn new_samples(name: String, samples: Vec<(f64, f64)>) -> Self {
         SampleBatch {
             name,
+            seq: None,
             payload: SamplePayload::Batch { samples },
         }
     }
@@ -35,6 +41,7 @@ impl SampleBatch {
     pub fn new_sampled_data(name: String, t0: f64, dt: f64, values: Vec<f64>) -> Self {
         SampleBatch {
             name,
+            seq: None,
             payload: SamplePayload::Sampled {
                 t: t0,
                 dt,
@@ -46,10 +53,130 @@ impl SampleBatch {
     pub fn new_text(name: String, t: f64, text: String) -> Self {
         SampleBatch {
             name,
+            seq: None,
             payload: SamplePayload::Text { t, text },
         }
     }
 
+    /// Create a message which attaches meta data to a signal.
+    pub fn new_metadata(name: String, metadata: &SignalMetadata) -> Self {
+        SampleBatch {
+            name,
+            seq: None,
+            payload: SamplePayload::Meta {
+                unit: metadata.unit.clone(),
+                description: metadata.description.clone(),
+                scale: Some(metadata.scale),
+                offset: Some(metadata.offset),
+                color: metadata.color.clone(),
+                tags: metadata.tags.clone(),
+                gap_threshold: metadata.gap_threshold,
+            },
+        }
+    }
+
+    /// Create an annotation message. The name holds the annotation title.
+    pub fn new_annotation(annotation: &Annotation) -> Self {
+        let end = if annotation.is_point() {
+            None
+        } else {
+            Some(annotation.timespan.end.amount)
+        };
+        SampleBatch {
+            name: annotation.title.clone(),
+            seq: None,
+            payload: SamplePayload::Annotation {
+                t: annotation.timespan.start.amount,
+                end,
+                color: annotation.color.clone(),
+            },
+        }
+    }
+
+    /// Create batches from observations which were added to a database.
+    ///
+    /// Values fit in a single batch, texts and profile events are
+    /// sent one by one.
+    pub fn from_new_observations(name: &str, observations: &NewObservations) -> Vec<Self> {
+        match observations {
+            NewObservations::Values(observations) => {
+                let samples = observations
+                    .iter()
+                    .map(|o| (o.timestamp.amount, o.value.value))
+                    .collect();
+                vec![SampleBatch::new_samples(name.to_owned(), samples)]
+            }
+            NewObservations::Texts(observations) => observations
+                .iter()
+                .map(|o| {
+                    SampleBatch::new_text(name.to_owned(), o.timestamp.amount, o.value.text.clone())
+                })
+                .collect(),
+            NewObservations::Profile(observations) => observations
+                .iter()
+                .map(|o| {
+                    let event = match &o.value {
+                        ProfileEvent::FunctionEnter { name } => {
+                            ProfileEventPayload::Enter { name: name.clone() }
+                        }
+                        ProfileEvent::FunctionExit => ProfileEventPayload::Exit,
+                    };
+                    SampleBatch {
+                        name: name.to_owned(),
+                        seq: None,
+                        payload: SamplePayload::Profile {
+                            t: o.timestamp.amount,
+                            event,
+                        },
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Number this batch, for transports which may lose batches.
+    pub fn with_sequence_number(mut self, seq: u64) -> Self {
+        self.seq = Some(seq);
+        self
+    }
+
+    pub fn sequence_number(&self) -> Option<u64> {
+        self.seq
+    }
+
+    /// The name of the signal to which this batch belongs.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The samples in this batch as (t, value) pairs.
+    ///
+    /// This is empty when the batch holds no samples, but for example text.
+    pub fn values(&self) -> Vec<(f64, f64)> {
+        match &self.payload {
+            SamplePayload::Batch { samples } => samples.clone(),
+            SamplePayload::Sampled { t, dt, data } => data
+                .iter()
+                .enumerate()
+                .map(|(index, value)| (t + dt * index as f64, *value))
+                .collect(),
+            SamplePayload::Single { t, value } => vec![(*t, *value)],
+            _ => vec![],
+        }
+    }
+
+    /// The amount of samples, texts or events in this batch.
+    ///
+    /// Meta data and annotations do not count.
+    pub fn observation_count(&self) -> usize {
+        match &self.payload {
+            SamplePayload::Batch { samples } => samples.len(),
+            SamplePayload::Sampled { data, .. } => data.len(),
+            SamplePayload::Meta { .. } | SamplePayload::Annotation { .. } => 0,
+            _ => 1,
+        }
+    }
+
     /// Feed this batch of observations into a database.
     pub fn to_db(&self, db: &TsDbHandle) {
         match &self.payload {
@@ -103,6 +230,33 @@ impl SampleBatch {
                 let event = Observation::new(timestamp, event);
                 db.add_profile_event(&self.name, event);
             }
+            SamplePayload::Meta {
+                unit,
+                description,
+                scale,
+                offset,
+                color,
+                tags,
+                gap_threshold,
+            } => {
+                let metadata = SignalMetadata {
+                    unit: unit.clone(),
+                    description: description.clone(),
+                    scale: scale.unwrap_or(1.0),
+                    offset: offset.unwrap_or(0.0),
+                    color: color.clone(),
+                    tags: tags.clone(),
+                    gap_threshold: *gap_threshold,
+                };
+                db.set_metadata(&self.name, metadata);
+            }
+            SamplePayload::Annotation { t, end, color } => {
+                let start = TimeStamp::new(*t);
+                let end = TimeStamp::new(end.unwrap_or(*t));
+                let mut annotation = Annotation::span(TimeSpan::new(start, end), &self.name);
+                annotation.color = color.clone();
+                db.add_annotation(annotation);
+            }
         }
     }
 
@@ -121,7 +275,7 @@ impl SampleBatch {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "type")]
 enum SamplePayload {
     /// A bulk of measurements.
@@ -178,9 +332,53 @@ enum SamplePayload {
         #[serde(flatten)]
         event: ProfileEventPayload,
     },
+
+    /// Meta data describing the signal.
+    #[serde(rename = "meta")]
+    Meta {
+        /// The unit of the signal, for example "V"
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        unit: Option<String>,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+
+        /// Display scale factor
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        scale: Option<f64>,
+
+        /// Display offset
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        offset: Option<f64>,
+
+        /// Preferred color, for example "#FF7F0E"
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        color: Option<String>,
+
+        #[serde(default)]
+        tags: Vec<String>,
+
+        /// Largest interval between samples which is drawn as a line, in seconds.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        gap_threshold: Option<f64>,
+    },
+
+    /// An annotation on the time line, the name is used as title.
+    #[serde(rename = "annotation")]
+    Annotation {
+        /// Timestamp of the annotation, or the start of the span.
+        t: f64,
+
+        /// End of the annotated span, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        end: Option<f64>,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        color: Option<String>,
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "event")]
 enum ProfileEventPayload {
     #[serde(rename = "enter")]
@@ -196,6 +394,8 @@ enum ProfileEventPayload {
 #[cfg(test)]
 mod tests {
     use super::SampleBatch;
+    use crate::time::TimeSpan;
+    use crate::tsdb::{Annotation, SignalMetadata, TsDb};
 
     #[test]
     /// Check a simple roundtrip operation (to bytes and back to data)
@@ -205,4 +405,25 @@ mod tests {
         let batch2: SampleBatch = SampleBatch::from_bytes(&data).unwrap();
         assert_eq!(batch.name, batch2.name);
     }
+
+    #[test]
+    fn metadata_roundtrip() {
+        let db = TsDb::default().into_handle();
+        let metadata = SignalMetadata::with_unit("rpm");
+        let batch = SampleBatch::new_metadata("motor".to_string(), &metadata);
+        let data = batch.to_bytes();
+        SampleBatch::from_bytes(&data).unwrap().to_db(&db);
+        assert_eq!(Some(metadata), db.get_metadata("motor"));
+    }
+
+    #[test]
+    fn annotation_roundtrip() {
+        let db = TsDb::default().into_handle();
+        let mut annotation = Annotation::span(TimeSpan::from_seconds(2, 4), "test step 3");
+        annotation.color = Some("#FF7F0E".to_owned());
+        let batch = SampleBatch::new_annotation(&annotation);
+        let data = batch.to_bytes();
+        SampleBatch::from_bytes(&data).unwrap().to_db(&db);
+        assert_eq!(vec![annotation], db.get_annotations(None));
+    }
 }
@@ -1,14 +1,30 @@
-//! Handle a single peer via tcp socket.
+//! Handle a single peer via a tcp or unix domain socket.
 
+use super::compact::{CompactPacket, SignalTable};
+use super::compression::Compression;
 use super::payload::SampleBatch;
 use super::peer_processor::PeerEvent;
-use crate::tsdb::TsDbHandle;
+use super::peer_registry::{PeerId, PeerRegistry};
+use super::protocol::Session;
+use super::request::{ClientMessage, Request, Response};
+use crate::tsdb::{TsDbHandle, MAX_PATTERN_LENGTH};
+use bytes::Bytes;
 use futures::channel::{mpsc, oneshot};
-use futures::{FutureExt, StreamExt};
-use tokio::net::TcpStream;
+use futures::{Future, FutureExt, SinkExt, StreamExt};
+use std::collections::HashMap;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::task::JoinHandle;
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
 
+/// Amount of data batches buffered per subscription.
+const SUBSCRIPTION_CAPACITY: usize = 100;
+
+/// Amount of packets buffered on their way to the client.
+///
+/// Subscribed data is dropped when the client does not read it fast
+/// enough. A client which does not even read its responses is disconnected.
+const OUTGOING_CAPACITY: usize = 256;
+
 /// A handle to a peer connection
 pub struct PeerHandle {
     kill_switch: oneshot::Sender<()>,
@@ -29,75 +45,483 @@ impl PeerHandle {
         self.join_handle.await?;
         Ok(())
     }
+
+    /// Disconnect the peer, without waiting for it.
+    pub fn kill(self) {
+        if self.kill_switch.send(()).is_err() {
+            info!("Peer already disconnected");
+        }
+    }
 }
 
-/// Handle a single client
-pub fn process_client(
-    socket: TcpStream,
+/// Handle a single client, and register it until it disconnects.
+///
+/// The connection is a future, so that a handshake, for example of
+/// TLS, happens in the task of the peer. When a token is given, the
+/// client must present it in its hello before anything else.
+pub fn process_client<F, S>(
+    connection: F,
+    address: String,
     db: TsDbHandle,
+    token: Option<String>,
+    registry: &PeerRegistry,
     peer_event_sink: mpsc::UnboundedSender<PeerEvent>,
-) -> PeerHandle {
-    info!("Got incoming socket! {:?}", socket);
+) where
+    F: Future<Output = std::io::Result<S>> + Send + 'static,
+    S: AsyncRead + AsyncWrite + Unpin + Send + std::fmt::Debug + 'static,
+{
+    let peer = registry.register(address);
     let (kill_switch, kill_switch_endpoint) = oneshot::channel::<()>();
-    let join_handle = tokio::spawn(async {
-        let res = peer_prog(db, socket, kill_switch_endpoint, peer_event_sink).await;
+    let join_handle = tokio::spawn(async move {
+        let finished_sink = peer_event_sink.clone();
+        let res = peer_prog(
+            peer,
+            db,
+            connection,
+            token,
+            kill_switch_endpoint,
+            peer_event_sink,
+        )
+        .await;
         if let Err(err) = res {
             error!("Error in peer: {:?}", err);
         }
+        if finished_sink
+            .unbounded_send(PeerEvent::Finished(peer))
+            .is_err()
+        {
+            debug!("Peer event processor already stopped");
+        }
     });
-    PeerHandle {
+    let handle = PeerHandle {
         join_handle,
         kill_switch,
-    }
+    };
+    registry.attach(peer, handle);
 }
 
-async fn peer_prog(
+async fn peer_prog<F, S>(
+    peer: PeerId,
     db: TsDbHandle,
-    socket: TcpStream,
+    connection: F,
+    token: Option<String>,
     kill_switch_endpoint: oneshot::Receiver<()>,
     peer_event_sink: mpsc::UnboundedSender<PeerEvent>,
-) -> std::io::Result<()> {
-    let mut framed_stream = Framed::new(socket, LengthDelimitedCodec::new()).fuse();
+) -> std::io::Result<()>
+where
+    F: Future<Output = std::io::Result<S>>,
+    S: AsyncRead + AsyncWrite + Unpin + Send + std::fmt::Debug + 'static,
+{
     let mut kill_switch_endpoint = kill_switch_endpoint.fuse();
 
-    loop {
+    let connection = connection.fuse();
+    futures::pin_mut!(connection);
+    let socket = futures::select! {
+        socket = connection => socket?,
+        _x = kill_switch_endpoint => {
+            return Ok(());
+        }
+    };
+    info!("Got incoming socket! {:?}", socket);
+
+    let (framed_sink, framed_stream) = Framed::new(socket, LengthDelimitedCodec::new()).split();
+    let mut framed_stream = framed_stream.fuse();
+
+    // Responses and subscribed data, on their way to the client. They are
+    // written by a task of their own, so that reading goes on while the
+    // client is slow to read.
+    let (outgoing, outgoing_rx) = mpsc::channel::<Outgoing>(OUTGOING_CAPACITY);
+    let (writer_stop, writer_stop_endpoint) = oneshot::channel::<()>();
+    let mut writer =
+        tokio::spawn(writer_prog(framed_sink, outgoing_rx, writer_stop_endpoint)).fuse();
+    let mut state = PeerState::new(peer, db.clone(), token, outgoing);
+
+    let flush = loop {
         futures::select! {
             optional_packet = framed_stream.next() => {
                 if let Some(packet) = optional_packet {
                     let packet = packet?;
-                    process_packet(&db, &packet, &peer_event_sink);
+                    if !process_packet(&db, &packet, &peer_event_sink, &mut state) {
+                        if state.overflowed {
+                            warn!("Disconnecting client which does not read its responses");
+                            break false;
+                        } else {
+                            info!("Closing connection of unauthenticated client");
+                            break true;
+                        }
+                    }
                 } else {
                     info!("Client disconnect!");
-                    break;
+                    break false;
                 }
             },
+            result = writer => {
+                return result?;
+            },
             _x = kill_switch_endpoint => {
                 info!("Killing client connection!");
-                break;
+                break false;
             }
         }
+    };
+
+    // Stop the subscriptions, after which the writer runs out of data:
+    drop(state);
+    if flush {
+        // Say goodbye:
+        let result = writer.await;
+        drop(writer_stop);
+        result?
+    } else {
+        drop(writer_stop);
+        writer.await?
     }
+}
+
+/// A packet on its way to the client, or a change of the connection.
+enum Outgoing {
+    Packet(Vec<u8>),
 
-    Ok(())
+    /// Compress the packets which follow, or stop compressing them.
+    SetCompression(Option<Compression>),
+}
+
+/// Send the outgoing packets, until there are no more or the writer is stopped.
+///
+/// Packets are compressed here, so that a packet is compressed when the
+/// connection was compressed at the moment it is sent.
+async fn writer_prog<W>(
+    mut sink: W,
+    outgoing_rx: mpsc::Receiver<Outgoing>,
+    stop_endpoint: oneshot::Receiver<()>,
+) -> std::io::Result<()>
+where
+    W: futures::Sink<Bytes, Error = std::io::Error> + Unpin,
+{
+    let mut compression: Option<Compression> = None;
+    let mut packets = outgoing_rx.filter_map(move |outgoing| {
+        let packet = match outgoing {
+            Outgoing::Packet(data) => {
+                let data = match compression {
+                    Some(compression) => compression.compress(&data),
+                    None => data,
+                };
+                Some(Ok(Bytes::from(data)))
+            }
+            Outgoing::SetCompression(new_compression) => {
+                compression = new_compression;
+                None
+            }
+        };
+        futures::future::ready(packet)
+    });
+    let writing = sink.send_all(&mut packets).fuse();
+    futures::pin_mut!(writing);
+    let mut stop_endpoint = stop_endpoint.fuse();
+    futures::select! {
+        result = writing => result,
+        _x = stop_endpoint => Ok(()),
+    }
 }
 
 /// Process a single message.
+///
+/// Returns false when the connection must be closed.
 fn process_packet(
     db: &TsDbHandle,
     packet: &[u8],
     peer_event_sink: &mpsc::UnboundedSender<PeerEvent>,
-) {
+    state: &mut PeerState,
+) -> bool {
+    let peer = state.peer;
     peer_event_sink
-        .unbounded_send(PeerEvent::BytesReceived(packet.len()))
+        .unbounded_send(PeerEvent::BytesReceived(peer, packet.len()))
+        .unwrap();
+    let report_error = || {
+        peer_event_sink
+            .unbounded_send(PeerEvent::Error(peer))
+            .unwrap();
+    };
+
+    let decompressed;
+    let packet = if let Some(compression) = state.compression {
+        match compression.decompress(packet) {
+            Ok(data) => {
+                decompressed = data;
+                &decompressed[..]
+            }
+            Err(err) => {
+                error!("Error decompressing packet: {}", err);
+                report_error();
+                return true;
+            }
+        }
+    } else {
+        packet
+    };
+    peer_event_sink
+        .unbounded_send(PeerEvent::PayloadReceived(peer, packet.len()))
         .unwrap();
 
     // try to decode cbor package:
-    match SampleBatch::from_bytes(packet) {
-        Ok(batch) => {
+    let message = ClientMessage::from_bytes(packet);
+
+    if !state.authenticated {
+        if let Ok(ClientMessage::Request(Request::Hello { token, .. })) = &message {
+            state.authenticated = state.check_token(token.as_deref());
+        }
+        if !state.authenticated {
+            warn!("Rejecting client without valid token");
+            report_error();
+            let error = Response::Error {
+                id: None,
+                message: "Authentication failed".to_owned(),
+            };
+            state.send(error.to_bytes());
+            return false;
+        }
+    }
+
+    let samples = match &message {
+        Ok(ClientMessage::Data(batch)) => batch.observation_count(),
+        Ok(ClientMessage::Compact(CompactPacket::Frame { samples })) => samples.len(),
+        _ => 0,
+    };
+    if samples > 0 {
+        peer_event_sink
+            .unbounded_send(PeerEvent::SamplesReceived(peer, samples))
+            .unwrap();
+    }
+
+    match message {
+        Ok(ClientMessage::Data(batch)) => {
             batch.to_db(db);
         }
-        Err(err) => {
-            error!("Error decoding packet: {:?}", err);
+        Ok(ClientMessage::Compact(packet)) => {
+            if let Err(message) = state.signals.apply(packet, db) {
+                error!("Error in compact packet: {}", message);
+                report_error();
+                if !state.session.is_legacy() {
+                    state.send(Response::Error { id: None, message }.to_bytes());
+                }
+            }
+        }
+        Ok(ClientMessage::Request(request)) => {
+            let response = request
+                .respond(db)
+                .unwrap_or_else(|| state.handle(&request));
+            state.send(response.to_bytes());
+
+            if let Request::Hello { client, .. } = request {
+                // The welcome itself is not compressed:
+                state.compression = Compression::from_session(&state.session);
+                if let Some(compression) = state.compression {
+                    info!("Compressing the connection with {:?}", compression);
+                }
+                state.queue(Outgoing::SetCompression(state.compression));
+                peer_event_sink
+                    .unbounded_send(PeerEvent::Introduced(peer, client))
+                    .unwrap();
+            }
+        }
+        Err(error) => {
+            error!("Error decoding packet: {:?}", error);
+            report_error();
+            // Clients which did not introduce themselves may never read:
+            if !state.session.is_legacy() {
+                state.send(error.to_bytes());
+            }
         }
     }
+
+    !state.overflowed
+}
+
+/// The state of the connection with a single client.
+///
+/// Each subscription runs a task, which forwards new data to the client.
+struct PeerState {
+    peer: PeerId,
+    db: TsDbHandle,
+    outgoing: mpsc::Sender<Outgoing>,
+
+    /// Set when a response did not fit in the outgoing queue.
+    overflowed: bool,
+    session: Session,
+
+    /// The token clients must present, if any.
+    token: Option<String>,
+    authenticated: bool,
+    compression: Option<Compression>,
+    signals: SignalTable,
+    next_subscription: u64,
+    kill_switches: HashMap<u64, oneshot::Sender<()>>,
+}
+
+impl PeerState {
+    fn new(
+        peer: PeerId,
+        db: TsDbHandle,
+        token: Option<String>,
+        outgoing: mpsc::Sender<Outgoing>,
+    ) -> Self {
+        PeerState {
+            peer,
+            db,
+            outgoing,
+            overflowed: false,
+            session: Session::legacy(),
+            authenticated: token.is_none(),
+            token,
+            compression: None,
+            signals: SignalTable::default(),
+            next_subscription: 1,
+            kill_switches: HashMap::new(),
+        }
+    }
+
+    /// Queue data for transmission to the client.
+    fn send(&mut self, data: Vec<u8>) {
+        self.queue(Outgoing::Packet(data));
+    }
+
+    fn queue(&mut self, outgoing: Outgoing) {
+        if let Err(err) = self.outgoing.try_send(outgoing) {
+            if err.is_full() {
+                self.overflowed = true;
+            } else {
+                debug!("Client connection already closed");
+            }
+        }
+    }
+
+    /// Test a token presented by the client.
+    fn check_token(&self, presented: Option<&str>) -> bool {
+        match (&self.token, presented) {
+            (Some(token), Some(presented)) => tokens_match(token, presented),
+            (None, _) => true,
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Handle the requests which affect the connection.
+    fn handle(&mut self, request: &Request) -> Response {
+        match request {
+            Request::Hello {
+                version,
+                client,
+                features,
+                ..
+            } => {
+                self.session = Session::negotiate(*version, client, features);
+                info!(
+                    "Client {} speaks protocol version {}, with features {:?}",
+                    client, self.session.version, self.session.features
+                );
+                Response::welcome(&self.session)
+            }
+            Request::Subscribe { id, pattern } if pattern.len() > MAX_PATTERN_LENGTH => {
+                Response::Error {
+                    id: Some(*id),
+                    message: format!("Patterns are limited to {} bytes", MAX_PATTERN_LENGTH),
+                }
+            }
+            Request::Subscribe { id, pattern } => {
+                let subscription = self.next_subscription;
+                self.next_subscription += 1;
+                let kill_switch = forward_data(&self.db, pattern, self.outgoing.clone());
+                self.kill_switches.insert(subscription, kill_switch);
+                info!("Client subscribed on {}", pattern);
+                Response::Subscribed {
+                    id: *id,
+                    subscription,
+                }
+            }
+            Request::Unsubscribe { id, subscription } => {
+                // Dropping the kill switch stops the forwarding:
+                self.kill_switches.remove(subscription);
+                Response::Unsubscribed { id: *id }
+            }
+            _ => unreachable!("Database requests are not handled here"),
+        }
+    }
+}
+
+/// Forward new data of signals matching a pattern, until the kill switch is used or dropped.
+fn forward_data(
+    db: &TsDbHandle,
+    pattern: &str,
+    mut outgoing: mpsc::Sender<Outgoing>,
+) -> oneshot::Sender<()> {
+    let mut data_queue = db.new_data_queue(pattern, SUBSCRIPTION_CAPACITY);
+    let (kill_switch, kill_switch_endpoint) = oneshot::channel::<()>();
+    tokio::spawn(async move {
+        let mut kill_switch_endpoint = kill_switch_endpoint.fuse();
+        let mut dropped = 0;
+        loop {
+            futures::select! {
+                optional_batch = data_queue.next() => {
+                    if let Some(batch) = optional_batch {
+                        if batch.dropped > 0 {
+                            warn!("Client lags behind, dropped {} batches", batch.dropped);
+                        }
+                        for packet in SampleBatch::from_new_observations(&batch.name, &batch.observations) {
+                            match outgoing.try_send(Outgoing::Packet(packet.to_bytes())) {
+                                Ok(()) if dropped > 0 => {
+                                    warn!("Client reads too slow, dropped {} packets", dropped);
+                                    dropped = 0;
+                                }
+                                Ok(()) => {}
+                                Err(err) if err.is_full() => {
+                                    dropped += 1;
+                                }
+                                Err(_) => {
+                                    return;
+                                }
+                            }
+                        }
+                    } else {
+                        break;
+                    }
+                },
+                _x = kill_switch_endpoint => {
+                    break;
+                }
+            }
+        }
+    });
+    kill_switch
+}
+
+/// Compare tokens in a time which depends on the presented token only.
+///
+/// Neither the position of the first wrong byte, nor the length of the
+/// expected token can be learned from the time taken.
+fn tokens_match(expected: &str, presented: &str) -> bool {
+    let expected = expected.as_bytes();
+    let mut difference = expected.len() ^ presented.len();
+    for (index, b) in presented.bytes().enumerate() {
+        let a = expected
+            .get(index % expected.len().max(1))
+            .copied()
+            .unwrap_or(0);
+        difference |= usize::from(a ^ b);
+    }
+    difference == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::tokens_match;
+
+    #[test]
+    fn token_comparison() {
+        assert!(tokens_match("secret", "secret"));
+        assert!(tokens_match("", ""));
+        assert!(!tokens_match("secret", "secreT"));
+        assert!(!tokens_match("secret", "secretsecret"));
+        assert!(!tokens_match("secret", "sec"));
+        assert!(!tokens_match("secret", ""));
+        assert!(!tokens_match("", "secret"));
+    }
 }
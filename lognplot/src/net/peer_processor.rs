@@ -1,5 +1,9 @@
 //! Process events from remotely connected peers
+//!
+//! The statistics of each peer are kept in the peer registry, and traced
+//! as META.peer.<id>.* signals.
 
+use super::peer_registry::{PeerId, PeerRegistry};
 use crate::tracer::{AnyTracer, Tracer};
 use futures::channel::{mpsc, oneshot};
 use futures::{FutureExt, StreamExt};
@@ -28,19 +32,40 @@ impl PeerEventProcessorHandle {
 }
 
 pub enum PeerEvent {
-    BytesReceived(usize),
-    // SamplesReceived(usize),
-    // Finished,
+    /// The client introduced itself with the given name.
+    Introduced(PeerId, String),
+
+    /// Size of a packet as received.
+    BytesReceived(PeerId, usize),
+
+    /// Size of a packet after decompression.
+    PayloadReceived(PeerId, usize),
+
+    /// Amount of samples in a packet.
+    SamplesReceived(PeerId, usize),
+
+    /// A packet could not be processed.
+    Error(PeerId),
+
+    /// The connection was closed.
+    Finished(PeerId),
 }
 
 pub fn start_peer_event_processor(
     peer_event_stream: mpsc::UnboundedReceiver<PeerEvent>,
+    registry: PeerRegistry,
     perf_tracer: Arc<AnyTracer>,
 ) -> PeerEventProcessorHandle {
     let (kill_switch, kill_switch_endpoint) = oneshot::channel::<()>();
 
     let join_handle = tokio::spawn(async {
-        process_peer_events(kill_switch_endpoint, peer_event_stream, perf_tracer).await
+        process_peer_events(
+            kill_switch_endpoint,
+            peer_event_stream,
+            registry,
+            perf_tracer,
+        )
+        .await
     });
 
     PeerEventProcessorHandle {
@@ -52,19 +77,21 @@ pub fn start_peer_event_processor(
 async fn process_peer_events(
     kill_switch_endpoint: oneshot::Receiver<()>,
     mut peer_event_stream: mpsc::UnboundedReceiver<PeerEvent>,
+    registry: PeerRegistry,
     perf_tracer: Arc<AnyTracer>,
 ) {
     let mut kill_switch_endpoint = kill_switch_endpoint.fuse();
 
     let mut total_bytes = 0;
-    // let mut total_samples = 0;
+    let mut total_payload_bytes = 0;
+    let mut total_samples = 0;
 
     perf_tracer.log_metric("total_bytes", std::time::Instant::now(), total_bytes as f64);
-    // perf_tracer.log_metric(
-    //     "total_samples",
-    //     std::time::Instant::now(),
-    //     total_samples as f64,
-    // );
+    perf_tracer.log_metric(
+        "total_samples",
+        std::time::Instant::now(),
+        total_samples as f64,
+    );
 
     loop {
         futures::select! {
@@ -72,17 +99,44 @@ async fn process_peer_events(
                 // println!("Event!");
                 if let Some(peer_event) = optional_peer_event {
                     match peer_event {
-                        PeerEvent::BytesReceived(amount) => {
+                        PeerEvent::Introduced(peer, client) => {
+                            registry.update(peer, |info| info.client = Some(client));
+                        }
+                        PeerEvent::BytesReceived(peer, amount) => {
                             total_bytes += amount;
                             perf_tracer.log_metric("total_bytes", std::time::Instant::now(), total_bytes as f64);
+                            if let Some(info) = registry.update(peer, |info| {
+                                info.bytes += amount as u64;
+                                info.last_activity = std::time::SystemTime::now();
+                            }) {
+                                trace_peer(&perf_tracer, peer, "bytes", info.bytes);
+                            }
+                        }
+                        PeerEvent::PayloadReceived(_peer, amount) => {
+                            total_payload_bytes += amount;
+                            let now = std::time::Instant::now();
+                            perf_tracer.log_metric("total_payload_bytes", now, total_payload_bytes as f64);
+                            if total_bytes > 0 {
+                                let ratio = total_payload_bytes as f64 / total_bytes as f64;
+                                perf_tracer.log_metric("compression_ratio", now, ratio);
+                            }
+                        }
+                        PeerEvent::SamplesReceived(peer, amount) => {
+                            total_samples += amount;
+                            perf_tracer.log_metric("total_samples", std::time::Instant::now(), total_samples as f64);
+                            if let Some(info) = registry.update(peer, |info| info.samples += amount as u64) {
+                                trace_peer(&perf_tracer, peer, "samples", info.samples);
+                            }
+                        }
+                        PeerEvent::Error(peer) => {
+                            if let Some(info) = registry.update(peer, |info| info.errors += 1) {
+                                trace_peer(&perf_tracer, peer, "errors", info.errors);
+                            }
+                        }
+                        PeerEvent::Finished(peer) => {
+                            registry.remove(peer);
+                            perf_tracer.log_metric("peers", std::time::Instant::now(), registry.len() as f64);
                         }
-                        // PeerEvent::SamplesReceived(amount) => {
-                        //     total_samples += amount;
-                        //     perf_tracer.log_metric("total_samples", std::time::Instant::now(), total_samples as f64);
-                        // }
-                        // PeerEvent::Finished => {
-                            // TODO: what to do?
-                        // }
                     }
                 } else {
                     // TODO: what to do in this case?
@@ -95,3 +149,9 @@ async fn process_peer_events(
         }
     }
 }
+
+/// Trace a statistic of a single peer as a meta signal.
+fn trace_peer(perf_tracer: &AnyTracer, peer: PeerId, statistic: &str, value: u64) {
+    let name = format!("META.peer.{}.{}", peer, statistic);
+    perf_tracer.log_metric(&name, std::time::Instant::now(), value as f64);
+}
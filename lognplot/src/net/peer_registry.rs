@@ -0,0 +1,170 @@
+//! Registry of connected peers, with statistics per connection.
+//!
+//! Peers are registered when they connect, and removed again when their
+//! connection is closed. The statistics are updated by the peer event
+//! processor.
+
+use super::peer::PeerHandle;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Identification of a connection, unique for the lifetime of a server.
+pub type PeerId = u64;
+
+/// Information about a single connection.
+#[derive(Debug, Clone)]
+pub struct PeerInfo {
+    pub id: PeerId,
+
+    /// Address of the client, or the path of the unix socket it connected to.
+    pub address: String,
+
+    /// Name of the client, when it introduced itself with a hello.
+    pub client: Option<String>,
+
+    pub connected: SystemTime,
+    pub last_activity: SystemTime,
+
+    /// Amount of bytes received, before decompression.
+    pub bytes: u64,
+
+    /// Amount of samples, texts and events received.
+    pub samples: u64,
+
+    /// Amount of packets which could not be processed.
+    pub errors: u64,
+}
+
+struct Entry {
+    info: PeerInfo,
+    handle: Option<PeerHandle>,
+}
+
+#[derive(Default)]
+struct Registry {
+    next_id: PeerId,
+    peers: BTreeMap<PeerId, Entry>,
+}
+
+/// The peers connected to a server.
+///
+/// This is a handle, clones refer to the same registry.
+#[derive(Clone, Default)]
+pub struct PeerRegistry {
+    inner: Arc<Mutex<Registry>>,
+}
+
+impl PeerRegistry {
+    /// Information about all connected peers, ordered by connection time.
+    pub fn peers(&self) -> Vec<PeerInfo> {
+        let registry = self.inner.lock().unwrap();
+        registry
+            .peers
+            .values()
+            .map(|entry| entry.info.clone())
+            .collect()
+    }
+
+    /// Disconnect a peer, returns false when it is not connected.
+    pub fn kick(&self, peer: PeerId) -> bool {
+        let entry = self.inner.lock().unwrap().peers.remove(&peer);
+        if let Some(entry) = entry {
+            info!("Kicking peer {} at {}", peer, entry.info.address);
+            if let Some(handle) = entry.handle {
+                handle.kill();
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.inner.lock().unwrap().peers.len()
+    }
+
+    /// Add a new connection, without a handle to its task yet.
+    pub(crate) fn register(&self, address: String) -> PeerId {
+        let mut registry = self.inner.lock().unwrap();
+        registry.next_id += 1;
+        let id = registry.next_id;
+        let now = SystemTime::now();
+        let info = PeerInfo {
+            id,
+            address,
+            client: None,
+            connected: now,
+            last_activity: now,
+            bytes: 0,
+            samples: 0,
+            errors: 0,
+        };
+        registry.peers.insert(id, Entry { info, handle: None });
+        id
+    }
+
+    /// Attach the handle of the task of a registered peer.
+    ///
+    /// When the peer is already gone, the handle is dropped.
+    pub(crate) fn attach(&self, peer: PeerId, handle: PeerHandle) {
+        if let Some(entry) = self.inner.lock().unwrap().peers.get_mut(&peer) {
+            entry.handle = Some(handle);
+        }
+    }
+
+    /// Modify the information of a peer, and get a copy of the result.
+    pub(crate) fn update<F>(&self, peer: PeerId, f: F) -> Option<PeerInfo>
+    where
+        F: FnOnce(&mut PeerInfo),
+    {
+        let mut registry = self.inner.lock().unwrap();
+        registry.peers.get_mut(&peer).map(|entry| {
+            f(&mut entry.info);
+            entry.info.clone()
+        })
+    }
+
+    /// Forget a peer whose connection was closed.
+    pub(crate) fn remove(&self, peer: PeerId) {
+        self.inner.lock().unwrap().peers.remove(&peer);
+    }
+
+    /// Remove all peers, and get the handles to stop them.
+    pub(crate) fn drain(&self) -> Vec<PeerHandle> {
+        let peers = std::mem::take(&mut self.inner.lock().unwrap().peers);
+        peers
+            .into_values()
+            .filter_map(|entry| entry.handle)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PeerRegistry;
+
+    #[test]
+    fn peers_come_and_go() {
+        let registry = PeerRegistry::default();
+        let a = registry.register("10.0.0.1:5000".to_owned());
+        let b = registry.register("10.0.0.2:5000".to_owned());
+        assert_ne!(a, b);
+
+        let info = registry.update(b, |info| {
+            info.client = Some("logger".to_owned());
+            info.samples += 10;
+        });
+        assert_eq!(info.unwrap().samples, 10);
+
+        registry.remove(a);
+        assert!(registry.update(a, |info| info.errors += 1).is_none());
+        let peers = registry.peers();
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].client.as_deref(), Some("logger"));
+
+        assert!(registry.kick(b));
+        assert!(!registry.kick(b));
+        assert_eq!(registry.len(), 0);
+    }
+}
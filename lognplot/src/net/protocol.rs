@@ -0,0 +1,109 @@
+//! Protocol versions and optional features.
+//!
+//! A client can introduce itself with a hello message, carrying its
+//! protocol version and the features it supports. The server answers
+//! with the version and features which both sides support. Clients
+//! which start sending data right away are treated as version 0, and
+//! are never sent anything they did not ask for.
+
+use super::compression::{FEATURE_LZ4, FEATURE_ZSTD};
+
+/// Version of the protocol implemented here.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Queries for names, summaries and samples.
+pub const FEATURE_REQUESTS: &str = "requests";
+
+/// Subscriptions on new data.
+pub const FEATURE_SUBSCRIBE: &str = "subscribe";
+
+/// Samples sent by signal id, see the `compact` module.
+pub const FEATURE_SIGNAL_IDS: &str = "signal_ids";
+
+/// The optional features implemented here.
+#[cfg(not(feature = "compression"))]
+pub const FEATURES: &[&str] = &[FEATURE_REQUESTS, FEATURE_SUBSCRIBE, FEATURE_SIGNAL_IDS];
+
+/// The optional features implemented here.
+#[cfg(feature = "compression")]
+pub const FEATURES: &[&str] = &[
+    FEATURE_REQUESTS,
+    FEATURE_SUBSCRIBE,
+    FEATURE_SIGNAL_IDS,
+    super::compression::FEATURE_ZSTD,
+    super::compression::FEATURE_LZ4,
+];
+
+/// What two sides of a connection agreed upon.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    /// The protocol version in use.
+    pub version: u32,
+
+    /// Name of the other side.
+    pub peer: String,
+
+    /// The features both sides support.
+    pub features: Vec<String>,
+}
+
+impl Session {
+    /// The session with a client which did not introduce itself.
+    pub fn legacy() -> Self {
+        Session {
+            version: 0,
+            peer: "unknown".to_owned(),
+            features: vec![],
+        }
+    }
+
+    /// Agree on the lowest version, and the features supported by both sides.
+    pub fn negotiate(version: u32, peer: &str, features: &[String]) -> Self {
+        let features = features
+            .iter()
+            .filter(|feature| FEATURES.contains(&feature.as_str()))
+            .cloned()
+            .collect();
+        Session {
+            version: version.min(PROTOCOL_VERSION),
+            peer: peer.to_owned(),
+            features,
+        }
+    }
+
+    /// Test if the other side did not introduce itself.
+    pub fn is_legacy(&self) -> bool {
+        self.version == 0
+    }
+
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+}
+
+/// The features implemented here, to announce them to the other side.
+///
+/// Compression is left out, it is only offered on request.
+pub fn supported_features() -> Vec<String> {
+    FEATURES
+        .iter()
+        .filter(|f| ![FEATURE_ZSTD, FEATURE_LZ4].contains(f))
+        .map(|f| (*f).to_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Session, FEATURE_SUBSCRIBE, PROTOCOL_VERSION};
+
+    #[test]
+    fn negotiation() {
+        let features = vec!["subscribe".to_owned(), "teleport".to_owned()];
+        let session = Session::negotiate(PROTOCOL_VERSION + 3, "python", &features);
+        assert_eq!(session.version, PROTOCOL_VERSION);
+        assert_eq!(session.features, vec![FEATURE_SUBSCRIBE.to_owned()]);
+        assert!(!session.is_legacy());
+        assert!(!session.supports("teleport"));
+        assert!(Session::legacy().is_legacy());
+    }
+}
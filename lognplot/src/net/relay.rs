@@ -0,0 +1,237 @@
+//! Forward signals to other lognplot servers.
+//!
+//! A relay subscribes on new data of signals matching glob patterns in a
+//! local database, and sends it to one or more upstream servers. Each
+//! upstream has a buffered client, which holds the data while the
+//! upstream cannot be reached, and which reconnects by itself. The client
+//! sends the metadata of the forwarded signals again after reconnecting.
+
+use std::collections::HashSet;
+use std::thread;
+
+use futures::channel::oneshot;
+use futures::{FutureExt, StreamExt};
+
+use super::buffered_client::{BufferedClient, BufferedClientOptions};
+use super::payload::SampleBatch;
+use crate::tsdb::{DataBatch, GlobPattern, SignalMetadata, TsDbHandle};
+
+/// Amount of batches queued between the database and the relay.
+const RELAY_QUEUE_CAPACITY: usize = 1000;
+
+/// What to forward, and where to.
+#[derive(Debug, Clone)]
+pub struct RelayOptions {
+    /// Glob patterns of the signals to forward.
+    pub patterns: Vec<String>,
+
+    /// Addresses of the servers to forward to.
+    pub upstreams: Vec<String>,
+
+    /// Put in front of the name of each forwarded signal, for example
+    /// `bench1/`, to tell apart the data of several sources upstream.
+    pub prefix: Option<String>,
+
+    /// Options of the connection to each upstream, such as the amount
+    /// of data to hold while it cannot be reached.
+    pub client: BufferedClientOptions,
+}
+
+impl Default for RelayOptions {
+    fn default() -> Self {
+        RelayOptions {
+            patterns: vec!["**".to_owned()],
+            upstreams: vec![],
+            prefix: None,
+            client: BufferedClientOptions::default(),
+        }
+    }
+}
+
+/// A handle to a running relay.
+pub struct RelayHandle {
+    thread: thread::JoinHandle<()>,
+    kill_switch: oneshot::Sender<()>,
+}
+
+impl RelayHandle {
+    /// Stop forwarding, after sending the data which is held, if connected.
+    pub fn stop(self) {
+        if self.kill_switch.send(()).is_err() {
+            debug!("Relay already stopped");
+        }
+        if self.thread.join().is_err() {
+            error!("Relay thread panicked");
+        }
+    }
+}
+
+/// Start forwarding new data in the database to the upstream servers.
+pub fn start_relay(db: TsDbHandle, options: RelayOptions) -> RelayHandle {
+    // Subscribe right away, so that no data is missed:
+    let queues = options.patterns.iter().enumerate().map(|(index, pattern)| {
+        db.new_data_queue(pattern, RELAY_QUEUE_CAPACITY)
+            .map(move |batch| (index, batch))
+    });
+    let queues = futures::stream::select_all(queues);
+
+    let (kill_switch, kill_switch_endpoint) = oneshot::channel::<()>();
+    let thread = thread::Builder::new()
+        .name("lognplot-relay".to_owned())
+        .spawn(move || {
+            futures::executor::block_on(relay_prog(db, options, queues, kill_switch_endpoint))
+        })
+        .expect("Failed to start relay thread");
+
+    RelayHandle {
+        thread,
+        kill_switch,
+    }
+}
+
+async fn relay_prog<Q>(
+    db: TsDbHandle,
+    options: RelayOptions,
+    queues: Q,
+    kill_switch_endpoint: oneshot::Receiver<()>,
+) where
+    Q: futures::Stream<Item = (usize, DataBatch)> + Unpin,
+{
+    let clients: Vec<BufferedClient> = options
+        .upstreams
+        .iter()
+        .map(|addr| {
+            info!("Relaying {:?} to {}", options.patterns, addr);
+            BufferedClient::with_options(addr, options.client.clone())
+        })
+        .collect();
+    let mut forwarder = Forwarder::new(options.patterns, options.prefix);
+
+    let mut queues = queues.fuse();
+    let mut kill_switch_endpoint = kill_switch_endpoint.fuse();
+    loop {
+        futures::select! {
+            optional_batch = queues.next() => {
+                if let Some((index, batch)) = optional_batch {
+                    if batch.dropped > 0 {
+                        warn!("Relay lags behind, dropped {} batches", batch.dropped);
+                    }
+                    if let Some(forwarding) = forwarder.forward(&db, index, &batch) {
+                        for client in &clients {
+                            if let Some(metadata) = &forwarding.metadata {
+                                client.send_metadata(&forwarding.name, metadata);
+                            }
+                            for packet in &forwarding.packets {
+                                client.send_batch(packet.clone());
+                            }
+                        }
+                    }
+                } else {
+                    break;
+                }
+            },
+            _x = kill_switch_endpoint => {
+                info!("Relay stopped by kill switch.");
+                break;
+            }
+        }
+    }
+}
+
+/// What to send upstream of a batch of new data.
+struct Forwarding {
+    /// Name of the signal upstream.
+    name: String,
+
+    /// Metadata of a signal which was not forwarded before.
+    metadata: Option<SignalMetadata>,
+
+    packets: Vec<SampleBatch>,
+}
+
+/// Turns new data into packets for the upstream servers.
+struct Forwarder {
+    patterns: Vec<GlobPattern>,
+    prefix: String,
+
+    /// Signals of which the metadata was handed to the clients.
+    known: HashSet<String>,
+}
+
+impl Forwarder {
+    fn new(patterns: Vec<String>, prefix: Option<String>) -> Self {
+        Forwarder {
+            patterns: patterns.iter().map(|p| GlobPattern::new(p)).collect(),
+            prefix: prefix.unwrap_or_default(),
+            known: HashSet::new(),
+        }
+    }
+
+    /// What to forward of a batch, received via the pattern with the given index.
+    ///
+    /// Signals matching several patterns are forwarded for the first
+    /// one only. The first batch of a signal carries its metadata.
+    fn forward(&mut self, db: &TsDbHandle, index: usize, batch: &DataBatch) -> Option<Forwarding> {
+        let earlier_match = self.patterns[..index]
+            .iter()
+            .any(|pattern| pattern.matches(&batch.name));
+        if earlier_match {
+            return None;
+        }
+
+        let name = format!("{}{}", self.prefix, batch.name);
+        let metadata = if self.known.insert(batch.name.clone()) {
+            db.get_metadata(&batch.name)
+        } else {
+            None
+        };
+        let packets = SampleBatch::from_new_observations(&name, &batch.observations);
+        Some(Forwarding {
+            name,
+            metadata,
+            packets,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Forwarder;
+    use crate::time::TimeStamp;
+    use crate::tsdb::observations::{Observation, Sample};
+    use crate::tsdb::{DataBatch, NewObservations, SignalMetadata, TsDb};
+
+    #[test]
+    fn prefix_and_overlapping_patterns() {
+        let db = TsDb::default().into_handle();
+        let metadata = SignalMetadata {
+            unit: Some("V".to_owned()),
+            ..SignalMetadata::default()
+        };
+        db.set_metadata("motor/voltage", metadata);
+
+        let batch = DataBatch {
+            name: "motor/voltage".to_owned(),
+            observations: NewObservations::Values(vec![Observation::new(
+                TimeStamp::new(1.0),
+                Sample::new(12.0),
+            )]),
+            dropped: 0,
+        };
+
+        let patterns = vec!["motor/**".to_owned(), "**".to_owned()];
+        let mut forwarder = Forwarder::new(patterns, Some("bench1/".to_owned()));
+        assert!(forwarder.forward(&db, 1, &batch).is_none());
+
+        let forwarding = forwarder.forward(&db, 0, &batch).unwrap();
+        assert_eq!(forwarding.name, "bench1/motor/voltage");
+        assert_eq!(forwarding.metadata.unwrap().unit.as_deref(), Some("V"));
+        assert_eq!(forwarding.packets.len(), 1);
+        assert_eq!(forwarding.packets[0].name(), "bench1/motor/voltage");
+        assert_eq!(forwarding.packets[0].values(), vec![(1.0, 12.0)]);
+
+        // The clients resend the metadata, it is handed to them only once:
+        let forwarding = forwarder.forward(&db, 0, &batch).unwrap();
+        assert!(forwarding.metadata.is_none());
+    }
+}
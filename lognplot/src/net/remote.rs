@@ -0,0 +1,202 @@
+//! Client to pull data out of a running lognplot.
+//!
+//! The `RemoteClient` mirrors the query part of the `TsDbApi`, but
+//! asks a server over TCP. Each call blocks until the answer arrives.
+
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind};
+
+use super::client::TcpClient;
+use super::payload::SampleBatch;
+use super::protocol::Session;
+use super::request::{Request, Response, ServerMessage, SignalSummary};
+use crate::time::{TimeSpan, TimeStamp};
+use crate::tsdb::observations::{Observation, Sample};
+
+/// Query a lognplot server, and subscribe on its data.
+pub struct RemoteClient {
+    client: TcpClient,
+    session: Session,
+    next_id: u64,
+
+    /// Data of subscriptions which arrived while waiting for a response.
+    pending: VecDeque<SampleBatch>,
+}
+
+impl RemoteClient {
+    pub fn new(addr: &str) -> std::io::Result<Self> {
+        RemoteClient::from_client(TcpClient::new(addr)?)
+    }
+
+    /// Use a new connection, which can still be used to send data.
+    ///
+    /// This performs the handshake with the server.
+    pub fn from_client(mut client: TcpClient) -> std::io::Result<Self> {
+        let session = client.hello("lognplot remote client")?;
+        Ok(RemoteClient {
+            client,
+            session,
+            next_id: 1,
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// The protocol version and features agreed with the server.
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// The underlying connection, for example to send data.
+    pub fn client(&mut self) -> &mut TcpClient {
+        &mut self.client
+    }
+
+    /// Get the names of all signals.
+    pub fn get_signal_names(&mut self) -> std::io::Result<Vec<String>> {
+        let id = self.new_id();
+        match self.request(Request::SignalNames { id })? {
+            Response::SignalNames { names, .. } => Ok(names),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Get a summary of a signal, over all time when no time span is given.
+    pub fn summary(
+        &mut self,
+        name: &str,
+        timespan: Option<&TimeSpan>,
+    ) -> std::io::Result<Option<SignalSummary>> {
+        let id = self.new_id();
+        let request = Request::Summary {
+            id,
+            name: name.to_owned(),
+            start: timespan.map(|t| t.start.amount),
+            end: timespan.map(|t| t.end.amount),
+        };
+        match self.request(request)? {
+            Response::Summary { summary, .. } => Ok(summary),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Download all samples of a signal.
+    pub fn get_raw_samples(
+        &mut self,
+        name: &str,
+    ) -> std::io::Result<Option<Vec<Observation<Sample>>>> {
+        self.raw_samples(name, None)
+    }
+
+    /// Download the samples of a signal within a time span.
+    pub fn get_raw_range(
+        &mut self,
+        name: &str,
+        timespan: &TimeSpan,
+    ) -> std::io::Result<Option<Vec<Observation<Sample>>>> {
+        self.raw_samples(name, Some(timespan))
+    }
+
+    fn raw_samples(
+        &mut self,
+        name: &str,
+        timespan: Option<&TimeSpan>,
+    ) -> std::io::Result<Option<Vec<Observation<Sample>>>> {
+        let id = self.new_id();
+        let request = Request::RawSamples {
+            id,
+            name: name.to_owned(),
+            start: timespan.map(|t| t.start.amount),
+            end: timespan.map(|t| t.end.amount),
+        };
+        match self.request(request)? {
+            Response::RawSamples { samples, .. } => Ok(samples.map(|samples| {
+                samples
+                    .into_iter()
+                    .map(|(t, value)| Observation::new(TimeStamp::new(t), Sample::new(value)))
+                    .collect()
+            })),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Subscribe on new data of all signals matching a glob pattern.
+    ///
+    /// Returns the id of the subscription. The data can be retrieved
+    /// with `next_batch`.
+    pub fn subscribe(&mut self, pattern: &str) -> std::io::Result<u64> {
+        let id = self.new_id();
+        let request = Request::Subscribe {
+            id,
+            pattern: pattern.to_owned(),
+        };
+        match self.request(request)? {
+            Response::Subscribed { subscription, .. } => Ok(subscription),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub fn unsubscribe(&mut self, subscription: u64) -> std::io::Result<()> {
+        let id = self.new_id();
+        match self.request(Request::Unsubscribe { id, subscription })? {
+            Response::Unsubscribed { .. } => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Wait for the next batch of subscribed data.
+    pub fn next_batch(&mut self) -> std::io::Result<SampleBatch> {
+        if let Some(batch) = self.pending.pop_front() {
+            return Ok(batch);
+        }
+
+        loop {
+            if let ServerMessage::Data(batch) = self.read_message()? {
+                return Ok(batch);
+            }
+        }
+    }
+
+    fn new_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Send a request, and wait for the response to it.
+    fn request(&mut self, request: Request) -> std::io::Result<Response> {
+        let id = request.id();
+        self.client.write_blob(request.to_bytes())?;
+
+        loop {
+            match self.read_message()? {
+                ServerMessage::Response(Response::Error {
+                    id: error_id,
+                    message,
+                }) if error_id == id => {
+                    return Err(Error::other(message));
+                }
+                ServerMessage::Response(response) => {
+                    if response.id() == id {
+                        return Ok(response);
+                    }
+                    warn!("Ignoring response {:?}", response);
+                }
+                ServerMessage::Data(batch) => {
+                    self.pending.push_back(batch);
+                }
+            }
+        }
+    }
+
+    fn read_message(&mut self) -> std::io::Result<ServerMessage> {
+        let data = self.client.read_blob()?;
+        ServerMessage::from_bytes(&data).map_err(|err| Error::new(ErrorKind::InvalidData, err))
+    }
+}
+
+fn unexpected(response: Response) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("Unexpected response: {:?}", response),
+    )
+}
@@ -0,0 +1,502 @@
+//! Requests from clients, and the responses of the server.
+//!
+//! Next to sending data, a client can ask the server for signal
+//! names, summaries and raw samples, and subscribe on new data. Each
+//! request carries an id, which the server copies into its response.
+//! New data of a subscription is pushed to the client as normal
+//! `SampleBatch` packets.
+//!
+//! The hello request starts a connection, see the `protocol` module.
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "server")]
+use super::compact::{CompactPacket, COMPACT_TYPES};
+use super::payload::SampleBatch;
+use super::protocol::Session;
+use crate::time::{TimeSpan, TimeStamp};
+use crate::tsdb::{Summary, TsDbHandle};
+
+/// A question from a client to the server.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum Request {
+    /// Introduction of the client, answered with a welcome.
+    #[serde(rename = "hello")]
+    Hello {
+        version: u32,
+
+        /// Name of the client program.
+        client: String,
+
+        #[serde(default)]
+        features: Vec<String>,
+
+        /// The shared secret, for servers which require one.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token: Option<String>,
+    },
+
+    /// List the names of all signals.
+    #[serde(rename = "get_names")]
+    SignalNames { id: u64 },
+
+    /// Summarize a signal, over all time or over the given period.
+    #[serde(rename = "get_summary")]
+    Summary {
+        id: u64,
+
+        name: String,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        start: Option<f64>,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        end: Option<f64>,
+    },
+
+    /// Download the raw samples of a signal, of all time or of the given period.
+    #[serde(rename = "get_samples")]
+    RawSamples {
+        id: u64,
+
+        name: String,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        start: Option<f64>,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        end: Option<f64>,
+    },
+
+    /// Receive new data of all signals matching a glob pattern.
+    #[serde(rename = "subscribe")]
+    Subscribe { id: u64, pattern: String },
+
+    /// Stop a subscription.
+    #[serde(rename = "unsubscribe")]
+    Unsubscribe { id: u64, subscription: u64 },
+}
+
+/// Most raw samples sent in a single response.
+///
+/// A response is sent as a single frame, which must stay well within
+/// the frame size limit of the connection. Larger downloads must be
+/// split into several periods by the client.
+pub const MAX_RAW_SAMPLES: usize = 100_000;
+
+/// Values of the type field of requests.
+#[cfg(feature = "server")]
+const REQUEST_TYPES: &[&str] = &[
+    "hello",
+    "get_names",
+    "get_summary",
+    "get_samples",
+    "subscribe",
+    "unsubscribe",
+];
+
+impl Request {
+    /// The id of the request, the hello request has none.
+    pub fn id(&self) -> Option<u64> {
+        match self {
+            Request::Hello { .. } => None,
+            Request::SignalNames { id }
+            | Request::Summary { id, .. }
+            | Request::RawSamples { id, .. }
+            | Request::Subscribe { id, .. }
+            | Request::Unsubscribe { id, .. } => Some(*id),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = vec![];
+        ciborium::ser::into_writer(self, &mut data).unwrap();
+        data
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, String> {
+        ciborium::de::from_reader(data).map_err(|e| format!("{:?}", e))
+    }
+
+    /// Answer requests which only need the database.
+    ///
+    /// The hello and subscriptions need the connection, so those
+    /// are handled by the server itself.
+    pub fn respond(&self, db: &TsDbHandle) -> Option<Response> {
+        match self {
+            Request::SignalNames { id } => Some(Response::SignalNames {
+                id: *id,
+                names: db.get_signal_names(),
+            }),
+            Request::Summary {
+                id,
+                name,
+                start,
+                end,
+            } => {
+                let timespan = make_timespan(*start, *end);
+                let summary = db
+                    .summary(name, timespan.as_ref())
+                    .map(|s| SignalSummary::from(&s));
+                Some(Response::Summary { id: *id, summary })
+            }
+            Request::RawSamples {
+                id,
+                name,
+                start,
+                end,
+            } => {
+                let observations = match make_timespan(*start, *end) {
+                    Some(timespan) => db.get_raw_range(name, &timespan),
+                    None => db.get_raw_samples(name),
+                };
+                if let Some(observations) = &observations {
+                    if observations.len() > MAX_RAW_SAMPLES {
+                        let message = format!(
+                            "Signal {} has {} samples in this period, at most {} can be sent at once",
+                            name,
+                            observations.len(),
+                            MAX_RAW_SAMPLES
+                        );
+                        return Some(Response::Error {
+                            id: Some(*id),
+                            message,
+                        });
+                    }
+                }
+                let samples = observations.map(|observations| {
+                    observations
+                        .iter()
+                        .map(|o| (o.timestamp.amount, o.value.value))
+                        .collect()
+                });
+                Some(Response::RawSamples { id: *id, samples })
+            }
+            Request::Hello { .. } | Request::Subscribe { .. } | Request::Unsubscribe { .. } => None,
+        }
+    }
+}
+
+/// A time span from optional limits, or none for all time.
+fn make_timespan(start: Option<f64>, end: Option<f64>) -> Option<TimeSpan> {
+    if start.is_none() && end.is_none() {
+        None
+    } else {
+        let start = TimeStamp::new(start.unwrap_or(f64::NEG_INFINITY));
+        let end = TimeStamp::new(end.unwrap_or(f64::INFINITY));
+        Some(TimeSpan::new(start, end))
+    }
+}
+
+/// The answer of the server to a request, with the id of the request.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum Response {
+    /// Answer to the hello, with the agreed version and features.
+    #[serde(rename = "welcome")]
+    Welcome {
+        version: u32,
+
+        /// Name and version of the server program.
+        server: String,
+
+        features: Vec<String>,
+    },
+
+    /// A message could not be handled.
+    ///
+    /// The id is present when the failing message had one.
+    #[serde(rename = "error")]
+    Error {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<u64>,
+
+        message: String,
+    },
+
+    #[serde(rename = "names")]
+    SignalNames { id: u64, names: Vec<String> },
+
+    /// The summary, or nothing when the signal does not exist.
+    #[serde(rename = "summary")]
+    Summary {
+        id: u64,
+        summary: Option<SignalSummary>,
+    },
+
+    /// The samples as (t, value) pairs, or nothing when there is no such value signal.
+    #[serde(rename = "raw_samples")]
+    RawSamples {
+        id: u64,
+        samples: Option<Vec<(f64, f64)>>,
+    },
+
+    /// A subscription was made, new data follows as sample batches.
+    #[serde(rename = "subscribed")]
+    Subscribed { id: u64, subscription: u64 },
+
+    #[serde(rename = "unsubscribed")]
+    Unsubscribed { id: u64 },
+}
+
+impl Response {
+    /// Create the welcome for a client.
+    pub fn welcome(session: &Session) -> Self {
+        Response::Welcome {
+            version: session.version,
+            server: format!("lognplot {}", env!("CARGO_PKG_VERSION")),
+            features: session.features.clone(),
+        }
+    }
+
+    /// The id of the request this response belongs to.
+    pub fn id(&self) -> Option<u64> {
+        match self {
+            Response::Welcome { .. } => None,
+            Response::Error { id, .. } => *id,
+            Response::SignalNames { id, .. }
+            | Response::Summary { id, .. }
+            | Response::RawSamples { id, .. }
+            | Response::Subscribed { id, .. }
+            | Response::Unsubscribed { id } => Some(*id),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = vec![];
+        ciborium::ser::into_writer(self, &mut data).unwrap();
+        data
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, String> {
+        ciborium::de::from_reader(data).map_err(|e| format!("{:?}", e))
+    }
+}
+
+/// Summary of a signal, as transferred over the network.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignalSummary {
+    /// Amount of observations.
+    pub count: usize,
+
+    /// Timestamp of the first observation.
+    pub start: f64,
+
+    /// Timestamp of the last observation.
+    pub end: f64,
+
+    /// Statistics of the values, only present for value signals.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mean: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stddev: Option<f64>,
+}
+
+impl From<&Summary> for SignalSummary {
+    fn from(summary: &Summary) -> Self {
+        let timespan = summary.timespan();
+        let mut signal_summary = SignalSummary {
+            count: summary.count(),
+            start: timespan.start.amount,
+            end: timespan.end.amount,
+            min: None,
+            max: None,
+            mean: None,
+            stddev: None,
+        };
+        if let Summary::Value(aggregation) = summary {
+            let metrics = aggregation.metrics();
+            signal_summary.min = Some(metrics.min);
+            signal_summary.max = Some(metrics.max);
+            signal_summary.mean = Some(metrics.mean());
+            signal_summary.stddev = Some(metrics.stddev());
+        }
+        signal_summary
+    }
+}
+
+/// A packet sent by a client to the server.
+#[cfg(feature = "server")]
+#[derive(Debug)]
+pub enum ClientMessage {
+    Data(SampleBatch),
+
+    /// Data by signal id.
+    Compact(CompactPacket),
+
+    Request(Request),
+}
+
+#[cfg(feature = "server")]
+impl ClientMessage {
+    /// Decode a packet, or describe why it cannot be handled.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Response> {
+        // Data is by far the most common:
+        let batch_error = match SampleBatch::from_bytes(data) {
+            Ok(batch) => return Ok(ClientMessage::Data(batch)),
+            Err(err) => err,
+        };
+
+        let (typ, id) = peek_type_and_id(data);
+        let message = match typ {
+            Some(typ) if COMPACT_TYPES.contains(&typ.as_str()) => {
+                match CompactPacket::from_bytes(data) {
+                    Ok(packet) => return Ok(ClientMessage::Compact(packet)),
+                    Err(err) => format!("Invalid {} packet: {}", typ, err),
+                }
+            }
+            Some(typ) if REQUEST_TYPES.contains(&typ.as_str()) => match Request::from_bytes(data) {
+                Ok(request) => return Ok(ClientMessage::Request(request)),
+                Err(err) => format!("Invalid {} request: {}", typ, err),
+            },
+            Some(typ) => format!(
+                "Unsupported or invalid message of type {}: {}",
+                typ, batch_error
+            ),
+            None => "Message without type".to_owned(),
+        };
+        Err(Response::Error { id, message })
+    }
+}
+
+/// Get the type and id fields of a packet, if present.
+#[cfg(feature = "server")]
+fn peek_type_and_id(data: &[u8]) -> (Option<String>, Option<u64>) {
+    use ciborium::value::Value;
+    use std::convert::TryFrom;
+
+    let mut typ = None;
+    let mut id = None;
+    if let Ok(Value::Map(entries)) = ciborium::de::from_reader::<Value, _>(data) {
+        for (key, value) in entries {
+            match (key.as_text(), value) {
+                (Some("type"), Value::Text(text)) => typ = Some(text),
+                (Some("id"), Value::Integer(integer)) => id = u64::try_from(integer).ok(),
+                _ => {}
+            }
+        }
+    }
+    (typ, id)
+}
+
+/// A packet sent by the server to a client.
+#[derive(Debug)]
+pub enum ServerMessage {
+    Response(Response),
+
+    /// New data of a subscription.
+    Data(SampleBatch),
+}
+
+impl ServerMessage {
+    pub fn from_bytes(data: &[u8]) -> Result<Self, String> {
+        match Response::from_bytes(data) {
+            Ok(response) => Ok(ServerMessage::Response(response)),
+            Err(_) => SampleBatch::from_bytes(data).map(ServerMessage::Data),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "server")]
+    use super::ClientMessage;
+    use super::{Request, Response, ServerMessage};
+    use crate::time::TimeStamp;
+    use crate::tsdb::observations::{Observation, Sample};
+    use crate::tsdb::TsDb;
+
+    #[test]
+    fn request_response() {
+        let db = TsDb::default().into_handle();
+        for t in 0..10 {
+            let observation = Observation::new(TimeStamp::new(t as f64), Sample::new(2.0));
+            db.add_value("motor", observation);
+        }
+
+        let request = Request::RawSamples {
+            id: 3,
+            name: "motor".to_owned(),
+            start: Some(2.0),
+            end: Some(4.0),
+        };
+        let request = Request::from_bytes(&request.to_bytes()).unwrap();
+        let response = request.respond(&db).unwrap();
+        let data = response.to_bytes();
+        match ServerMessage::from_bytes(&data).unwrap() {
+            ServerMessage::Response(Response::RawSamples { id, samples }) => {
+                assert_eq!(id, 3);
+                assert_eq!(samples, Some(vec![(2.0, 2.0), (3.0, 2.0), (4.0, 2.0)]));
+            }
+            other => panic!("Unexpected message: {:?}", other),
+        }
+
+        let request = Request::Summary {
+            id: 4,
+            name: "motor".to_owned(),
+            start: None,
+            end: None,
+        };
+        match request.respond(&db).unwrap() {
+            Response::Summary { summary, .. } => {
+                let summary = summary.unwrap();
+                assert_eq!(summary.count, 10);
+                assert_eq!(summary.mean, Some(2.0));
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn unsupported_messages() {
+        let mut data: Vec<u8> = vec![];
+        let packet = vec![("type", "teleport"), ("id", "x")];
+        ciborium::ser::into_writer(
+            &packet
+                .into_iter()
+                .collect::<std::collections::HashMap<_, _>>(),
+            &mut data,
+        )
+        .unwrap();
+        match ClientMessage::from_bytes(&data) {
+            Err(Response::Error { id: None, message }) => {
+                assert!(message.contains("teleport"), "{}", message);
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        // A request with a missing field:
+        let mut data: Vec<u8> = vec![];
+        let mut packet = std::collections::HashMap::new();
+        packet.insert("type", ciborium::value::Value::from("get_summary"));
+        packet.insert("id", ciborium::value::Value::from(7));
+        ciborium::ser::into_writer(&packet, &mut data).unwrap();
+        match ClientMessage::from_bytes(&data) {
+            Err(Response::Error { id, message }) => {
+                assert_eq!(id, Some(7));
+                assert!(message.starts_with("Invalid get_summary request"));
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        let hello = Request::Hello {
+            version: 1,
+            client: "test".to_owned(),
+            features: vec![],
+            token: None,
+        };
+        match ClientMessage::from_bytes(&hello.to_bytes()) {
+            Ok(ClientMessage::Request(request)) => assert_eq!(request, hello),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+}
@@ -1,14 +1,21 @@
 //! TCP based server for data
 
-use super::peer::{process_client, PeerHandle};
-use super::peer_processor::start_peer_event_processor;
+use super::peer::process_client;
+use super::peer_processor::{start_peer_event_processor, PeerEvent};
+use super::peer_registry::{PeerId, PeerInfo, PeerRegistry};
+use super::udp::{start_udp_listener, UdpSourceStatistics, UdpStatistics};
+#[cfg(unix)]
+use super::unix::{bind_unix_socket, start_unix_listener};
 use crate::tracer::{AnyTracer, Tracer};
 use crate::tsdb::TsDbHandle;
 use futures::channel::{mpsc, oneshot};
 use futures::{FutureExt, StreamExt};
-use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::thread;
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpStream};
 
 /// This is a handle to a started TCP server.
 /// You can use this handle to stop the server.
@@ -18,9 +25,176 @@ pub struct ServerHandle {
 
     // This switch can be used to trigger shutdown of the server.
     kill_switch: oneshot::Sender<()>,
+
+    udp_statistics: UdpStatistics,
+
+    peers: PeerRegistry,
+
+    local_addr: SocketAddr,
+    udp_local_addr: Option<SocketAddr>,
+}
+
+/// Where the server listens.
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    /// TCP port for connections.
+    pub port: u16,
+
+    /// UDP port for datagrams, if any.
+    pub udp_port: Option<u16>,
+
+    /// Path of a unix domain socket for local connections, if any.
+    pub unix_path: Option<PathBuf>,
+
+    /// Address to listen on for TCP and UDP, by default all addresses.
+    pub bind_address: Option<IpAddr>,
+
+    /// Encrypt TCP connections, this requires the tls feature.
+    pub tls: Option<TlsOptions>,
+
+    /// Shared secret which clients must present in their hello.
+    ///
+    /// Datagrams cannot carry it, so it cannot be combined with a UDP port.
+    pub token: Option<String>,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        ServerOptions {
+            port: 12345,
+            udp_port: None,
+            unix_path: None,
+            bind_address: None,
+            tls: None,
+            token: None,
+        }
+    }
+}
+
+/// Certificate and key files of an encrypting server, in PEM format.
+#[derive(Debug, Clone)]
+pub struct TlsOptions {
+    pub certificate: PathBuf,
+    pub key: PathBuf,
+
+    /// Certificate authorities of client certificates.
+    ///
+    /// When given, only clients with a certificate signed by one of
+    /// these can connect.
+    pub client_ca_certificates: Option<PathBuf>,
+}
+
+#[cfg(feature = "tls")]
+type TlsAcceptor = tokio_rustls::TlsAcceptor;
+
+#[cfg(feature = "tls")]
+fn tls_acceptor(options: &TlsOptions) -> std::io::Result<TlsAcceptor> {
+    let config = super::tls::server_config(
+        &options.certificate,
+        &options.key,
+        options.client_ca_certificates.as_deref(),
+    )?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+#[cfg(not(feature = "tls"))]
+enum TlsAcceptor {}
+
+#[cfg(not(feature = "tls"))]
+fn tls_acceptor(_options: &TlsOptions) -> std::io::Result<TlsAcceptor> {
+    Err(std::io::Error::other(
+        "Encrypted connections require the tls feature",
+    ))
+}
+
+/// Sockets bound before the server thread starts, so that errors reach the caller.
+struct Listeners {
+    tcp: std::net::TcpListener,
+    udp: Option<std::net::UdpSocket>,
+    #[cfg(unix)]
+    unix: Option<(PathBuf, std::os::unix::net::UnixListener)>,
+    tls_acceptor: Option<TlsAcceptor>,
+}
+
+impl Listeners {
+    fn bind(options: &ServerOptions) -> std::io::Result<Self> {
+        if options.token.is_some() && options.udp_port.is_some() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "A token cannot be combined with a UDP port, since datagrams cannot carry it",
+            ));
+        }
+
+        let tls_acceptor = if let Some(tls) = &options.tls {
+            Some(tls_acceptor(tls)?)
+        } else {
+            None
+        };
+
+        let ip = options
+            .bind_address
+            .unwrap_or(IpAddr::V6(Ipv6Addr::UNSPECIFIED));
+        let udp = if let Some(udp_port) = options.udp_port {
+            Some(std::net::UdpSocket::bind(SocketAddr::new(ip, udp_port))?)
+        } else {
+            None
+        };
+
+        info!("Starting up server at port {}!", options.port);
+        let tcp = std::net::TcpListener::bind(SocketAddr::new(ip, options.port))?;
+
+        #[cfg(unix)]
+        let unix = if let Some(unix_path) = &options.unix_path {
+            Some((unix_path.clone(), bind_unix_socket(unix_path)?))
+        } else {
+            None
+        };
+        #[cfg(not(unix))]
+        if options.unix_path.is_some() {
+            warn!("Unix domain sockets are not supported on this platform");
+        }
+
+        Ok(Listeners {
+            tcp,
+            udp,
+            #[cfg(unix)]
+            unix,
+            tls_acceptor,
+        })
+    }
 }
 
 impl ServerHandle {
+    /// Loss statistics of each source which sent datagrams.
+    pub fn udp_statistics(&self) -> HashMap<SocketAddr, UdpSourceStatistics> {
+        self.udp_statistics.lock().unwrap().clone()
+    }
+
+    /// The clients which are connected right now.
+    pub fn peers(&self) -> Vec<PeerInfo> {
+        self.peers.peers()
+    }
+
+    /// Disconnect a client, returns false when it is not connected.
+    pub fn kick(&self, peer: PeerId) -> bool {
+        self.peers.kick(peer)
+    }
+
+    /// The address on which the server accepts TCP connections.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// The address on which the server receives datagrams, if any.
+    pub fn udp_local_addr(&self) -> Option<SocketAddr> {
+        self.udp_local_addr
+    }
+
+    /// The registry of connected clients, which stays valid while the server runs.
+    pub fn peer_registry(&self) -> PeerRegistry {
+        self.peers.clone()
+    }
+
     pub fn stop(self) {
         match self.kill_switch.send(()) {
             Err(_) => {
@@ -34,8 +208,41 @@ impl ServerHandle {
     }
 }
 
+/// Start a server on the given port.
+///
+/// Panics when the server cannot be started, use `start_server`
+/// to handle such errors.
 pub fn run_server(db: TsDbHandle, port: u16, perf_tracer: Arc<AnyTracer>) -> ServerHandle {
+    let options = ServerOptions {
+        port,
+        ..ServerOptions::default()
+    };
+    start_server(db, options, perf_tracer)
+        .unwrap_or_else(|err| panic!("Could not start server at port {}: {}", port, err))
+}
+
+/// Start a server in a thread of its own.
+///
+/// The sockets are bound, and the certificates loaded, before this
+/// returns, so that an unusable configuration is reported here.
+pub fn start_server(
+    db: TsDbHandle,
+    options: ServerOptions,
+    perf_tracer: Arc<AnyTracer>,
+) -> std::io::Result<ServerHandle> {
+    let listeners = Listeners::bind(&options)?;
+    let local_addr = listeners.tcp.local_addr()?;
+    let udp_local_addr = listeners
+        .udp
+        .as_ref()
+        .map(|socket| socket.local_addr())
+        .transpose()?;
+
     let (kill_switch, kill_switch_receiver) = oneshot::channel::<()>();
+    let udp_statistics = UdpStatistics::default();
+    let server_udp_statistics = udp_statistics.clone();
+    let peers = PeerRegistry::default();
+    let server_peers = peers.clone();
     let thread = thread::spawn(move || {
         info!("Server thread begun!!!");
         let mut runtime = tokio::runtime::Builder::new()
@@ -46,7 +253,17 @@ pub fn run_server(db: TsDbHandle, port: u16, perf_tracer: Arc<AnyTracer>) -> Ser
             .unwrap();
 
         runtime.block_on(async {
-            if let Err(err) = server_prog(db, port, perf_tracer, kill_switch_receiver).await {
+            if let Err(err) = server_prog(
+                db,
+                options,
+                listeners,
+                server_udp_statistics,
+                server_peers,
+                perf_tracer,
+                kill_switch_receiver,
+            )
+            .await
+            {
                 error!("Server stopped with error: {}", err);
             }
         });
@@ -54,41 +271,63 @@ pub fn run_server(db: TsDbHandle, port: u16, perf_tracer: Arc<AnyTracer>) -> Ser
         info!("Server finished!!!");
     });
 
-    ServerHandle {
+    Ok(ServerHandle {
         thread,
         kill_switch,
-    }
+        udp_statistics,
+        peers,
+        local_addr,
+        udp_local_addr,
+    })
 }
 
 async fn server_prog(
     db: TsDbHandle,
-    port: u16,
+    options: ServerOptions,
+    listeners: Listeners,
+    udp_statistics: UdpStatistics,
+    peers: PeerRegistry,
     perf_tracer: Arc<AnyTracer>,
     kill_switch_receiver: oneshot::Receiver<()>,
 ) -> std::io::Result<()> {
-    let peers: Arc<Mutex<Vec<PeerHandle>>> = Arc::new(Mutex::new(vec![]));
-    info!("Starting up server at port {}!", port);
-    // let addr = format!("127.0.0.1:{}", port);
-    // let addr: std::net::SocketAddr = addr.parse().unwrap();
-    let ip = std::net::Ipv6Addr::UNSPECIFIED;
-    let addr = std::net::SocketAddrV6::new(ip, port, 0, 0);
-    let std_listener = std::net::TcpListener::bind(addr)?;
-    // info!("a: only v6={}", std_listener.only_v6()?);
-    // let mut listener = TcpListener::bind(&addr).await?;
-    let mut listener = TcpListener::from_std(std_listener)?;
-    info!("Server listening on {:?}", addr);
+    let tls_acceptor = listeners.tls_acceptor;
+
+    let udp_listener = if let Some(socket) = listeners.udp {
+        Some(start_udp_listener(
+            db.clone(),
+            socket,
+            udp_statistics,
+            perf_tracer.clone(),
+        )?)
+    } else {
+        None
+    };
+
+    let mut listener = TcpListener::from_std(listeners.tcp)?;
+    info!("Server listening on {:?}", listener.local_addr()?);
     let mut kill_switch_receiver = kill_switch_receiver.fuse();
     let mut incoming = listener.incoming().fuse();
 
     let (peer_event_sink, peer_event_rx) = mpsc::unbounded();
-    let peer_processor_handle = start_peer_event_processor(peer_event_rx, perf_tracer.clone());
+    let peer_processor_handle =
+        start_peer_event_processor(peer_event_rx, peers.clone(), perf_tracer.clone());
+
+    #[cfg(unix)]
+    let unix_listener = if let Some((unix_path, unix_listener)) = listeners.unix {
+        Some(start_unix_listener(
+            db.clone(),
+            unix_listener,
+            unix_path,
+            options.token.clone(),
+            peers.clone(),
+            peer_event_sink.clone(),
+        )?)
+    } else {
+        None
+    };
 
     loop {
-        perf_tracer.log_metric(
-            "peers",
-            std::time::Instant::now(),
-            peers.lock().unwrap().len() as f64,
-        );
+        perf_tracer.log_metric("peers", std::time::Instant::now(), peers.len() as f64);
 
         futures::select! {
             _x = kill_switch_receiver => {
@@ -99,8 +338,14 @@ async fn server_prog(
                 if let Some(new_client) = optional_new_client {
                     let peer_socket = new_client?;
                     info!("Client connected!");
-                    let peer = process_client(peer_socket, db.clone(), peer_event_sink.clone());
-                    peers.lock().unwrap().push(peer);
+                    accept_client(
+                        peer_socket,
+                        tls_acceptor.as_ref(),
+                        db.clone(),
+                        options.token.clone(),
+                        &peers,
+                        peer_event_sink.clone(),
+                    );
                 } else {
                     info!("No more incoming connections.");
                     break;
@@ -109,13 +354,60 @@ async fn server_prog(
         };
     }
 
+    #[cfg(unix)]
+    {
+        if let Some(unix_listener) = unix_listener {
+            unix_listener.stop().await?;
+        }
+    }
+
     info!("Shutting down peer connections");
 
-    for peer in peers.lock().unwrap().drain(..) {
+    for peer in peers.drain() {
         peer.stop().await?;
     }
 
     peer_processor_handle.stop().await?;
 
+    if let Some(udp_listener) = udp_listener {
+        udp_listener.stop().await?;
+    }
+
     Ok(())
 }
+
+/// Start handling a TCP connection, encrypted or not.
+fn accept_client(
+    socket: TcpStream,
+    tls_acceptor: Option<&TlsAcceptor>,
+    db: TsDbHandle,
+    token: Option<String>,
+    peers: &PeerRegistry,
+    peer_event_sink: mpsc::UnboundedSender<PeerEvent>,
+) {
+    let address = match socket.peer_addr() {
+        Ok(address) => address.to_string(),
+        Err(_) => "unknown".to_owned(),
+    };
+    match tls_acceptor {
+        #[cfg(feature = "tls")]
+        Some(tls_acceptor) => process_client(
+            tls_acceptor.accept(socket),
+            address,
+            db,
+            token,
+            peers,
+            peer_event_sink,
+        ),
+        #[cfg(not(feature = "tls"))]
+        Some(tls_acceptor) => match *tls_acceptor {},
+        None => process_client(
+            futures::future::ready(Ok(socket)),
+            address,
+            db,
+            token,
+            peers,
+            peer_event_sink,
+        ),
+    }
+}
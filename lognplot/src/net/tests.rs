@@ -0,0 +1,515 @@
+//! Tests of the server and the clients, connected over loopback.
+
+use super::async_client::AsyncClient;
+use super::buffered_client::{BufferedClient, BufferedClientOptions};
+use super::client::TcpClient;
+#[cfg(unix)]
+use super::client::UNIX_ADDRESS_PREFIX;
+use super::compact::CompactPacket;
+#[cfg(feature = "compression")]
+use super::compression::Compression;
+use super::payload::SampleBatch;
+use super::protocol::{supported_features, FEATURE_SIGNAL_IDS, PROTOCOL_VERSION};
+use super::relay::{start_relay, RelayOptions};
+use super::remote::RemoteClient;
+use super::request::{Request, Response, ServerMessage, MAX_RAW_SAMPLES};
+use super::server::{start_server, ServerHandle, ServerOptions, TlsOptions};
+use super::udp_client::UdpClient;
+use crate::time::{TimeSpan, TimeStamp};
+use crate::tracer::AnyTracer;
+use crate::tsdb::observations::{Observation, Sample};
+use crate::tsdb::{SignalMetadata, TsDb, TsDbHandle};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Start a server on a free port of the loopback interface.
+fn start(options: ServerOptions) -> (TsDbHandle, ServerHandle) {
+    start_on(0, options)
+}
+
+fn start_on(port: u16, options: ServerOptions) -> (TsDbHandle, ServerHandle) {
+    let db = TsDb::default().into_handle();
+    let options = ServerOptions {
+        port,
+        bind_address: Some("127.0.0.1".parse().unwrap()),
+        ..options
+    };
+    let server = start_server(db.clone(), options, Arc::new(AnyTracer::new_void())).unwrap();
+    (db, server)
+}
+
+fn connect(server: &ServerHandle) -> TcpClient {
+    let client = TcpClient::new(&server.local_addr().to_string()).unwrap();
+    client
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    client
+}
+
+/// Wait until the database holds the given amount of samples of a signal.
+fn wait_for_samples(db: &TsDbHandle, name: &str, count: usize) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while db.summary(name, None).map(|s| s.count()) != Some(count) {
+        assert!(
+            Instant::now() < deadline,
+            "No {} samples of {}",
+            count,
+            name
+        );
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn read_message(client: &mut TcpClient) -> ServerMessage {
+    ServerMessage::from_bytes(&client.read_blob().unwrap()).unwrap()
+}
+
+/// Read the error which must be the last packet on the connection.
+fn read_final_error(client: &mut TcpClient) -> String {
+    let message = match read_message(client) {
+        ServerMessage::Response(Response::Error { message, .. }) => message,
+        _ => panic!("Expected an error"),
+    };
+    assert!(client.read_blob().is_err(), "Connection is still open");
+    message
+}
+
+#[test]
+fn authentication() {
+    let options = ServerOptions {
+        token: Some("secret".to_owned()),
+        ..ServerOptions::default()
+    };
+    let (db, server) = start(options);
+
+    let mut client = connect(&server);
+    client.set_token("secret");
+    client.hello("test").unwrap();
+    client.send_sample("accepted", 1.0, 2.0).unwrap();
+    wait_for_samples(&db, "accepted", 1);
+
+    let mut client = connect(&server);
+    client.set_token("wrong");
+    let err = client.hello("test").unwrap_err();
+    assert_eq!(err.to_string(), "Authentication failed");
+    assert!(client.read_blob().is_err(), "Connection is still open");
+
+    let mut client = connect(&server);
+    let request = Request::SignalNames { id: 1 };
+    client.write_blob(request.to_bytes()).unwrap();
+    assert_eq!(read_final_error(&mut client), "Authentication failed");
+
+    let mut client = connect(&server);
+    client.send_sample("rejected", 1.0, 2.0).unwrap();
+    assert_eq!(read_final_error(&mut client), "Authentication failed");
+
+    // The rejected clients are gone, and so is their data:
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(server.peers().len(), 1);
+    assert_eq!(db.get_signal_names(), vec!["accepted".to_owned()]);
+
+    server.stop();
+}
+
+#[test]
+fn startup_errors() {
+    let db = TsDb::default().into_handle();
+    let tracer = Arc::new(AnyTracer::new_void());
+
+    let options = ServerOptions {
+        port: 0,
+        udp_port: Some(0),
+        token: Some("secret".to_owned()),
+        ..ServerOptions::default()
+    };
+    assert!(start_server(db.clone(), options, tracer.clone()).is_err());
+
+    let options = ServerOptions {
+        port: 0,
+        tls: Some(TlsOptions {
+            certificate: "/nonexistent/server.pem".into(),
+            key: "/nonexistent/server.key".into(),
+            client_ca_certificates: None,
+        }),
+        ..ServerOptions::default()
+    };
+    assert!(start_server(db.clone(), options, tracer.clone()).is_err());
+
+    // The port is taken by the first server:
+    let (_, server) = start(ServerOptions::default());
+    let options = ServerOptions {
+        port: server.local_addr().port(),
+        bind_address: Some(server.local_addr().ip()),
+        ..ServerOptions::default()
+    };
+    assert!(start_server(db, options, tracer).is_err());
+    server.stop();
+}
+
+#[test]
+fn metadata_survives_a_server_restart() {
+    let (db, server) = start(ServerOptions::default());
+    let addr = server.local_addr();
+    let options = BufferedClientOptions {
+        min_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(50),
+        ..BufferedClientOptions::default()
+    };
+    let client = BufferedClient::with_options(&addr.to_string(), options);
+    client.send_metadata("voltage", &SignalMetadata::with_unit("V"));
+    client.send_sample("voltage", 1.0, 12.0);
+    client.flush();
+    wait_for_samples(&db, "voltage", 1);
+    server.stop();
+
+    let (db, server) = start_on(addr.port(), ServerOptions::default());
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while db.get_signal_names().is_empty() {
+        assert!(Instant::now() < deadline, "The client did not reconnect");
+        client.send_sample("voltage", 2.0, 12.0);
+        client.flush();
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    let metadata = db.get_metadata("voltage").unwrap();
+    assert_eq!(metadata.unit.as_deref(), Some("V"));
+
+    client.close();
+    server.stop();
+}
+
+#[test]
+fn remote_queries_and_subscription() {
+    let (db, server) = start(ServerOptions::default());
+    let mut sender = connect(&server);
+    sender
+        .send_samples("robot/speed", vec![(1.0, 2.0), (2.0, 4.0), (3.0, 6.0)])
+        .unwrap();
+    wait_for_samples(&db, "robot/speed", 3);
+
+    let mut remote = RemoteClient::from_client(connect(&server)).unwrap();
+    assert_eq!(
+        remote.get_signal_names().unwrap(),
+        vec!["robot/speed".to_owned()]
+    );
+    let summary = remote.summary("robot/speed", None).unwrap().unwrap();
+    assert_eq!((summary.count, summary.start, summary.end), (3, 1.0, 3.0));
+    assert!(remote.summary("missing", None).unwrap().is_none());
+
+    let timespan = TimeSpan::new(TimeStamp::new(1.5), TimeStamp::new(3.0));
+    let samples: Vec<(f64, f64)> = remote
+        .get_raw_range("robot/speed", &timespan)
+        .unwrap()
+        .unwrap()
+        .iter()
+        .map(|o| (o.timestamp.amount, o.value.value))
+        .collect();
+    assert_eq!(samples, vec![(2.0, 4.0), (3.0, 6.0)]);
+
+    let subscription = remote.subscribe("robot/**").unwrap();
+    sender.send_sample("other", 4.0, 8.0).unwrap();
+    sender.send_sample("robot/speed", 4.0, 8.0).unwrap();
+    let batch = remote.next_batch().unwrap();
+    assert_eq!(batch.name(), "robot/speed");
+    assert_eq!(batch.values(), vec![(4.0, 8.0)]);
+
+    // No more data after unsubscribing:
+    remote.unsubscribe(subscription).unwrap();
+    sender.send_sample("robot/speed", 5.0, 10.0).unwrap();
+    wait_for_samples(&db, "robot/speed", 5);
+    remote.get_signal_names().unwrap();
+    remote
+        .client()
+        .set_read_timeout(Some(Duration::from_millis(200)))
+        .unwrap();
+    assert!(remote.next_batch().is_err());
+
+    server.stop();
+}
+
+#[test]
+fn too_many_raw_samples() {
+    let (db, server) = start(ServerOptions::default());
+    // Too many samples to fit a single frame, as well as more than may be sent:
+    let count = 500_000;
+    assert!(count > MAX_RAW_SAMPLES);
+    let samples = (0..count)
+        .map(|i| Observation::new(TimeStamp::new(i as f64), Sample::new(i as f64)))
+        .collect();
+    db.add_values("big", samples);
+
+    let mut remote = RemoteClient::from_client(connect(&server)).unwrap();
+    let subscription = remote.subscribe("big").unwrap();
+    assert!(remote.get_raw_samples("big").is_err());
+
+    // The connection and its subscription survive, and a smaller period can be downloaded:
+    let timespan = TimeSpan::new(TimeStamp::new(0.0), TimeStamp::new(9.0));
+    let part = remote.get_raw_range("big", &timespan).unwrap().unwrap();
+    assert_eq!(part.len(), 10);
+    let mut sender = connect(&server);
+    sender.send_sample("big", count as f64, 1.0).unwrap();
+    let batch = remote.next_batch().unwrap();
+    assert_eq!(batch.values(), vec![(count as f64, 1.0)]);
+    remote.unsubscribe(subscription).unwrap();
+
+    server.stop();
+}
+
+#[test]
+fn handshake() {
+    let (db, server) = start(ServerOptions::default());
+
+    let mut client = connect(&server);
+    let session = client.hello("test").unwrap();
+    assert_eq!(session.version, PROTOCOL_VERSION);
+    assert_eq!(session.features, supported_features());
+    assert!(session.peer.starts_with("lognplot"));
+    client.send_sample("introduced", 1.0, 2.0).unwrap();
+    wait_for_samples(&db, "introduced", 1);
+    let names: Vec<Option<String>> = server.peers().into_iter().map(|p| p.client).collect();
+    assert_eq!(names, vec![Some("test".to_owned())]);
+
+    // A newer client gets the version and features known to the server:
+    let mut client = connect(&server);
+    let hello = Request::Hello {
+        version: PROTOCOL_VERSION + 1,
+        client: "future".to_owned(),
+        features: vec!["teleport".to_owned(), "subscribe".to_owned()],
+        token: None,
+    };
+    client.write_blob(hello.to_bytes()).unwrap();
+    match read_message(&mut client) {
+        ServerMessage::Response(Response::Welcome {
+            version, features, ..
+        }) => {
+            assert_eq!(version, PROTOCOL_VERSION);
+            assert_eq!(features, vec!["subscribe".to_owned()]);
+        }
+        _ => panic!("Expected a welcome"),
+    }
+
+    // A client which does not introduce itself is never sent anything:
+    let mut client = connect(&server);
+    client.send_sample("legacy", 1.0, 2.0).unwrap();
+    wait_for_samples(&db, "legacy", 1);
+    client
+        .set_read_timeout(Some(Duration::from_millis(200)))
+        .unwrap();
+    assert!(client.read_blob().is_err());
+
+    server.stop();
+}
+
+#[test]
+fn udp_datagrams() {
+    let options = ServerOptions {
+        udp_port: Some(0),
+        ..ServerOptions::default()
+    };
+    let (db, server) = start(options);
+    let addr = server.udp_local_addr().unwrap();
+
+    let mut client = UdpClient::new(&addr.to_string()).unwrap();
+    client.send_sample("udp", 1.0, 2.0).unwrap();
+    client
+        .send_samples("udp", vec![(2.0, 4.0), (3.0, 6.0)])
+        .unwrap();
+    client
+        .send_text("udp_text", 1.0, "hello".to_owned())
+        .unwrap();
+    wait_for_samples(&db, "udp", 3);
+    wait_for_samples(&db, "udp_text", 1);
+
+    let statistics = server.udp_statistics();
+    assert_eq!(statistics.len(), 1);
+    let source = statistics.values().next().unwrap();
+    assert_eq!((source.received, source.lost), (3, 0));
+
+    server.stop();
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_socket() {
+    let path = std::env::temp_dir().join(format!("lognplot-test-{}.sock", std::process::id()));
+    let options = ServerOptions {
+        unix_path: Some(path.clone()),
+        ..ServerOptions::default()
+    };
+    let (db, server) = start(options);
+
+    let addr = format!("{}{}", UNIX_ADDRESS_PREFIX, path.display());
+    let mut client = TcpClient::new(&addr).unwrap();
+    client.hello("test").unwrap();
+    client.send_sample("local", 1.0, 2.0).unwrap();
+    wait_for_samples(&db, "local", 1);
+
+    let mut remote = RemoteClient::from_client(TcpClient::connect_unix(&path).unwrap()).unwrap();
+    assert_eq!(remote.get_signal_names().unwrap(), vec!["local".to_owned()]);
+
+    server.stop();
+    assert!(!path.exists(), "The socket file is left behind");
+}
+
+#[test]
+fn async_client() {
+    let (db, server) = start(ServerOptions::default());
+    let addr = server.local_addr().to_string();
+
+    let mut runtime = tokio::runtime::Builder::new()
+        .basic_scheduler()
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        use futures::SinkExt;
+
+        let mut client = AsyncClient::connect(&addr).await.unwrap();
+        let session = client.hello("test").await.unwrap();
+        assert_eq!(session.version, PROTOCOL_VERSION);
+        client.send_sample("async", 1.0, 2.0).await.unwrap();
+
+        // The client is a sink of batches:
+        let batches = vec![
+            SampleBatch::new_samples("async".to_owned(), vec![(2.0, 4.0), (3.0, 6.0)]),
+            SampleBatch::new_text("async_text".to_owned(), 1.0, "hello".to_owned()),
+        ];
+        let mut batches = futures::stream::iter(batches.into_iter().map(Ok));
+        client.send_all(&mut batches).await.unwrap();
+    });
+    wait_for_samples(&db, "async", 3);
+    wait_for_samples(&db, "async_text", 1);
+
+    server.stop();
+}
+
+fn raw_samples(db: &TsDbHandle, name: &str) -> Vec<(f64, f64)> {
+    db.get_raw_samples(name)
+        .unwrap()
+        .iter()
+        .map(|o| (o.timestamp.amount, o.value.value))
+        .collect()
+}
+
+#[test]
+fn frames_by_signal_id() {
+    let (db, server) = start(ServerOptions::default());
+
+    let mut client = connect(&server);
+    let session = client.hello("test").unwrap();
+    assert!(session.supports(FEATURE_SIGNAL_IDS));
+    client.set_epoch(1000.0).unwrap();
+    client
+        .send_frame(&[("a", 1001.0, 1.0), ("b", 1001.0, 2.0)])
+        .unwrap();
+    client.send_frame(&[("a", 1002.0, 3.0)]).unwrap();
+    wait_for_samples(&db, "a", 2);
+    wait_for_samples(&db, "b", 1);
+    assert_eq!(raw_samples(&db, "a"), vec![(1001.0, 1.0), (1002.0, 3.0)]);
+    assert_eq!(raw_samples(&db, "b"), vec![(1001.0, 2.0)]);
+
+    // Samples of an id which was never defined are reported:
+    let frame = CompactPacket::Frame {
+        samples: vec![(7, 0.0, 1.0)],
+    };
+    client.write_blob(frame.to_bytes()).unwrap();
+    match read_message(&mut client) {
+        ServerMessage::Response(Response::Error { message, .. }) => {
+            assert!(message.contains("undefined"), "{}", message)
+        }
+        _ => panic!("Expected an error"),
+    }
+
+    server.stop();
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn compression_on_the_wire() {
+    let (db, server) = start(ServerOptions::default());
+
+    for (index, compression) in [Compression::Zstd, Compression::Lz4].iter().enumerate() {
+        let mut client = connect(&server);
+        let session = client.hello_compressed("test", *compression).unwrap();
+        assert!(session.supports(compression.feature()));
+
+        let name = format!("signal{}", index);
+        let subscribe = Request::Subscribe {
+            id: 1,
+            pattern: name.clone(),
+        };
+        client.write_blob(subscribe.to_bytes()).unwrap();
+        match read_message(&mut client) {
+            ServerMessage::Response(Response::Subscribed { id: 1, .. }) => {}
+            _ => panic!("Expected a subscription"),
+        }
+
+        // Regular samples compress well:
+        let samples: Vec<(f64, f64)> = (0..10000).map(|i| (i as f64, 1.0)).collect();
+        let size = SampleBatch::new_samples(name.clone(), samples.clone())
+            .to_bytes()
+            .len();
+        client.send_samples(&name, samples.clone()).unwrap();
+        wait_for_samples(&db, &name, samples.len());
+        match read_message(&mut client) {
+            ServerMessage::Data(batch) => assert_eq!(batch.values(), samples),
+            _ => panic!("Expected data"),
+        }
+        let peer = server.peers().into_iter().max_by_key(|p| p.id).unwrap();
+        assert!(
+            peer.bytes < size as u64 / 2,
+            "{} of {} bytes on the wire",
+            peer.bytes,
+            size
+        );
+        client.close().unwrap();
+    }
+
+    server.stop();
+}
+
+#[test]
+fn relay_to_upstreams() {
+    let (upstream_db1, upstream1) = start(ServerOptions::default());
+    let (upstream_db2, upstream2) = start(ServerOptions::default());
+
+    let db = TsDb::default().into_handle();
+    let options = RelayOptions {
+        patterns: vec!["robot/**".to_owned()],
+        upstreams: vec![
+            upstream1.local_addr().to_string(),
+            upstream2.local_addr().to_string(),
+        ],
+        prefix: Some("bench1/".to_owned()),
+        ..RelayOptions::default()
+    };
+    let relay = start_relay(db.clone(), options);
+
+    db.set_metadata("robot/speed", SignalMetadata::with_unit("m/s"));
+    let samples = vec![
+        Observation::new(TimeStamp::new(1.0), Sample::new(2.0)),
+        Observation::new(TimeStamp::new(2.0), Sample::new(4.0)),
+    ];
+    db.add_values("robot/speed", samples);
+    db.add_value(
+        "other",
+        Observation::new(TimeStamp::new(1.0), Sample::new(2.0)),
+    );
+
+    for upstream_db in &[upstream_db1, upstream_db2] {
+        wait_for_samples(upstream_db, "bench1/robot/speed", 2);
+        assert_eq!(
+            raw_samples(upstream_db, "bench1/robot/speed"),
+            vec![(1.0, 2.0), (2.0, 4.0)]
+        );
+        let metadata = upstream_db.get_metadata("bench1/robot/speed").unwrap();
+        assert_eq!(metadata.unit.as_deref(), Some("m/s"));
+        assert_eq!(
+            upstream_db.get_signal_names(),
+            vec!["bench1/robot/speed".to_owned()]
+        );
+    }
+
+    relay.stop();
+    upstream1.stop();
+    upstream2.stop();
+}
@@ -0,0 +1,109 @@
+//! Encrypted connections.
+//!
+//! Certificates and keys are read from PEM files. When the server is
+//! given certificates of a certificate authority for clients, only
+//! clients with a certificate signed by it can connect.
+
+use rustls::internal::pemfile;
+use rustls::{
+    AllowAnyAuthenticatedClient, Certificate, ClientConfig, NoClientAuth, PrivateKey,
+    RootCertStore, ServerConfig,
+};
+use std::fs::File;
+use std::io::{BufReader, Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+/// Certificates for an encrypted connection to a server.
+#[derive(Debug, Clone)]
+pub struct TlsClientOptions {
+    /// Certificate authorities to check the certificate of the server with.
+    pub ca_certificates: PathBuf,
+
+    /// Certificate of this client, for servers which require one.
+    pub certificate: Option<PathBuf>,
+
+    /// Key of the certificate of this client.
+    pub key: Option<PathBuf>,
+}
+
+/// Create the configuration of a server.
+pub fn server_config(
+    certificate: &Path,
+    key: &Path,
+    client_ca_certificates: Option<&Path>,
+) -> std::io::Result<ServerConfig> {
+    let verifier = if let Some(path) = client_ca_certificates {
+        AllowAnyAuthenticatedClient::new(load_root_store(path)?)
+    } else {
+        NoClientAuth::new()
+    };
+    let mut config = ServerConfig::new(verifier);
+    config
+        .set_single_cert(load_certificates(certificate)?, load_private_key(key)?)
+        .map_err(|err| Error::new(ErrorKind::InvalidInput, err))?;
+    Ok(config)
+}
+
+/// Create the configuration of a client.
+pub fn client_config(options: &TlsClientOptions) -> std::io::Result<ClientConfig> {
+    let mut config = ClientConfig::new();
+    config.root_store = load_root_store(&options.ca_certificates)?;
+    match (&options.certificate, &options.key) {
+        (Some(certificate), Some(key)) => {
+            config
+                .set_single_client_cert(load_certificates(certificate)?, load_private_key(key)?)
+                .map_err(|err| Error::new(ErrorKind::InvalidInput, err))?;
+        }
+        (None, None) => {}
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "A client certificate needs both a certificate and a key",
+            ));
+        }
+    }
+    Ok(config)
+}
+
+fn load_certificates(path: &Path) -> std::io::Result<Vec<Certificate>> {
+    let certificates =
+        pemfile::certs(&mut open(path)?).map_err(|_| invalid_file(path, "certificates"))?;
+    if certificates.is_empty() {
+        return Err(invalid_file(path, "certificates"));
+    }
+    Ok(certificates)
+}
+
+/// Load the first PKCS8 or RSA private key from a file.
+fn load_private_key(path: &Path) -> std::io::Result<PrivateKey> {
+    let mut keys =
+        pemfile::pkcs8_private_keys(&mut open(path)?).map_err(|_| invalid_file(path, "key"))?;
+    if keys.is_empty() {
+        keys =
+            pemfile::rsa_private_keys(&mut open(path)?).map_err(|_| invalid_file(path, "key"))?;
+    }
+    keys.into_iter()
+        .next()
+        .ok_or_else(|| invalid_file(path, "key"))
+}
+
+fn load_root_store(path: &Path) -> std::io::Result<RootCertStore> {
+    let mut store = RootCertStore::empty();
+    for certificate in load_certificates(path)? {
+        store
+            .add(&certificate)
+            .map_err(|err| Error::new(ErrorKind::InvalidData, format!("{:?}", err)))?;
+    }
+    Ok(store)
+}
+
+fn open(path: &Path) -> std::io::Result<BufReader<File>> {
+    Ok(BufReader::new(File::open(path)?))
+}
+
+fn invalid_file(path: &Path, what: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("No valid {} in {}", what, path.display()),
+    )
+}
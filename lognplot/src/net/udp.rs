@@ -0,0 +1,253 @@
+//! Receive data in UDP datagrams.
+//!
+//! Each datagram holds a single CBOR encoded sample batch, the same
+//! as a packet on a TCP connection. Datagrams can get lost, so the
+//! sequence numbers of the batches are tracked per source.
+
+use super::payload::SampleBatch;
+use crate::tracer::{AnyTracer, Tracer};
+use crate::tsdb::TsDbHandle;
+use futures::channel::oneshot;
+use futures::FutureExt;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::net::UdpSocket;
+use tokio::task::JoinHandle;
+
+/// Largest possible datagram.
+const MAX_DATAGRAM_SIZE: usize = 65536;
+
+/// A sequence number this much below the expected one means the
+/// source restarted, instead of a late datagram.
+const RESTART_DISTANCE: u64 = 1000;
+
+/// Amount of recent sequence numbers of which arrival is remembered,
+/// to tell duplicates from late datagrams.
+const WINDOW_SIZE: u64 = 64;
+
+/// Loss statistics per source address.
+pub type UdpStatistics = Arc<Mutex<HashMap<SocketAddr, UdpSourceStatistics>>>;
+
+/// Datagrams received from a single source.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UdpSourceStatistics {
+    /// Amount of datagrams received.
+    pub received: usize,
+
+    /// Amount of datagrams which never arrived, judged from the sequence numbers.
+    pub lost: usize,
+
+    /// Amount of datagrams which arrived after a later datagram.
+    pub out_of_order: usize,
+
+    next_sequence_number: Option<u64>,
+
+    /// First sequence number seen since the source (re)started.
+    first_sequence_number: u64,
+
+    /// Bit i is set when sequence number `next - 1 - i` arrived.
+    window: u64,
+}
+
+impl UdpSourceStatistics {
+    /// Register a datagram, which may carry a sequence number.
+    pub fn observe(&mut self, sequence_number: Option<u64>) {
+        self.received += 1;
+
+        let sequence_number = if let Some(sequence_number) = sequence_number {
+            sequence_number
+        } else {
+            return;
+        };
+
+        match self.next_sequence_number {
+            Some(expected) if sequence_number < expected => {
+                let distance = expected - 1 - sequence_number;
+                let seen = expected - self.first_sequence_number;
+                if sequence_number == 0
+                    || sequence_number < self.first_sequence_number
+                    || distance >= seen.min(RESTART_DISTANCE)
+                {
+                    self.restart(sequence_number);
+                } else if distance < WINDOW_SIZE {
+                    let bit = 1 << distance;
+                    if self.window & bit == 0 {
+                        // This one was counted as lost before:
+                        self.window |= bit;
+                        self.out_of_order += 1;
+                        self.lost = self.lost.saturating_sub(1);
+                    }
+                } else {
+                    self.out_of_order += 1;
+                    self.lost = self.lost.saturating_sub(1);
+                }
+            }
+            Some(expected) => {
+                let skipped = sequence_number - expected;
+                self.lost += skipped as usize;
+                self.window = if skipped + 1 < WINDOW_SIZE {
+                    (self.window << (skipped + 1)) | 1
+                } else {
+                    1
+                };
+                self.next_sequence_number = Some(sequence_number + 1);
+            }
+            None => {
+                self.restart(sequence_number);
+            }
+        }
+    }
+
+    fn restart(&mut self, sequence_number: u64) {
+        self.next_sequence_number = Some(sequence_number + 1);
+        self.first_sequence_number = sequence_number;
+        self.window = 1;
+    }
+}
+
+/// A handle to a running UDP listener.
+pub struct UdpListenerHandle {
+    kill_switch: oneshot::Sender<()>,
+    join_handle: JoinHandle<()>,
+}
+
+impl UdpListenerHandle {
+    pub async fn stop(self) -> std::io::Result<()> {
+        info!("Stopping UDP listener");
+        if self.kill_switch.send(()).is_err() {
+            info!("UDP listener already stopped");
+        }
+        self.join_handle.await?;
+        Ok(())
+    }
+}
+
+/// Start receiving datagrams on the given bound socket.
+pub fn start_udp_listener(
+    db: TsDbHandle,
+    socket: std::net::UdpSocket,
+    statistics: UdpStatistics,
+    perf_tracer: Arc<AnyTracer>,
+) -> std::io::Result<UdpListenerHandle> {
+    let socket = UdpSocket::from_std(socket)?;
+    info!("Listening for datagrams on {:?}", socket.local_addr()?);
+
+    let (kill_switch, kill_switch_endpoint) = oneshot::channel::<()>();
+    let join_handle = tokio::spawn(async move {
+        udp_prog(db, socket, statistics, perf_tracer, kill_switch_endpoint).await;
+    });
+
+    Ok(UdpListenerHandle {
+        kill_switch,
+        join_handle,
+    })
+}
+
+async fn udp_prog(
+    db: TsDbHandle,
+    mut socket: UdpSocket,
+    statistics: UdpStatistics,
+    perf_tracer: Arc<AnyTracer>,
+    kill_switch_endpoint: oneshot::Receiver<()>,
+) {
+    let mut kill_switch_endpoint = kill_switch_endpoint.fuse();
+    let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+
+    loop {
+        let result = {
+            let received = socket.recv_from(&mut buffer).fuse();
+            futures::pin_mut!(received);
+            futures::select! {
+                result = received => result,
+                _x = kill_switch_endpoint => {
+                    info!("UDP listener shutdown by kill switch.");
+                    break;
+                }
+            }
+        };
+
+        match result {
+            Ok((size, source)) => {
+                process_datagram(&db, &buffer[..size], source, &statistics, &perf_tracer);
+            }
+            Err(err) => {
+                error!("Error receiving datagram: {}", err);
+            }
+        }
+    }
+}
+
+fn process_datagram(
+    db: &TsDbHandle,
+    datagram: &[u8],
+    source: SocketAddr,
+    statistics: &UdpStatistics,
+    perf_tracer: &AnyTracer,
+) {
+    match SampleBatch::from_bytes(datagram) {
+        Ok(batch) => {
+            let lost = {
+                let mut statistics = statistics.lock().unwrap();
+                let source_statistics = statistics.entry(source).or_default();
+                let lost_before = source_statistics.lost;
+                source_statistics.observe(batch.sequence_number());
+                if source_statistics.lost != lost_before {
+                    Some(statistics.values().map(|s| s.lost).sum::<usize>())
+                } else {
+                    None
+                }
+            };
+            if let Some(lost) = lost {
+                perf_tracer.log_metric("udp_lost", std::time::Instant::now(), lost as f64);
+            }
+            batch.to_db(db);
+        }
+        Err(err) => {
+            error!("Error decoding datagram from {}: {:?}", source, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UdpSourceStatistics;
+
+    #[test]
+    fn loss_counting() {
+        let mut statistics = UdpSourceStatistics::default();
+        for sequence_number in &[5, 6, 8, 9, 7, 12] {
+            statistics.observe(Some(*sequence_number));
+        }
+        assert_eq!(statistics.received, 6);
+        assert_eq!(statistics.lost, 2);
+        assert_eq!(statistics.out_of_order, 1);
+
+        // A restart of the source is not counted as loss:
+        let mut statistics = UdpSourceStatistics::default();
+        for sequence_number in &[3000, 3001, 0, 1] {
+            statistics.observe(Some(*sequence_number));
+        }
+        statistics.observe(None);
+        assert_eq!(statistics.received, 5);
+        assert_eq!(statistics.lost, 0);
+        assert_eq!(statistics.out_of_order, 0);
+
+        // Also after a shorter run than the restart distance:
+        let mut statistics = UdpSourceStatistics::default();
+        for sequence_number in &[500, 501, 502, 0, 1, 2] {
+            statistics.observe(Some(*sequence_number));
+        }
+        assert_eq!(statistics.lost, 0);
+        assert_eq!(statistics.out_of_order, 0);
+
+        // Duplicates are neither late, nor do they make up for lost datagrams:
+        let mut statistics = UdpSourceStatistics::default();
+        for sequence_number in &[1, 2, 2, 5, 3, 3, 2] {
+            statistics.observe(Some(*sequence_number));
+        }
+        assert_eq!(statistics.received, 7);
+        assert_eq!(statistics.lost, 1);
+        assert_eq!(statistics.out_of_order, 1);
+    }
+}
@@ -0,0 +1,71 @@
+use std::net::{ToSocketAddrs, UdpSocket};
+
+use super::payload::SampleBatch;
+
+/// A UDP client to send logging events in datagrams.
+///
+/// Each batch is sent in a single datagram, numbered so the server can
+/// count lost datagrams. Nothing is retransmitted, and a batch must fit
+/// in a datagram.
+pub struct UdpClient {
+    socket: UdpSocket,
+    sequence_number: u64,
+}
+
+impl UdpClient {
+    pub fn new(addr: &str) -> std::io::Result<Self> {
+        let server = addr.to_socket_addrs()?.next().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "No address given")
+        })?;
+        let local = if server.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(server)?;
+        Ok(UdpClient {
+            socket,
+            sequence_number: 0,
+        })
+    }
+
+    /// Transmit a single sample.
+    pub fn send_sample(&mut self, name: &str, timestamp: f64, value: f64) -> std::io::Result<()> {
+        let payload = SampleBatch::new_sample(name.to_owned(), timestamp, value);
+        self.send_sample_batch(payload)
+    }
+
+    /// Transmit a batch of samples.
+    pub fn send_samples(&mut self, name: &str, samples: Vec<(f64, f64)>) -> std::io::Result<()> {
+        let payload = SampleBatch::new_samples(name.to_owned(), samples);
+        self.send_sample_batch(payload)
+    }
+
+    /// Send a batch of equally spaced samples.
+    pub fn send_sampled_samples(
+        &mut self,
+        name: &str,
+        t0: f64,
+        dt: f64,
+        values: Vec<f64>,
+    ) -> std::io::Result<()> {
+        let payload = SampleBatch::new_sampled_data(name.to_owned(), t0, dt, values);
+        self.send_sample_batch(payload)
+    }
+
+    /// Send a single text event
+    pub fn send_text(&mut self, name: &str, timestamp: f64, text: String) -> std::io::Result<()> {
+        let payload = SampleBatch::new_text(name.to_owned(), timestamp, text);
+        self.send_sample_batch(payload)
+    }
+
+    fn send_sample_batch(&mut self, payload: SampleBatch) -> std::io::Result<()> {
+        let data = payload
+            .with_sequence_number(self.sequence_number)
+            .to_bytes();
+        self.sequence_number += 1;
+        self.socket.send(&data)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+//! Accept connections on a unix domain socket.
+//!
+//! Producers on the same host can skip the TCP loopback this way. The
+//! framing is the same as on a TCP connection.
+
+use super::peer::process_client;
+use super::peer_processor::PeerEvent;
+use super::peer_registry::PeerRegistry;
+use crate::tsdb::TsDbHandle;
+use futures::channel::{mpsc, oneshot};
+use futures::{FutureExt, StreamExt};
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use tokio::net::UnixListener;
+use tokio::task::JoinHandle;
+
+/// A handle to a running unix socket listener.
+pub struct UnixListenerHandle {
+    path: PathBuf,
+    kill_switch: oneshot::Sender<()>,
+    join_handle: JoinHandle<()>,
+}
+
+impl UnixListenerHandle {
+    /// Stop accepting connections, and remove the socket file.
+    pub async fn stop(self) -> std::io::Result<()> {
+        info!("Stopping unix socket listener");
+        if self.kill_switch.send(()).is_err() {
+            info!("Unix socket listener already stopped");
+        }
+        self.join_handle.await?;
+        std::fs::remove_file(&self.path)
+    }
+}
+
+/// Create a socket at the given path.
+///
+/// A socket file left behind by an earlier server is replaced. Any
+/// other kind of file at the path is left alone, and is an error.
+pub fn bind_unix_socket(path: &Path) -> std::io::Result<std::os::unix::net::UnixListener> {
+    remove_stale_socket(path)?;
+    std::os::unix::net::UnixListener::bind(path)
+}
+
+/// Start accepting connections on a socket created at the given path.
+pub fn start_unix_listener(
+    db: TsDbHandle,
+    listener: std::os::unix::net::UnixListener,
+    path: PathBuf,
+    token: Option<String>,
+    peers: PeerRegistry,
+    peer_event_sink: mpsc::UnboundedSender<PeerEvent>,
+) -> std::io::Result<UnixListenerHandle> {
+    let listener = UnixListener::from_std(listener)?;
+    info!("Server listening on {}", path.display());
+    let address = format!("unix:{}", path.display());
+
+    let (kill_switch, kill_switch_endpoint) = oneshot::channel::<()>();
+    let join_handle = tokio::spawn(async move {
+        if let Err(err) = unix_prog(
+            db,
+            listener,
+            address,
+            token,
+            peers,
+            peer_event_sink,
+            kill_switch_endpoint,
+        )
+        .await
+        {
+            error!("Unix socket listener stopped with error: {}", err);
+        }
+    });
+
+    Ok(UnixListenerHandle {
+        path,
+        kill_switch,
+        join_handle,
+    })
+}
+
+fn remove_stale_socket(path: &Path) -> std::io::Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path),
+        Ok(_) => Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket", path.display()),
+        )),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+async fn unix_prog(
+    db: TsDbHandle,
+    mut listener: UnixListener,
+    address: String,
+    token: Option<String>,
+    peers: PeerRegistry,
+    peer_event_sink: mpsc::UnboundedSender<PeerEvent>,
+    kill_switch_endpoint: oneshot::Receiver<()>,
+) -> std::io::Result<()> {
+    let mut kill_switch_endpoint = kill_switch_endpoint.fuse();
+    let mut incoming = listener.incoming().fuse();
+
+    loop {
+        futures::select! {
+            _x = kill_switch_endpoint => {
+                info!("Unix socket listener shutdown by kill switch.");
+                break;
+            },
+            optional_new_client = incoming.next() => {
+                if let Some(new_client) = optional_new_client {
+                    let peer_socket = new_client?;
+                    info!("Client connected via unix socket!");
+                    process_client(
+                        futures::future::ready(Ok(peer_socket)),
+                        address.clone(),
+                        db.clone(),
+                        token.clone(),
+                        &peers,
+                        peer_event_sink.clone(),
+                    );
+                } else {
+                    info!("No more incoming connections.");
+                    break;
+                }
+            },
+        };
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::remove_stale_socket;
+
+    #[test]
+    fn only_sockets_are_replaced() {
+        let path = std::env::temp_dir().join(format!("lognplot-test-{}.txt", std::process::id()));
+        assert!(remove_stale_socket(&path).is_ok());
+
+        std::fs::write(&path, "precious").unwrap();
+        assert!(remove_stale_socket(&path).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "precious");
+        std::fs::remove_file(&path).unwrap();
+
+        let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        drop(listener);
+        assert!(remove_stale_socket(&path).is_ok());
+        assert!(!path.exists());
+    }
+}
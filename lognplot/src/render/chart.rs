@@ -11,10 +11,11 @@ use crate::time::TimeStamp;
 use crate::tsdb::observations::{
     Aggregation, CountMetrics, Observation, ProfileEvent, Sample, SampleMetrics, Text,
 };
-use crate::tsdb::{QueryResult, RangeQueryResult};
+use crate::tsdb::{QueryResult, RangeQueryResult, SignalMetadata};
 use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::rc::Rc;
+use std::str::FromStr;
 
 use superslice::Ext;
 
@@ -61,6 +62,9 @@ where
 
     curve_data_cache: HashMap<String, Rc<CurveData>>,
 
+    // Meta data of the curves, fetched together with their data:
+    curve_metadata_cache: HashMap<String, SignalMetadata>,
+
     text_track_y: f64,
 }
 
@@ -80,6 +84,7 @@ where
             layout,
             options,
             curve_data_cache: HashMap::new(),
+            curve_metadata_cache: HashMap::new(),
             text_track_y: 0.0,
         }
     }
@@ -88,6 +93,7 @@ where
         self.fetch_curve_data();
         self.draw_axis();
         self.draw_box();
+        self.draw_annotations();
         self.draw_curves();
         self.draw_cursor();
         self.draw_title();
@@ -104,7 +110,11 @@ where
         let dy = text_height * 1.3;
 
         for curve in &self.chart.curves {
-            let name = curve.name();
+            let name = if let Some(unit) = self.curve_unit(curve) {
+                format!("{} [{}]", curve.name(), unit)
+            } else {
+                curve.name()
+            };
             let color = curve.color();
             self.canvas.set_pen(color, 1.0);
             self.canvas
@@ -233,10 +243,10 @@ where
         self.canvas.set_pen(Color::black(), 1.0);
         self.canvas.set_line_width(1.0);
 
-        if let Some(title) = &self.chart.y_axis.label {
+        if let Some(title) = self.y_axis_label() {
             let p = Point::new(10.0, self.layout.height / 2.0);
             self.canvas
-                .print_text(&p, HorizontalAnchor::Left, VerticalAnchor::Middle, title);
+                .print_text(&p, HorizontalAnchor::Left, VerticalAnchor::Middle, &title);
         }
 
         let x = self.layout.plot_left;
@@ -298,6 +308,50 @@ where
         self.canvas.draw_polygon(&outline);
     }
 
+    /// Draw annotations as shaded periods, or as markers at a single moment.
+    fn draw_annotations(&mut self) {
+        for annotation in self.chart.get_annotations() {
+            let color = annotation
+                .color
+                .as_ref()
+                .and_then(|c| Color::from_str(c).ok())
+                .unwrap_or_else(Color::gray);
+            let left = self
+                .x_domain_to_pixel(&annotation.timespan.start)
+                .max(self.layout.plot_left);
+            let right = self
+                .x_domain_to_pixel(&annotation.timespan.end)
+                .min(self.layout.plot_right);
+
+            if annotation.is_point() {
+                self.canvas.set_pen(color, 0.8);
+                self.canvas.set_line_width(1.0);
+                self.canvas.draw_line(&[
+                    Point::new(left, self.layout.plot_top),
+                    Point::new(left, self.layout.plot_bottom),
+                ]);
+            } else {
+                self.canvas.set_pen(color, 0.2);
+                self.canvas.fill_rect(
+                    left,
+                    self.layout.plot_top,
+                    right - left,
+                    self.layout.plot_bottom - self.layout.plot_top,
+                );
+            }
+
+            self.canvas.set_pen(Color::black(), 1.0);
+            let label_point = Point::new(left + 3.0, self.layout.plot_top + 3.0);
+            self.draw_dotted_text(
+                &label_point,
+                HorizontalAnchor::Left,
+                VerticalAnchor::Top,
+                &annotation.title,
+                (self.layout.plot_right - left).max(0.0),
+            );
+        }
+    }
+
     /// Draw a cursor and some values along it.
     fn draw_cursor(&mut self) {
         if let Some(cursor) = &self.chart.cursor {
@@ -374,11 +428,14 @@ where
                                 let min = metrics.min;
                                 let mean = metrics.mean();
                                 let max = metrics.max;
-                                let labels = vec![
+                                let mut labels = vec![
                                     format!("mean={}", mean),
                                     format!("min={}", min),
                                     format!("max={}", max),
                                 ];
+                                if metrics.invalid > 0 {
+                                    labels.push(format!("invalid={}", metrics.invalid));
+                                }
                                 values.push((Some((ts, mean)), labels, curve.color()));
                             }
                         }
@@ -525,58 +582,121 @@ where
         }
     }
 
-    /// Fetch curve data from backing data store.
+    /// Fetch curve data and meta data from backing data store.
+    ///
+    /// Values are scaled for display, and gaps longer than the gap
+    /// threshold of a signal are marked, so that the line is broken there.
     fn fetch_curve_data(&mut self) {
         let timespan = self.chart.x_axis.timespan();
         let pixels: usize = self.layout.plot_width as usize;
         let point_count = pixels / PIXELS_PER_AGGREGATION;
         for curve in &self.chart.curves {
-            let data = curve.query(&timespan, point_count);
+            let mut data = curve.query(&timespan, point_count);
+            if let Some(metadata) = curve.data.metadata() {
+                if let Some(QueryResult::Value(values)) = &mut data {
+                    values.scale(metadata.scale, metadata.offset);
+                    if let Some(threshold) = metadata.gap_threshold {
+                        values.mark_gaps(threshold);
+                    }
+                }
+                self.curve_metadata_cache.insert(curve.name(), metadata);
+            }
             self.curve_data_cache.insert(curve.name(), Rc::new(data));
         }
     }
 
+    /// The unit of a curve, if known.
+    fn curve_unit(&self, curve: &Curve) -> Option<&str> {
+        self.curve_metadata_cache
+            .get(&curve.name())
+            .and_then(|metadata| metadata.unit.as_deref())
+    }
+
+    /// Determine the label of the y axis.
+    ///
+    /// When no label was set, the units of the curves are used.
+    fn y_axis_label(&self) -> Option<String> {
+        if self.chart.y_axis.label.is_some() {
+            return self.chart.y_axis.label.clone();
+        }
+
+        let mut units: Vec<&str> = vec![];
+        for unit in self.chart.curves.iter().filter_map(|c| self.curve_unit(c)) {
+            if !units.contains(&unit) {
+                units.push(unit);
+            }
+        }
+
+        if units.is_empty() {
+            None
+        } else {
+            Some(format!("[{}]", units.join(", ")))
+        }
+    }
+
     fn query_curve_data(&self, curve: &Curve) -> Rc<CurveData> {
         self.curve_data_cache[&curve.name()].clone()
     }
 
     /// Draw single observations.
+    ///
+    /// The line is broken at NaN or infinite values.
     fn draw_observations(
         &mut self,
         observations: &[Observation<Sample>],
         color: Color,
         draw_markers: bool,
     ) {
-        let points: Vec<Point> = observations
-            .iter()
-            .map(|o| {
-                Point::new(
-                    self.x_domain_to_pixel(&o.timestamp),
-                    self.y_domain_to_pixel(o.value.value),
-                )
-            })
-            .collect();
-        trace!("Drawing {} points", points.len());
-
         self.canvas.set_pen(color, 1.0);
         self.canvas.set_line_width(2.0);
-        self.canvas.draw_line(&points);
 
-        // Draw markers as small solid square dots
-        // Idea from pulseview (sigrok application)
-        if draw_markers {
-            for point in points {
-                self.canvas
-                    .fill_rect(point.x() - 4.0, point.y() - 4.0, 8.0, 8.0);
+        for segment in observations.split(|o| !o.value.value.is_finite()) {
+            let points: Vec<Point> = segment
+                .iter()
+                .map(|o| {
+                    Point::new(
+                        self.x_domain_to_pixel(&o.timestamp),
+                        self.y_domain_to_pixel(o.value.value),
+                    )
+                })
+                .collect();
+            trace!("Drawing {} points", points.len());
+
+            if points.len() > 1 {
+                self.canvas.draw_line(&points);
+            }
+
+            // Draw markers as small solid square dots
+            // Idea from pulseview (sigrok application)
+            // Lone points between gaps are always marked, to keep them visible.
+            if draw_markers || points.len() == 1 {
+                for point in points {
+                    self.canvas
+                        .fill_rect(point.x() - 4.0, point.y() - 4.0, 8.0, 8.0);
+                }
             }
         }
     }
 
     /// Draw aggregated values
+    ///
+    /// Aggregations without valid samples break the drawing into
+    /// separate parts.
     fn draw_aggregations(
         &mut self,
         aggregations: &[Aggregation<Sample, SampleMetrics>],
         color: Color,
+    ) {
+        for run in aggregations.split(|a| a.metrics().count == 0) {
+            self.draw_aggregation_run(run, color.clone());
+        }
+    }
+
+    /// Draw a series of aggregations as a single band.
+    fn draw_aggregation_run(
+        &mut self,
+        aggregations: &[Aggregation<Sample, SampleMetrics>],
+        color: Color,
     ) {
         if aggregations.is_empty() {
             return;
@@ -2,10 +2,12 @@
 mod resolution;
 mod timespan;
 mod timestamp;
+mod tracker;
 
 pub use resolution::Resolution;
 pub use timespan::TimeSpan;
 pub use timestamp::TimeStamp;
+pub use tracker::{TimeTracker, TrackerNoise};
 
 pub trait TimeModifiers {
     fn add_nanos(&self, amount: isize) -> Self;
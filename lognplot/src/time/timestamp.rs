@@ -44,6 +44,14 @@ impl PartialOrd for TimeStamp {
     }
 }
 
+impl std::ops::Add<f64> for TimeStamp {
+    type Output = TimeStamp;
+
+    fn add(self, other: f64) -> TimeStamp {
+        TimeStamp::new(self.amount + other)
+    }
+}
+
 impl std::ops::Sub<f64> for TimeStamp {
     type Output = TimeStamp;
 
@@ -8,16 +8,46 @@
 //! estimated based on observations and also based upon
 //! a prediction by a model.
 //!
+//! The moments of predictions and updates are seconds on a
+//! reference clock. By default this is the monotonic clock of
+//! this process, the `_at` variants take an explicit moment.
+//!
 //! Some extra notes:
 //! - We should stop using the time estimate when no
 //!   new values arrive, and P becomes too large.
 //!
 
-use lognplot::tracer::{AnyTracer, Tracer};
+use crate::tracer::{AnyTracer, Tracer};
 use nalgebra::{Matrix1, Matrix2, RowVector2, Vector2};
 use std::sync::Arc;
 use std::time::Instant;
 
+/// Innovations larger than this amount of seconds reset the estimate.
+const RESET_THRESHOLD: f64 = 5.0;
+
+/// Noise parameters of the filter, as variances.
+#[derive(Debug, Clone, Copy)]
+pub struct TrackerNoise {
+    /// Process noise (Q) on the time, per second of prediction.
+    pub time: f64,
+
+    /// Process noise (Q) on the time propulsion speed, per second of prediction.
+    pub rate: f64,
+
+    /// Measurement noise (R) on observed times.
+    pub measurement: f64,
+}
+
+impl Default for TrackerNoise {
+    fn default() -> Self {
+        TrackerNoise {
+            time: 0.001,
+            rate: 0.001,
+            measurement: 0.01,
+        }
+    }
+}
+
 pub struct TimeTracker {
     /// State estimate
     ///
@@ -29,62 +59,91 @@ pub struct TimeTracker {
     /// This value will increase when the estimate becomes uncertain.
     covariance: Matrix2<f64>,
 
-    // previous timestamp!
-    prev: Option<Instant>,
+    /// Whether the estimate was reset with an observation.
+    initialized: bool,
+
+    // previous moment on the reference clock!
+    prev: Option<f64>,
+
+    /// Start of the default reference clock.
+    epoch: Instant,
+
+    noise: TrackerNoise,
 
     /// The time tracker can be traced as well (inception!)
-    perf_tracer: Arc<AnyTracer>,
+    perf_tracer: Option<Arc<AnyTracer>>,
 
     trace_prefix: String,
 }
 
 impl TimeTracker {
     pub fn new(perf_tracer: Arc<AnyTracer>, trace_prefix: &str) -> Self {
+        let mut tracker = Self::untraced(TrackerNoise::default());
+        tracker.perf_tracer = Some(perf_tracer);
+        tracker.trace_prefix = trace_prefix.to_owned();
+        tracker
+    }
+
+    /// A tracker with the given noise, which does not trace itself.
+    pub fn untraced(noise: TrackerNoise) -> Self {
         TimeTracker {
             x_hat: Vector2::zeros(),
             covariance: Matrix2::identity(),
+            initialized: false,
             prev: None,
-            perf_tracer,
-            trace_prefix: trace_prefix.to_owned(),
+            epoch: Instant::now(),
+            noise,
+            perf_tracer: None,
+            trace_prefix: String::new(),
         }
     }
 
-    fn get_dt(&mut self) -> f64 {
-        // Determine dt since last prediction:
-        let now = Instant::now();
+    fn now(&self) -> f64 {
+        self.epoch.elapsed().as_secs_f64()
+    }
 
-        let prev = self.prev.replace(now.clone());
-        if let Some(prev) = prev {
-            let delta = now - prev;
-            delta.as_secs_f64()
-        } else {
-            // First prediction
-            0.0
+    fn get_dt(&mut self, now: f64) -> f64 {
+        // Determine dt since last prediction:
+        match self.prev {
+            // Never predict backwards in time:
+            Some(prev) if now < prev => 0.0,
+            Some(prev) => {
+                self.prev = Some(now);
+                now - prev
+            }
+            None => {
+                // First prediction
+                self.prev = Some(now);
+                0.0
+            }
         }
     }
 
-    fn reset(&mut self, observation: f64) {
+    fn reset(&mut self, observation: f64, now: f64) {
         self.x_hat = Vector2::new(observation, 1.0);
         self.covariance = Matrix2::identity();
-        self.prev = None;
+        self.initialized = true;
+        self.prev = Some(now);
     }
 
     // Advance the model, to predict the new value
     pub fn predict(&mut self) {
+        self.predict_at(self.now());
+    }
+
+    /// Advance the model up to the given moment on the reference clock.
+    pub fn predict_at(&mut self, now: f64) {
         // Only predict when we are somewhat accurate:
         if self.covariance.norm() < 10.0 {
-            let dt = self.get_dt();
+            let dt = self.get_dt(now);
             // State transition model:
             let transition_model = Matrix2::new(1.0, dt, 0.0, 1.0);
 
-            // Assume (for now) time elapses one second per second:
-            // let time_speed = 1.0;  // (s/s)
-
             // Estimated time increases:
             self.x_hat = transition_model * self.x_hat;
 
             // Some noise on the prediction (Q):
-            let noise_model = Matrix2::new(0.001 * dt, 0.0, 0.0, 0.001 * dt);
+            let noise_model = Matrix2::new(self.noise.time * dt, 0.0, 0.0, self.noise.rate * dt);
 
             // This estimate becomes more unpredictable over time
             self.covariance =
@@ -99,8 +158,13 @@ impl TimeTracker {
 
     // Inject a newly observed value!
     pub fn update(&mut self, observation: f64) {
+        self.update_at(observation, self.now());
+    }
+
+    /// Inject a value observed at the given moment on the reference clock.
+    pub fn update_at(&mut self, observation: f64, now: f64) {
         // Update to the last prediction possible:
-        self.predict();
+        self.predict_at(now);
 
         // Observation model (H):
         let observation_model = RowVector2::new(1.0, 0.0);
@@ -108,11 +172,11 @@ impl TimeTracker {
         // Innovation:
         let y: Matrix1<f64> = Matrix1::new(observation) - observation_model * self.x_hat;
 
-        if y.norm() > 5.0 {
-            self.reset(observation);
+        if !self.initialized || y.norm() > RESET_THRESHOLD {
+            self.reset(observation, now);
         } else {
             // Some measurement noise (R):
-            let measurement_noise: Matrix1<f64> = Matrix1::new(0.01);
+            let measurement_noise: Matrix1<f64> = Matrix1::new(self.noise.measurement);
 
             // Innovation covariance (S):
             let innovation_covariance: Matrix1<f64> =
@@ -127,50 +191,50 @@ impl TimeTracker {
                     .expect("Inverse must work");
 
             // Update estimate:
-            self.x_hat = self.x_hat + kalman_gain * y;
+            self.x_hat += kalman_gain * y;
 
             // Update variance:
             self.covariance =
                 (Matrix2::identity() - kalman_gain * observation_model) * self.covariance;
-
-            // println!(
-            //     "Updated with value {}: x_hat={} P={}",
-            //     observation, self.x_hat, self.P
-            // );
         }
 
         self.trace();
     }
 
     fn trace(&self) {
+        let perf_tracer = if let Some(perf_tracer) = &self.perf_tracer {
+            perf_tracer
+        } else {
+            return;
+        };
         let t1 = Instant::now();
 
-        self.perf_tracer.log_metric(
+        perf_tracer.log_metric(
             &format!("META.{}.x_hat[0]", self.trace_prefix),
             t1,
             self.x_hat[0],
         );
-        self.perf_tracer.log_metric(
+        perf_tracer.log_metric(
             &format!("META.{}.x_hat[1]", self.trace_prefix),
             t1,
             self.x_hat[1],
         );
-        self.perf_tracer.log_metric(
+        perf_tracer.log_metric(
             &format!("META.{}.P[0, 0]", self.trace_prefix),
             t1,
             self.covariance[(0, 0)],
         );
-        self.perf_tracer.log_metric(
+        perf_tracer.log_metric(
             &format!("META.{}.P[0, 1]", self.trace_prefix),
             t1,
             self.covariance[(0, 1)],
         );
-        self.perf_tracer.log_metric(
+        perf_tracer.log_metric(
             &format!("META.{}.P[1, 0]", self.trace_prefix),
             t1,
             self.covariance[(1, 0)],
         );
-        self.perf_tracer.log_metric(
+        perf_tracer.log_metric(
             &format!("META.{}.P[1, 1]", self.trace_prefix),
             t1,
             self.covariance[(1, 1)],
@@ -180,4 +244,19 @@ impl TimeTracker {
     pub fn get_estimate(&self) -> f64 {
         self.x_hat[0]
     }
+
+    /// Estimated seconds of remote time per second on the reference clock.
+    pub fn get_rate(&self) -> f64 {
+        self.x_hat[1]
+    }
+
+    /// Moment on the reference clock of the estimate, if any
+    /// observation was made.
+    pub fn estimate_moment(&self) -> Option<f64> {
+        if self.initialized {
+            self.prev
+        } else {
+            None
+        }
+    }
 }
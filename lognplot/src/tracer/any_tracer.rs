@@ -1,5 +1,5 @@
 use super::{DbTracer, TcpTracer, Tracer};
-use crate::net::TcpClient;
+use crate::net::BufferedClient;
 use crate::tsdb::TsDbHandle;
 use std::time::Instant;
 
@@ -16,8 +16,8 @@ pub enum AnyTracer {
 }
 
 impl AnyTracer {
-    /// Create a new tracer which traces into the given tcp client.
-    pub fn new_tcp(client: TcpClient) -> Self {
+    /// Create a new tracer which traces into the given client.
+    pub fn new_tcp(client: BufferedClient) -> Self {
         AnyTracer::Net(TcpTracer::new(client))
     }
 
@@ -1,20 +1,20 @@
 //! Trace metrics over the web.
 
 use super::Tracer;
-use crate::net::TcpClient;
-use std::sync::Mutex;
+use crate::net::BufferedClient;
 use std::time::Instant;
 
+/// Send metrics to a server, without blocking the traced program.
 pub struct TcpTracer {
     gui_start_instant: Instant,
-    client: Mutex<TcpClient>,
+    client: BufferedClient,
 }
 
 impl TcpTracer {
-    pub fn new(client: TcpClient) -> Self {
+    pub fn new(client: BufferedClient) -> Self {
         TcpTracer {
             gui_start_instant: Instant::now(),
-            client: Mutex::new(client),
+            client,
         }
     }
 }
@@ -23,26 +23,12 @@ impl Tracer for TcpTracer {
     fn log_metric(&self, name: &str, timestamp: Instant, value: f64) {
         let elapsed = timestamp.duration_since(self.gui_start_instant);
         let elapsed_seconds: f64 = elapsed.as_secs_f64();
-        if let Err(err) = self
-            .client
-            .lock()
-            .unwrap()
-            .send_sample(name, elapsed_seconds, value)
-        {
-            error!("Error sending metric: {:?}", err);
-        }
+        self.client.send_sample(name, elapsed_seconds, value);
     }
 
     fn log_text(&self, name: &str, timestamp: Instant, text: String) {
         let elapsed = timestamp.duration_since(self.gui_start_instant);
         let elapsed_seconds: f64 = elapsed.as_secs_f64();
-        if let Err(err) = self
-            .client
-            .lock()
-            .unwrap()
-            .send_text(name, elapsed_seconds, text)
-        {
-            error!("Error sending text: {:?}", err);
-        }
+        self.client.send_text(name, elapsed_seconds, text);
     }
 }
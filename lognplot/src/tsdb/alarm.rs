@@ -0,0 +1,350 @@
+//! Limit monitoring with alarm rules.
+//!
+//! Rules are checked on every value added to the database.
+//! Rules about missing samples are checked by calling
+//! `AlarmEngine::check_timeouts` on a regular interval.
+
+use super::observations::{Observation, Sample};
+use crate::time::TimeStamp;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+/// Name of the text track which receives an entry for each alarm.
+pub const ALARM_TRACK: &str = "alarms";
+
+/// The condition under which an alarm is raised.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum AlarmCondition {
+    /// Value stays above the level for at least duration seconds.
+    #[serde(rename = "above")]
+    Above {
+        level: f64,
+        #[serde(default)]
+        duration: f64,
+    },
+
+    /// Value stays below the level for at least duration seconds.
+    #[serde(rename = "below")]
+    Below {
+        level: f64,
+        #[serde(default)]
+        duration: f64,
+    },
+
+    /// Value stays outside the range low .. high for at least duration seconds.
+    #[serde(rename = "outside")]
+    Outside {
+        low: f64,
+        high: f64,
+        #[serde(default)]
+        duration: f64,
+    },
+
+    /// No new sample arrived within timeout seconds.
+    #[serde(rename = "stale")]
+    Stale { timeout: f64 },
+}
+
+/// A rule on a signal.
+///
+/// Rules are stored in JSON like this:
+/// `{"signal": "battery_voltage", "type": "below", "level": 10.5, "duration": 2.0}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlarmRule {
+    /// Optional name of the rule, used in the alarm message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// The signal to watch.
+    pub signal: String,
+
+    #[serde(flatten)]
+    pub condition: AlarmCondition,
+}
+
+impl AlarmRule {
+    pub fn new(signal: &str, condition: AlarmCondition) -> Self {
+        AlarmRule {
+            name: None,
+            signal: signal.to_owned(),
+            condition,
+        }
+    }
+
+    /// Describe the violation of this rule.
+    fn describe(&self) -> String {
+        let violation = match &self.condition {
+            AlarmCondition::Above { level, duration } => {
+                format!("{} above {} for {} s", self.signal, level, duration)
+            }
+            AlarmCondition::Below { level, duration } => {
+                format!("{} below {} for {} s", self.signal, level, duration)
+            }
+            AlarmCondition::Outside {
+                low,
+                high,
+                duration,
+            } => format!(
+                "{} outside {} .. {} for {} s",
+                self.signal, low, high, duration
+            ),
+            AlarmCondition::Stale { timeout } => {
+                format!("no sample on {} for {} s", self.signal, timeout)
+            }
+        };
+
+        if let Some(name) = &self.name {
+            format!("{}: {}", name, violation)
+        } else {
+            violation
+        }
+    }
+
+    /// Test if a value violates this rule, and the allowed duration of the violation.
+    fn violation(&self, value: f64) -> Option<f64> {
+        match self.condition {
+            AlarmCondition::Above { level, duration } if value > level => Some(duration),
+            AlarmCondition::Below { level, duration } if value < level => Some(duration),
+            AlarmCondition::Outside {
+                low,
+                high,
+                duration,
+            } if value < low || value > high => Some(duration),
+            _ => None,
+        }
+    }
+}
+
+/// A raised alarm.
+#[derive(Debug, Clone)]
+pub struct Alarm {
+    pub signal: String,
+    pub timestamp: TimeStamp,
+    pub message: String,
+}
+
+/// A rule along with its evaluation state.
+#[derive(Debug)]
+struct RuleState {
+    rule: AlarmRule,
+
+    /// Moment at which the current violation started.
+    violation_start: Option<TimeStamp>,
+
+    /// The current violation was reported already.
+    raised: bool,
+
+    /// Time of the last sample, both in data time and wall clock time.
+    last_sample: Option<(TimeStamp, Instant)>,
+
+    /// Moment the rule was added, the start of the wait for a first sample.
+    added: Instant,
+}
+
+impl RuleState {
+    fn new(rule: AlarmRule, added: Instant) -> Self {
+        RuleState {
+            rule,
+            violation_start: None,
+            raised: false,
+            last_sample: None,
+            added,
+        }
+    }
+
+    fn raise(&mut self, timestamp: TimeStamp) -> Alarm {
+        self.raised = true;
+        Alarm {
+            signal: self.rule.signal.clone(),
+            timestamp,
+            message: self.rule.describe(),
+        }
+    }
+
+    fn process(&mut self, observation: &Observation<Sample>, now: Instant) -> Option<Alarm> {
+        let timestamp = &observation.timestamp;
+        if let AlarmCondition::Stale { .. } = self.rule.condition {
+            self.last_sample = Some((timestamp.clone(), now));
+            self.raised = false;
+            return None;
+        }
+
+        if let Some(duration) = self.rule.violation(observation.value.value) {
+            let start = self
+                .violation_start
+                .get_or_insert_with(|| timestamp.clone());
+            if !self.raised && timestamp.amount - start.amount >= duration {
+                return Some(self.raise(timestamp.clone()));
+            }
+        } else {
+            self.violation_start = None;
+            self.raised = false;
+        }
+        None
+    }
+
+    fn check_timeout(&mut self, now: Instant, current: &TimeStamp) -> Option<Alarm> {
+        if let AlarmCondition::Stale { timeout } = self.rule.condition {
+            if self.raised {
+                return None;
+            }
+            // Compare in seconds, a Duration cannot hold a negative or NaN timeout:
+            if let Some((timestamp, seen)) = self.last_sample.clone() {
+                if now.duration_since(seen).as_secs_f64() > timeout {
+                    return Some(self.raise(timestamp + timeout));
+                }
+            } else if now.duration_since(self.added).as_secs_f64() > timeout {
+                // The signal never sent a sample, so there is no data time to relate to:
+                return Some(self.raise(current.clone()));
+            }
+        }
+        None
+    }
+}
+
+/// Evaluates alarm rules on incoming data.
+#[derive(Debug, Default)]
+pub struct AlarmEngine {
+    rules: Vec<RuleState>,
+}
+
+impl AlarmEngine {
+    pub fn add_rule(&mut self, rule: AlarmRule, now: Instant) {
+        self.rules.push(RuleState::new(rule, now));
+    }
+
+    pub fn clear_rules(&mut self) {
+        self.rules.clear();
+    }
+
+    pub fn rules(&self) -> Vec<AlarmRule> {
+        self.rules.iter().map(|r| r.rule.clone()).collect()
+    }
+
+    /// Check new observations of a signal against the rules.
+    pub fn process(
+        &mut self,
+        name: &str,
+        observations: &[Observation<Sample>],
+        now: Instant,
+    ) -> Vec<Alarm> {
+        let mut alarms = vec![];
+        for rule in self.rules.iter_mut().filter(|r| r.rule.signal == name) {
+            for observation in observations {
+                if let Some(alarm) = rule.process(observation, now) {
+                    alarms.push(alarm);
+                }
+            }
+        }
+        alarms
+    }
+
+    /// Check for signals which did not receive samples for too long.
+    ///
+    /// Alarms on signals which never received a sample are raised at
+    /// the `current` time.
+    pub fn check_timeouts(&mut self, now: Instant, current: &TimeStamp) -> Vec<Alarm> {
+        self.rules
+            .iter_mut()
+            .filter_map(|r| r.check_timeout(now, current))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AlarmCondition, AlarmEngine, AlarmRule};
+    use crate::time::TimeStamp;
+    use crate::tsdb::observations::{Observation, Sample};
+    use std::time::{Duration, Instant};
+
+    fn observations(values: &[f64]) -> Vec<Observation<Sample>> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| Observation::new(TimeStamp::from_seconds(i as isize), Sample::new(*v)))
+            .collect()
+    }
+
+    #[test]
+    fn limit_with_duration() {
+        let mut engine = AlarmEngine::default();
+        let condition = AlarmCondition::Below {
+            level: 10.5,
+            duration: 2.0,
+        };
+        let now = Instant::now();
+        engine.add_rule(AlarmRule::new("battery_voltage", condition), now);
+
+        let values = [12.0, 10.0, 10.0, 12.0, 10.0, 10.0, 10.0, 10.0];
+        let alarms = engine.process("battery_voltage", &observations(&values), now);
+        assert_eq!(alarms.len(), 1);
+        assert_eq!(alarms[0].timestamp.amount, 6.0);
+        assert_eq!(alarms[0].message, "battery_voltage below 10.5 for 2 s");
+
+        assert!(engine
+            .process("other", &observations(&values), now)
+            .is_empty());
+    }
+
+    #[test]
+    fn stale_signal() {
+        let mut engine = AlarmEngine::default();
+        let mut rule = AlarmRule::new("heartbeat", AlarmCondition::Stale { timeout: 1.0 });
+        rule.name = Some("Heartbeat lost".to_owned());
+        let start = Instant::now();
+        engine.add_rule(rule, start);
+        let current = TimeStamp::new(100.0);
+
+        engine.process("heartbeat", &observations(&[1.0, 1.0]), start);
+        assert!(engine.check_timeouts(start, &current).is_empty());
+
+        let later = start + Duration::from_millis(1500);
+        let alarms = engine.check_timeouts(later, &current);
+        assert_eq!(alarms.len(), 1);
+        assert_eq!(alarms[0].timestamp.amount, 2.0);
+        assert_eq!(
+            alarms[0].message,
+            "Heartbeat lost: no sample on heartbeat for 1 s"
+        );
+
+        // Raised only once:
+        assert!(engine.check_timeouts(later, &current).is_empty());
+    }
+
+    #[test]
+    fn signal_without_samples() {
+        let mut engine = AlarmEngine::default();
+        let start = Instant::now();
+        engine.add_rule(
+            AlarmRule::new("heartbeat", AlarmCondition::Stale { timeout: 1.0 }),
+            start,
+        );
+        let current = TimeStamp::new(100.0);
+        assert!(engine.check_timeouts(start, &current).is_empty());
+
+        let later = start + Duration::from_millis(1500);
+        let alarms = engine.check_timeouts(later, &current);
+        assert_eq!(alarms.len(), 1);
+        assert_eq!(alarms[0].timestamp.amount, 100.0);
+        assert!(engine.check_timeouts(later, &current).is_empty());
+    }
+
+    #[test]
+    fn invalid_timeout() {
+        let mut engine = AlarmEngine::default();
+        let start = Instant::now();
+        for timeout in &[f64::NAN, f64::INFINITY, -1.0] {
+            engine.add_rule(
+                AlarmRule::new("heartbeat", AlarmCondition::Stale { timeout: *timeout }),
+                start,
+            );
+        }
+        let current = TimeStamp::new(100.0);
+
+        // Only the negative timeout has passed:
+        let later = start + Duration::from_millis(1500);
+        assert_eq!(engine.check_timeouts(later, &current).len(), 1);
+    }
+}
@@ -0,0 +1,100 @@
+//! Annotations on the time line.
+//!
+//! Annotations mark moments or periods of interest,
+//! for example the start of a test step.
+
+use crate::time::{TimeSpan, TimeStamp};
+
+/// A labelled time point or time span.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    /// The period of this annotation. For a time point, start and end are equal.
+    pub timespan: TimeSpan,
+
+    pub title: String,
+
+    /// Color of the annotation, for example `#FF7F0E`.
+    pub color: Option<String>,
+}
+
+impl Annotation {
+    /// Create an annotation at a single moment.
+    pub fn point(timestamp: TimeStamp, title: &str) -> Self {
+        Self::span(TimeSpan::new(timestamp.clone(), timestamp), title)
+    }
+
+    /// Create an annotation covering a period.
+    pub fn span(timespan: TimeSpan, title: &str) -> Self {
+        Annotation {
+            timespan,
+            title: title.to_owned(),
+            color: None,
+        }
+    }
+
+    /// Test if this annotation is a single time point.
+    pub fn is_point(&self) -> bool {
+        self.timespan.start == self.timespan.end
+    }
+}
+
+/// Collection of annotations, ordered by start time.
+#[derive(Debug, Default)]
+pub struct AnnotationStore {
+    annotations: Vec<Annotation>,
+}
+
+impl AnnotationStore {
+    pub fn add(&mut self, annotation: Annotation) {
+        let index = self
+            .annotations
+            .iter()
+            .rposition(|a| a.timespan.start <= annotation.timespan.start)
+            .map_or(0, |i| i + 1);
+        self.annotations.insert(index, annotation);
+    }
+
+    /// Get the annotations which overlap the given timespan.
+    pub fn query(&self, timespan: &TimeSpan) -> Vec<Annotation> {
+        self.annotations
+            .iter()
+            .take_while(|a| a.timespan.start <= timespan.end)
+            .filter(|a| a.timespan.overlap(timespan))
+            .cloned()
+            .collect()
+    }
+
+    pub fn all(&self) -> Vec<Annotation> {
+        self.annotations.clone()
+    }
+
+    pub fn clear(&mut self) {
+        self.annotations.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Annotation, AnnotationStore};
+    use crate::time::{TimeSpan, TimeStamp};
+
+    #[test]
+    fn query_in_order() {
+        let mut store = AnnotationStore::default();
+        store.add(Annotation::point(
+            TimeStamp::from_seconds(5),
+            "motor stalled",
+        ));
+        store.add(Annotation::span(
+            TimeSpan::from_seconds(1, 3),
+            "test step 3",
+        ));
+        store.add(Annotation::point(TimeStamp::from_seconds(20), "done"));
+
+        let found = store.query(&TimeSpan::from_seconds(2, 10));
+        let titles: Vec<&str> = found.iter().map(|a| a.title.as_str()).collect();
+        assert_eq!(titles, vec!["test step 3", "motor stalled"]);
+        assert!(found[1].is_point());
+        assert!(!found[0].is_point());
+    }
+}
@@ -3,9 +3,16 @@
 //! This trait defines the time series database API
 
 use super::observations::{Observation, ProfileEvent, Sample, Text};
-use super::ChangeSubscriber;
+use super::SignalMetadata;
+use super::{AlarmRule, Annotation, ClockModel};
+use super::{ChangeSubscriber, DataSubscriber};
+use super::{CrossStatistics, Resampled, Spectrum, SpectrumOptions};
+use super::{CrossingEdge, Extremes, SearchDirection, ValuePredicate};
+use super::{IngestStatistics, MemoryBudget, MemoryUsage};
 use super::{Query, QueryResult};
 use super::{QuickSummary, Summary};
+use super::{TextHit, TextQuery};
+use super::{Trigger, TriggerHit, TriggerId};
 use crate::time::TimeSpan;
 
 /// Database API
@@ -19,15 +26,86 @@ pub trait TsDbApi {
     // ==== Remove api
     fn delete_all(&mut self);
     fn delete(&mut self, name: &str);
+    fn delete_matching(&mut self, pattern: &str);
 
     // ==== Query api
     fn get_signal_names(&self) -> Vec<String>;
+    fn get_child_names(&self, prefix: &str) -> Vec<String>;
+    fn match_signal_names(&self, pattern: &str) -> Vec<String>;
     fn quick_summary(&self, name: &str) -> Option<QuickSummary>;
     fn summary(&self, name: &str, timespan: Option<&TimeSpan>) -> Option<Summary>;
     fn get_raw_samples(&self, name: &str) -> Option<Vec<Observation<Sample>>>;
+    fn get_raw_range(&self, name: &str, timespan: &TimeSpan) -> Option<Vec<Observation<Sample>>>;
     fn query(&self, name: &str, query: Query) -> Option<QueryResult>;
 
+    // ==== Search api
+    fn find_value(
+        &self,
+        name: &str,
+        predicate: ValuePredicate,
+        timespan: &TimeSpan,
+        direction: SearchDirection,
+    ) -> Option<Observation<Sample>>;
+    fn find_crossing(
+        &self,
+        name: &str,
+        level: f64,
+        edge: CrossingEdge,
+        timespan: &TimeSpan,
+        direction: SearchDirection,
+    ) -> Option<Observation<Sample>>;
+    fn find_extremes(&self, name: &str, timespan: &TimeSpan) -> Option<Extremes>;
+    fn search_text(
+        &self,
+        pattern: &str,
+        query: &TextQuery,
+        timespan: &TimeSpan,
+        limit: usize,
+    ) -> Vec<TextHit>;
+
+    // ==== Statistics api
+    fn ingest_statistics(&self, name: &str) -> Option<IngestStatistics>;
+    fn resample(&self, name: &str, timespan: &TimeSpan, count: usize) -> Option<Resampled>;
+    fn cross_statistics(&self, a: &str, b: &str, timespan: &TimeSpan) -> Option<CrossStatistics>;
+    fn spectrum(
+        &self,
+        name: &str,
+        timespan: &TimeSpan,
+        options: &SpectrumOptions,
+    ) -> Option<Spectrum>;
+
+    // ==== Trigger api
+    fn arm_trigger(&mut self, trigger: Trigger) -> TriggerId;
+    fn disarm_trigger(&mut self, id: TriggerId);
+    fn get_trigger_hits(&self, id: TriggerId) -> Vec<TriggerHit>;
+
+    // ==== Annotation api
+    fn add_annotation(&mut self, annotation: Annotation);
+    fn get_annotations(&self, timespan: Option<&TimeSpan>) -> Vec<Annotation>;
+
+    // ==== Clock api
+    fn set_clock_model(&mut self, source: &str, model: ClockModel);
+    fn estimate_clock(&mut self, source: &str);
+    fn assign_clock(&mut self, pattern: &str, source: &str);
+    fn get_clock_model(&self, source: &str) -> Option<ClockModel>;
+
+    // ==== Memory api
+    fn memory_usage(&self, name: &str) -> Option<MemoryUsage>;
+    fn total_memory_usage(&self) -> MemoryUsage;
+    fn set_memory_budget(&mut self, budget: Option<MemoryBudget>);
+
+    // ==== Alarm api
+    fn add_alarm_rule(&mut self, rule: AlarmRule);
+    fn clear_alarm_rules(&mut self);
+    fn get_alarm_rules(&self) -> Vec<AlarmRule>;
+    fn check_alarms(&mut self);
+
+    // ==== Meta data api
+    fn set_metadata(&mut self, name: &str, metadata: SignalMetadata);
+    fn get_metadata(&self, name: &str) -> Option<SignalMetadata>;
+
     // notifications
     fn register_notifier(&mut self, subscriber: ChangeSubscriber);
+    fn register_data_subscriber(&mut self, subscriber: DataSubscriber);
     fn poll_events(&mut self);
 }
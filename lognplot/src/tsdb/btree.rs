@@ -4,7 +4,7 @@
 //! Leaf and intermediate nodes can have multiple child nodes.
 
 use super::observations::{Aggregation, Metrics, Observation};
-use super::RangeQueryResult;
+use super::{RangeQueryResult, SearchDirection};
 use crate::time::TimeSpan;
 
 /// This is the intermediate level fanout ratio.
@@ -139,6 +139,44 @@ where
         Aggregation::from_aggregations(&all_aggregations)
     }
 
+    /// Find the first observation in the given direction which satisfies a predicate.
+    ///
+    /// Sub trees for which `may_contain` returns false are skipped
+    /// entirely, which makes the search fast on large traces.
+    pub fn find<P, F>(
+        &self,
+        timespan: &TimeSpan,
+        direction: SearchDirection,
+        may_contain: &P,
+        predicate: &F,
+    ) -> Option<Observation<V>>
+    where
+        P: Fn(&Aggregation<V, M>) -> bool,
+        F: Fn(&Observation<V>) -> bool,
+    {
+        self.root
+            .find(timespan, direction, may_contain, predicate)
+            .cloned()
+    }
+
+    /// Find up to `limit` observations which satisfy a predicate, oldest first.
+    pub fn find_all<P, F>(
+        &self,
+        timespan: &TimeSpan,
+        may_contain: &P,
+        predicate: &F,
+        limit: usize,
+    ) -> Vec<Observation<V>>
+    where
+        P: Fn(&Aggregation<V, M>) -> bool,
+        F: Fn(&Observation<V>) -> bool,
+    {
+        let mut found = vec![];
+        self.root
+            .find_all(timespan, may_contain, predicate, limit, &mut found);
+        found.into_iter().cloned().collect()
+    }
+
     /// Get a summary about all data in this tree.
     pub fn summary(&self) -> Option<Aggregation<V, M>> {
         self.root.metrics()
@@ -148,6 +186,25 @@ where
     pub fn to_vec(&self) -> Vec<Observation<V>> {
         self.root.to_vec()
     }
+
+    /// Estimate the bytes used by observations and by tree nodes,
+    /// for a tree holding `count` observations.
+    ///
+    /// Observations are only appended, so all leaves and intermediate
+    /// nodes, except the last ones, are completely filled.
+    pub fn memory_estimate(count: usize) -> (usize, usize) {
+        let leaves = count.div_ceil(LEAF_CHUNK_SIZE);
+        let mut nodes = leaves.max(1);
+        let mut level = leaves;
+        while level > 1 {
+            level = level.div_ceil(INTERMEDIATE_CHUNK_SIZE);
+            nodes += level;
+        }
+
+        let observations = count * std::mem::size_of::<Observation<V>>();
+        let aggregations = nodes * std::mem::size_of::<Node<V, M>>();
+        (observations, aggregations)
+    }
 }
 
 /// This is a sort of B+ tree data structure
@@ -268,6 +325,63 @@ where
             Node::Intermediate(internal) => internal.metrics(),
         }
     }
+
+    /// Search this node for an observation.
+    fn find<P, F>(
+        &self,
+        timespan: &TimeSpan,
+        direction: SearchDirection,
+        may_contain: &P,
+        predicate: &F,
+    ) -> Option<&Observation<V>>
+    where
+        P: Fn(&Aggregation<V, M>) -> bool,
+        F: Fn(&Observation<V>) -> bool,
+    {
+        match self {
+            Node::Intermediate(internal) => {
+                internal.find(timespan, direction, may_contain, predicate)
+            }
+            Node::Leaf(leaf) => leaf.find(timespan, direction, predicate),
+        }
+    }
+
+    /// Collect matching observations of this node, until `limit` are found.
+    fn find_all<'n, P, F>(
+        &'n self,
+        timespan: &TimeSpan,
+        may_contain: &P,
+        predicate: &F,
+        limit: usize,
+        found: &mut Vec<&'n Observation<V>>,
+    ) where
+        P: Fn(&Aggregation<V, M>) -> bool,
+        F: Fn(&Observation<V>) -> bool,
+    {
+        match self {
+            Node::Intermediate(internal) => {
+                for child in &internal.children {
+                    if found.len() >= limit {
+                        break;
+                    }
+                    if let Some(child_metrics) = child.metrics() {
+                        if child_metrics.timespan.overlap(timespan) && may_contain(&child_metrics) {
+                            child.find_all(timespan, may_contain, predicate, limit, found);
+                        }
+                    }
+                }
+            }
+            Node::Leaf(leaf) => {
+                let remaining = limit.saturating_sub(found.len());
+                found.extend(
+                    leaf.observations
+                        .iter()
+                        .filter(|o| timespan.contains(&o.timestamp) && predicate(o))
+                        .take(remaining),
+                );
+            }
+        }
+    }
 }
 
 /// The result of selecting a time range on a node.
@@ -483,6 +597,48 @@ where
         self.children.iter().collect()
     }
 
+    /// Search the child nodes, skipping the ones which cannot contain a match.
+    fn find<P, F>(
+        &self,
+        timespan: &TimeSpan,
+        direction: SearchDirection,
+        may_contain: &P,
+        predicate: &F,
+    ) -> Option<&Observation<V>>
+    where
+        P: Fn(&Aggregation<V, M>) -> bool,
+        F: Fn(&Observation<V>) -> bool,
+    {
+        let candidates = self.children.iter().filter(|child| {
+            if let Some(child_metrics) = child.metrics() {
+                child_metrics.timespan.overlap(timespan) && may_contain(&child_metrics)
+            } else {
+                false
+            }
+        });
+
+        let mut found = None;
+        match direction {
+            SearchDirection::Forward => {
+                for child in candidates {
+                    found = child.find(timespan, direction, may_contain, predicate);
+                    if found.is_some() {
+                        break;
+                    }
+                }
+            }
+            SearchDirection::Backward => {
+                for child in candidates.rev() {
+                    found = child.find(timespan, direction, may_contain, predicate);
+                    if found.is_some() {
+                        break;
+                    }
+                }
+            }
+        }
+        found
+    }
+
     fn to_vec(&self) -> Vec<Observation<V>> {
         let mut samples: Vec<Observation<V>> = vec![];
         for child in &self.children {
@@ -563,6 +719,23 @@ where
         self.observations.iter().collect()
     }
 
+    /// Find the first matching observation in the given direction.
+    fn find<F>(
+        &self,
+        timespan: &TimeSpan,
+        direction: SearchDirection,
+        predicate: &F,
+    ) -> Option<&Observation<V>>
+    where
+        F: Fn(&Observation<V>) -> bool,
+    {
+        let matches = |o: &&Observation<V>| timespan.contains(&o.timestamp) && predicate(o);
+        match direction {
+            SearchDirection::Forward => self.observations.iter().find(matches),
+            SearchDirection::Backward => self.observations.iter().rev().find(matches),
+        }
+    }
+
     fn to_vec(&self) -> Vec<Observation<V>> {
         self.observations.clone()
     }
@@ -0,0 +1,300 @@
+//! Time bases of data sources.
+//!
+//! Embedded targets often timestamp their samples with their own
+//! tick clock. To show these samples on the same time axis as other
+//! data, each source can have a clock model, which converts source
+//! time into reference time. The model can be set manually, or be
+//! estimated from the moments at which samples arrive.
+//!
+//! The estimator is the time tracker which the GUI also uses for
+//! tailing, tracking source time as a function of reference time.
+
+use super::observations::{Aggregation, Metrics, Observation};
+use super::{Extremes, LastValue, QueryResult, QuickSummary, RangeQueryResult, Resampled};
+use super::{Summary, TextHit};
+use crate::time::{TimeSpan, TimeStamp, TimeTracker, TrackerNoise};
+use serde::{Deserialize, Serialize};
+
+/// Noise of the estimator. Arrival jitter is in the order of
+/// milliseconds, and clocks drift slowly.
+const ESTIMATOR_NOISE: TrackerNoise = TrackerNoise {
+    time: 1.0e-6,
+    rate: 1.0e-12,
+    measurement: 1.0e-4,
+};
+
+/// Conversion from source time into reference time.
+///
+/// reference time = t + offset + drift * t
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct ClockModel {
+    /// Offset in seconds.
+    pub offset: f64,
+
+    /// Drift in seconds per second, for example `50e-6` for a
+    /// clock which runs 50 ppm slow.
+    #[serde(default)]
+    pub drift: f64,
+}
+
+impl ClockModel {
+    pub fn new(offset: f64, drift: f64) -> Self {
+        ClockModel { offset, drift }
+    }
+
+    /// Convert a source time into reference time.
+    pub fn apply(&self, t: f64) -> f64 {
+        t + self.offset + self.drift * t
+    }
+
+    /// Convert a reference time back into source time.
+    pub fn invert(&self, t: f64) -> f64 {
+        (t - self.offset) / (1.0 + self.drift)
+    }
+
+    pub fn apply_timestamp(&self, timestamp: &TimeStamp) -> TimeStamp {
+        TimeStamp::new(self.apply(timestamp.amount))
+    }
+
+    pub fn apply_timespan(&self, timespan: &TimeSpan) -> TimeSpan {
+        TimeSpan::new(
+            self.apply_timestamp(&timespan.start),
+            self.apply_timestamp(&timespan.end),
+        )
+    }
+
+    pub fn invert_timespan(&self, timespan: &TimeSpan) -> TimeSpan {
+        TimeSpan::new(
+            TimeStamp::new(self.invert(timespan.start.amount)),
+            TimeStamp::new(self.invert(timespan.end.amount)),
+        )
+    }
+}
+
+/// Estimates a clock model from pairs of source and reference time.
+pub struct ClockEstimator {
+    tracker: TimeTracker,
+}
+
+impl Default for ClockEstimator {
+    fn default() -> Self {
+        ClockEstimator {
+            tracker: TimeTracker::untraced(ESTIMATOR_NOISE),
+        }
+    }
+}
+
+impl ClockEstimator {
+    /// Inject an observation of a source time, and the reference time at that moment.
+    pub fn update(&mut self, source_time: f64, reference_time: f64) {
+        self.tracker.update_at(source_time, reference_time);
+    }
+
+    /// Get the current clock model, if any observations were made.
+    pub fn model(&self) -> Option<ClockModel> {
+        let moment = self.tracker.estimate_moment()?;
+        let rate = self.tracker.get_rate();
+        if rate > 0.0 {
+            // source time = estimate + rate * (reference time - moment)
+            let offset = moment - self.tracker.get_estimate() / rate;
+            Some(ClockModel::new(offset, 1.0 / rate - 1.0))
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Debug for ClockEstimator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClockEstimator")
+            .field("model", &self.model())
+            .finish()
+    }
+}
+
+/// The time base of a single source.
+#[derive(Debug)]
+pub struct TimeBase {
+    model: ClockModel,
+
+    /// Estimator, when the model is estimated automatically.
+    estimator: Option<ClockEstimator>,
+}
+
+impl TimeBase {
+    /// A time base with a fixed model.
+    pub fn manual(model: ClockModel) -> Self {
+        TimeBase {
+            model,
+            estimator: None,
+        }
+    }
+
+    /// A time base which is estimated from observations.
+    pub fn automatic() -> Self {
+        TimeBase {
+            model: ClockModel::default(),
+            estimator: Some(ClockEstimator::default()),
+        }
+    }
+
+    pub fn is_automatic(&self) -> bool {
+        self.estimator.is_some()
+    }
+
+    pub fn model(&self) -> ClockModel {
+        self.model
+    }
+
+    /// Feed an observation into the estimator, if any.
+    pub fn observe(&mut self, source_time: f64, reference_time: f64) {
+        if let Some(estimator) = &mut self.estimator {
+            estimator.update(source_time, reference_time);
+            if let Some(model) = estimator.model() {
+                self.model = model;
+            }
+        }
+    }
+}
+
+/// Data which carries timestamps, which can be converted from
+/// source time into reference time.
+pub(crate) trait Retime {
+    fn retime(&mut self, model: &ClockModel);
+}
+
+impl Retime for TimeStamp {
+    fn retime(&mut self, model: &ClockModel) {
+        self.amount = model.apply(self.amount);
+    }
+}
+
+impl Retime for TimeSpan {
+    fn retime(&mut self, model: &ClockModel) {
+        self.start.retime(model);
+        self.end.retime(model);
+    }
+}
+
+impl<T: Retime> Retime for Option<T> {
+    fn retime(&mut self, model: &ClockModel) {
+        if let Some(item) = self {
+            item.retime(model);
+        }
+    }
+}
+
+impl<T: Retime> Retime for Vec<T> {
+    fn retime(&mut self, model: &ClockModel) {
+        for item in self {
+            item.retime(model);
+        }
+    }
+}
+
+impl<V> Retime for Observation<V> {
+    fn retime(&mut self, model: &ClockModel) {
+        self.timestamp.retime(model);
+    }
+}
+
+impl<V, M> Retime for Aggregation<V, M>
+where
+    M: Metrics<V> + From<V>,
+{
+    fn retime(&mut self, model: &ClockModel) {
+        self.timespan.retime(model);
+    }
+}
+
+impl<V, M> Retime for RangeQueryResult<V, M>
+where
+    M: Metrics<V> + From<V>,
+{
+    fn retime(&mut self, model: &ClockModel) {
+        match self {
+            RangeQueryResult::Observations(observations) => observations.retime(model),
+            RangeQueryResult::Aggregations(aggregations) => aggregations.retime(model),
+        }
+    }
+}
+
+impl Retime for QueryResult {
+    fn retime(&mut self, model: &ClockModel) {
+        match self {
+            QueryResult::Value(result) => result.retime(model),
+            QueryResult::Text(result) => result.retime(model),
+            QueryResult::Profile(result) => result.retime(model),
+        }
+    }
+}
+
+impl Retime for Summary {
+    fn retime(&mut self, model: &ClockModel) {
+        match self {
+            Summary::Value(summary) => summary.retime(model),
+            Summary::Text(summary) => summary.retime(model),
+            Summary::Profile(summary) => summary.retime(model),
+        }
+    }
+}
+
+impl Retime for QuickSummary {
+    fn retime(&mut self, model: &ClockModel) {
+        match &mut self.last {
+            LastValue::Value(last) => last.retime(model),
+            LastValue::Text(last) => last.retime(model),
+            LastValue::Profile(last) => last.retime(model),
+        }
+    }
+}
+
+impl Retime for Extremes {
+    fn retime(&mut self, model: &ClockModel) {
+        self.min.retime(model);
+        self.max.retime(model);
+    }
+}
+
+impl Retime for TextHit {
+    fn retime(&mut self, model: &ClockModel) {
+        self.timestamp.retime(model);
+    }
+}
+
+impl Retime for Resampled {
+    fn retime(&mut self, model: &ClockModel) {
+        self.start.retime(model);
+        self.step *= 1.0 + model.drift;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClockModel, TimeBase};
+
+    #[test]
+    fn estimate_offset_and_drift() {
+        // A target clock which started 1000 seconds after the reference,
+        // and runs 100 ppm slow:
+        let truth = ClockModel::new(1000.0, 100.0e-6);
+
+        let mut time_base = TimeBase::automatic();
+        for i in 0..2000 {
+            let source_time = i as f64 * 0.5;
+            // Some arrival jitter:
+            let jitter = if i % 2 == 0 { 0.005 } else { -0.005 };
+            time_base.observe(source_time, truth.apply(source_time) + jitter);
+        }
+
+        let model = time_base.model();
+        assert!((model.offset - truth.offset).abs() < 0.01);
+        assert!((model.drift - truth.drift).abs() < 10.0e-6);
+        assert!((model.apply(500.0) - truth.apply(500.0)).abs() < 0.01);
+        assert!((model.invert(model.apply(500.0)) - 500.0).abs() < 1.0e-9);
+
+        // A reboot of the target resets the estimate:
+        time_base.observe(0.0, 5000.0);
+        assert_eq!(time_base.model().apply(0.0), 5000.0);
+    }
+}
@@ -0,0 +1,149 @@
+//! Statistics on pairs of signals.
+//!
+//! Both signals are resampled onto the same time grid, after
+//! which the values can be compared sample by sample.
+
+use super::resample::Resampled;
+
+/// Relation between two signals a and b over a period of time.
+#[derive(Debug, Clone)]
+pub struct CrossStatistics {
+    /// Amount of resampled values used.
+    pub count: usize,
+
+    pub covariance: f64,
+
+    /// Pearson correlation coefficient, between -1 and 1.
+    pub correlation: f64,
+
+    /// Slope of the least squares fit b = slope * a + intercept.
+    pub slope: f64,
+
+    pub intercept: f64,
+
+    /// Time in seconds by which b lags behind a, at the peak of the
+    /// absolute cross correlation, so inverted signals are found as well.
+    pub lag: f64,
+
+    /// Correlation coefficient of a and b, when b is shifted back by the lag.
+    ///
+    /// This is negative when b follows a inverted.
+    pub lag_correlation: f64,
+}
+
+/// Compare two signals, resampled on the same grid.
+///
+/// Lags up to `max_lag` steps in both directions are tried.
+pub fn cross_statistics(a: &Resampled, b: &Resampled, max_lag: usize) -> Option<CrossStatistics> {
+    let count = a.len().min(b.len());
+    if count < 2 {
+        return None;
+    }
+    let a = &a.values[..count];
+    let step = b.step;
+    let b = &b.values[..count];
+
+    let (covariance, correlation) = pearson(a, b);
+    let variance_a = covariance_of(a, a);
+    let slope = if variance_a > 0.0 {
+        covariance / variance_a
+    } else {
+        f64::NAN
+    };
+    let intercept = mean(b) - slope * mean(a);
+
+    // Find the shift of b with the strongest correlation, positive or negative:
+    let max_lag = max_lag.min(count - 2) as isize;
+    let mut best = (0, correlation);
+    for lag in -max_lag..=max_lag {
+        let (a_part, b_part) = if lag >= 0 {
+            (&a[..count - lag as usize], &b[lag as usize..])
+        } else {
+            (&a[(-lag) as usize..], &b[..count - (-lag) as usize])
+        };
+        let (_, lag_correlation) = pearson(a_part, b_part);
+        if lag_correlation.abs() > best.1.abs() {
+            best = (lag, lag_correlation);
+        }
+    }
+
+    Some(CrossStatistics {
+        count,
+        covariance,
+        correlation,
+        slope,
+        intercept,
+        lag: best.0 as f64 * step,
+        lag_correlation: best.1,
+    })
+}
+
+fn mean(x: &[f64]) -> f64 {
+    x.iter().sum::<f64>() / x.len() as f64
+}
+
+fn covariance_of(x: &[f64], y: &[f64]) -> f64 {
+    let mean_x = mean(x);
+    let mean_y = mean(y);
+    let sum: f64 = x
+        .iter()
+        .zip(y)
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    sum / x.len() as f64
+}
+
+/// Calculate the covariance and correlation coefficient.
+fn pearson(x: &[f64], y: &[f64]) -> (f64, f64) {
+    let covariance = covariance_of(x, y);
+    let deviations = (covariance_of(x, x) * covariance_of(y, y)).sqrt();
+    let correlation = if deviations > 0.0 {
+        covariance / deviations
+    } else {
+        f64::NAN
+    };
+    (covariance, correlation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::cross_statistics;
+    use crate::time::TimeStamp;
+    use crate::tsdb::resample::Resampled;
+
+    fn resampled(f: impl Fn(f64) -> f64) -> Resampled {
+        let step = 0.01;
+        Resampled {
+            start: TimeStamp::new(0.0),
+            step,
+            values: (0..500).map(|i| f(i as f64 * step)).collect(),
+        }
+    }
+
+    #[test]
+    fn delayed_and_scaled() {
+        let a = resampled(|t| (t * 7.0).sin() + (t * 3.0).cos());
+        let b =
+            resampled(|t| 2.0 * ((t - 0.05) * 7.0).sin() + 2.0 * ((t - 0.05) * 3.0).cos() + 1.0);
+        let stats = cross_statistics(&a, &b, 100).unwrap();
+        assert_eq!(stats.count, 500);
+        assert!(stats.correlation > 0.9);
+        assert!((stats.lag - 0.05).abs() < 1.0e-9);
+        assert!(stats.lag_correlation > 0.999);
+
+        let stats = cross_statistics(&a, &a, 100).unwrap();
+        assert!((stats.correlation - 1.0).abs() < 1.0e-9);
+        assert!((stats.slope - 1.0).abs() < 1.0e-9);
+        assert!(stats.intercept.abs() < 1.0e-9);
+        assert_eq!(stats.lag, 0.0);
+    }
+
+    #[test]
+    fn delayed_and_inverted() {
+        let a = resampled(|t| (t * 7.0).sin() + (t * 3.0).cos());
+        let b = resampled(|t| -((t - 0.08) * 7.0).sin() - ((t - 0.08) * 3.0).cos());
+        let stats = cross_statistics(&a, &b, 100).unwrap();
+        assert!((stats.lag - 0.08).abs() < 1.0e-9);
+        assert!(stats.lag_correlation < -0.999);
+    }
+}
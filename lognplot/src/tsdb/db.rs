@@ -1,14 +1,42 @@
 //! Time series database which uses B+ trees to store tha data.
 
+use super::alarm::AlarmEngine;
+use super::annotation::AnnotationStore;
+use super::clock::{ClockModel, Retime, TimeBase};
 use super::handle::{make_handle, LockedTsDb};
+use super::ingest::{IngestStatistics, IngestTracker};
+use super::memory::{MemoryBudget, MemoryUsage};
+use super::metadata::is_valid_color;
+use super::namespace::{child_names, GlobPattern};
 use super::observations::{Observation, ProfileEvent, Sample, Text};
 use super::query::Query;
-use super::ChangeSubscriber;
+use super::trigger::TriggerEngine;
+use super::SignalMetadata;
+use super::{cross_statistics, CrossStatistics, Resampled};
+use super::{spectrum, Spectrum, SpectrumOptions};
+use super::{Alarm, AlarmRule, Annotation, ALARM_TRACK};
+use super::{ChangeSubscriber, DataSubscriber, NewObservations};
+use super::{CrossingEdge, Extremes, SearchDirection, ValuePredicate};
 use super::{QueryResult, QuickSummary};
 use super::{Summary, TsDbApi};
+use super::{TextHit, TextIndex, TextQuery};
 use super::{Track, TrackType};
+use super::{Trigger, TriggerHit, TriggerId, TRIGGER_TRACK_PREFIX};
 use crate::time::{TimeSpan, TimeStamp};
+use std::cell::RefCell;
+use std::cmp::Reverse;
 use std::collections::HashMap;
+use std::time::{Instant, SystemTime};
+
+/// Amount of points on which signals are compared by `cross_statistics`.
+const CROSS_STATISTICS_POINTS: usize = 1000;
+
+/// Upper limit on the amount of points used for a spectrum.
+const MAX_SPECTRUM_POINTS: usize = 1 << 20;
+
+/// Fraction of the memory budget freed in addition, when it is exceeded,
+/// so that traces are not reduced again with each new observation.
+const EVICTION_HEADROOM: f64 = 0.1;
 
 /// A time series database which can be used as a library.
 /// Note that this struct is not usable in multiple threads.
@@ -17,7 +45,31 @@ use std::collections::HashMap;
 pub struct TsDb {
     path: String,
     data: HashMap<String, Track>,
+    metadata: HashMap<String, SignalMetadata>,
+    annotations: AnnotationStore,
+    triggers: TriggerEngine,
+    alarms: AlarmEngine,
+    time_bases: HashMap<String, TimeBase>,
+
+    /// Glob patterns of signals, and the source whose time base they use.
+    clock_assignments: Vec<(GlobPattern, String)>,
+
+    /// Clock models of backups, frozen when their source restarted,
+    /// since the time base of the source continues with the new data.
+    frozen_clocks: HashMap<String, ClockModel>,
     change_subscribers: Vec<ChangeSubscriber>,
+    data_subscribers: Vec<DataSubscriber>,
+
+    /// The moment each trace was last queried, to find data nobody looks at.
+    viewed: RefCell<HashMap<String, Instant>>,
+    memory_budget: Option<MemoryBudget>,
+
+    /// Memory used per signal, and by all signals together, kept up to date
+    /// as data is added, so that the budget is checked without a full scan.
+    signal_memory: HashMap<String, usize>,
+    memory_total: usize,
+    text_indices: HashMap<String, TextIndex>,
+    ingest: HashMap<String, IngestTracker>,
 }
 
 impl std::fmt::Display for TsDb {
@@ -30,11 +82,26 @@ impl Default for TsDb {
     fn default() -> Self {
         let path = "x".to_string();
         let data = HashMap::new();
+        let metadata = HashMap::new();
         let change_subscribers = vec![];
         Self {
             path,
             data,
+            metadata,
+            annotations: AnnotationStore::default(),
+            triggers: TriggerEngine::default(),
+            alarms: AlarmEngine::default(),
+            time_bases: HashMap::new(),
+            clock_assignments: vec![],
+            frozen_clocks: HashMap::new(),
             change_subscribers,
+            data_subscribers: vec![],
+            viewed: RefCell::new(HashMap::new()),
+            memory_budget: None,
+            signal_memory: HashMap::new(),
+            memory_total: 0,
+            text_indices: HashMap::new(),
+            ingest: HashMap::new(),
         }
     }
 }
@@ -88,17 +155,206 @@ impl TsDb {
         // Copy of the old data:
         let no_data = self.data.insert(backup_new_name.clone(), trace);
         assert!(no_data.is_none()); // "Name must not be present already."
+
+        // The backup describes the same kind of signal:
+        if let Some(metadata) = self.metadata.get(name).cloned() {
+            self.metadata.insert(backup_new_name.clone(), metadata);
+        }
+
+        if let Some(index) = self.text_indices.remove(name) {
+            self.text_indices.insert(backup_new_name.clone(), index);
+        }
+
+        if let Some(usage) = self.signal_memory.remove(name) {
+            self.signal_memory.insert(backup_new_name.clone(), usage);
+        }
+
+        if let Some(model) = self.clock_model(name) {
+            self.frozen_clocks.insert(backup_new_name.clone(), model);
+        }
+
         self.notify_signal_added(&backup_new_name);
         self.notify_signal_changed(&backup_new_name);
     }
 
+    /// Find the source whose time base a signal uses.
+    ///
+    /// Trigger markers and alarms are logged in reference time already.
+    fn clock_source(&self, name: &str) -> Option<&str> {
+        if name == ALARM_TRACK || name.starts_with(TRIGGER_TRACK_PREFIX) {
+            return None;
+        }
+        self.clock_assignments
+            .iter()
+            .find(|(pattern, _)| pattern.matches(name))
+            .map(|(_, source)| source.as_str())
+    }
+
+    /// Get the model which converts the stored timestamps of a signal into
+    /// reference time, if the signal has a time base.
+    ///
+    /// Timestamps are stored as the source sent them, and the model is
+    /// applied when data is retrieved. A changing estimate thus never
+    /// rewrites stored data.
+    fn clock_model(&self, name: &str) -> Option<ClockModel> {
+        if let Some(model) = self.frozen_clocks.get(name) {
+            return Some(*model);
+        }
+        let source = self.clock_source(name)?;
+        self.time_bases.get(source).map(|t| t.model())
+    }
+
+    /// Get new observations of a signal in reference time, if the signal has a time base.
+    ///
+    /// Time bases which are estimated automatically are updated with the
+    /// arrival time of the last observation first.
+    fn in_reference_time<V: Clone>(
+        &mut self,
+        name: &str,
+        observations: &[Observation<V>],
+    ) -> Option<Vec<Observation<V>>> {
+        let source = self.clock_source(name)?.to_owned();
+        let time_base = self.time_bases.get_mut(&source)?;
+
+        if time_base.is_automatic() {
+            if let Some(last) = observations.last() {
+                time_base.observe(last.timestamp.amount, wall_clock_time());
+            }
+        }
+
+        let model = time_base.model();
+        let mut converted = observations.to_vec();
+        converted.retime(&model);
+        Some(converted)
+    }
+
+    /// Update the ingestion statistics of a signal with new observations.
+    fn track_ingest<V>(&mut self, name: &str, observations: &[Observation<V>]) {
+        let arrival = Instant::now();
+        let mut timestamps = observations.iter().map(|o| o.timestamp.amount);
+        if let Some(tracker) = self.ingest.get_mut(name) {
+            for timestamp in timestamps {
+                tracker.observe(timestamp, arrival);
+            }
+        } else if let Some(first) = timestamps.next() {
+            let mut tracker = IngestTracker::new(first, arrival);
+            for timestamp in timestamps {
+                tracker.observe(timestamp, arrival);
+            }
+            self.ingest.insert(name.to_owned(), tracker);
+        }
+    }
+
+    /// Update the memory total after data of a signal changed.
+    fn account_memory(&mut self, name: &str) {
+        let usage = self.memory_usage(name).map_or(0, |usage| usage.total());
+        let previous = self.signal_memory.insert(name.to_owned(), usage);
+        self.memory_total = self.memory_total + usage - previous.unwrap_or(0);
+    }
+
+    /// Shrink traces until the memory budget is met.
+    ///
+    /// Traces which were never viewed go first, then the least recently
+    /// viewed ones, the largest first. Within a trace, the oldest data is
+    /// reduced, by as much as needed to get some headroom below the budget.
+    /// The next trace is only touched when the budget is still exceeded.
+    fn enforce_memory_budget(&mut self) {
+        let budget = if let Some(budget) = self.memory_budget {
+            budget
+        } else {
+            return;
+        };
+        if self.memory_total <= budget.limit {
+            return;
+        }
+
+        let target = (budget.limit as f64 * (1.0 - EVICTION_HEADROOM)) as usize;
+        let victims: Vec<String> = {
+            let viewed = self.viewed.borrow();
+            let mut victims: Vec<(Option<Instant>, Reverse<usize>, String)> = self
+                .signal_memory
+                .iter()
+                .map(|(name, usage)| (viewed.get(name).cloned(), Reverse(*usage), name.clone()))
+                .collect();
+            victims.sort();
+            victims.into_iter().map(|(_, _, name)| name).collect()
+        };
+
+        for victim in victims {
+            if self.memory_total <= budget.limit {
+                break;
+            }
+
+            let track = self.data.get_mut(&victim).unwrap();
+            if track.reduce(budget.policy, self.memory_total - target) {
+                if let (Some(index), Some(summary)) =
+                    (self.text_indices.get_mut(&victim), track.summary(None))
+                {
+                    index.prune_before(&summary.timespan().start);
+                }
+                self.account_memory(&victim);
+                self.notify_signal_changed(&victim);
+            }
+        }
+    }
+
     fn new_trace(&mut self, name: &str, typ: TrackType) {
         let trace = Track::new_with_type(typ);
         self.data.insert(name.to_owned(), trace);
     }
 
+    /// Place a marker for each trigger hit, and notify listeners.
+    fn handle_trigger_hits(&mut self, hits: Vec<TriggerHit>) {
+        for hit in hits {
+            let marker_name = format!("{}/{}", TRIGGER_TRACK_PREFIX, hit.signal);
+            let marker = Observation::new(hit.timestamp.clone(), Text::new(hit.message.clone()));
+            self.add_text(&marker_name, marker);
+
+            for subscriber in &mut self.change_subscribers {
+                subscriber.notify_trigger(&hit);
+            }
+        }
+    }
+
+    /// Log alarms into the alarm track, and notify listeners.
+    fn handle_alarms(&mut self, alarms: Vec<Alarm>) {
+        for mut alarm in alarms {
+            // Alarms of different signals might arrive out of order,
+            // keep the alarm track in order:
+            if let Some(summary) = self.data.get(ALARM_TRACK).and_then(|t| t.quick_summary()) {
+                let last_timestamp = summary.last_timestamp();
+                if &alarm.timestamp < last_timestamp {
+                    alarm.timestamp = last_timestamp.clone();
+                }
+            }
+
+            warn!("Alarm: {}", alarm.message);
+            let entry = Observation::new(alarm.timestamp.clone(), Text::new(alarm.message.clone()));
+            self.add_text(ALARM_TRACK, entry);
+
+            for subscriber in &mut self.change_subscribers {
+                subscriber.notify_alarm(&alarm);
+            }
+        }
+    }
+
     // Events
 
+    /// Test if any data subscriber wants the observations of the given signal.
+    fn is_subscribed(&self, name: &str) -> bool {
+        self.data_subscribers.iter().any(|s| s.matches(name))
+    }
+
+    /// Hand new observations to the interested data subscribers.
+    fn publish_data(&mut self, name: &str, observations: NewObservations) {
+        for subscriber in self.data_subscribers.iter_mut() {
+            if subscriber.matches(name) {
+                subscriber.send(name, observations.clone());
+            }
+        }
+        self.data_subscribers.retain(|s| s.is_connected());
+    }
+
     /// Notify listeners of the newly arrived data.
     fn notify_signal_changed(&mut self, name: &str) {
         for subscriber in &mut self.change_subscribers {
@@ -112,8 +368,16 @@ impl TsDb {
         }
     }
 
-    fn delete_event(&mut self, _name: &str) {
-        unimplemented!("TODO");
+    fn notify_signal_deleted(&mut self, name: &str) {
+        for subscriber in &mut self.change_subscribers {
+            subscriber.notify_signal_deleted(name);
+        }
+    }
+
+    fn notify_annotations_changed(&mut self) {
+        for subscriber in &mut self.change_subscribers {
+            subscriber.notify_annotations_changed();
+        }
     }
 
     fn notify_delete_all(&mut self) {
@@ -128,35 +392,132 @@ impl TsDbApi for TsDb {
         self.data.keys().cloned().collect()
     }
 
+    /// Get the direct children of a namespace prefix.
+    ///
+    /// For signals `robot/arm/joint1` and `robot/base`, the children
+    /// of `robot` are `robot/arm` and `robot/base`.
+    fn get_child_names(&self, prefix: &str) -> Vec<String> {
+        child_names(prefix, self.data.keys())
+    }
+
+    /// Get the names of all signals matching the given glob pattern.
+    fn match_signal_names(&self, pattern: &str) -> Vec<String> {
+        let pattern = GlobPattern::new(pattern);
+        let mut names: Vec<String> = self
+            .data
+            .keys()
+            .filter(|name| pattern.matches(name))
+            .cloned()
+            .collect();
+        names.sort();
+        names
+    }
+
     /// Add a single observation to the database.
     fn add_value(&mut self, name: &str, observation: Observation<Sample>) {
-        let trace = self.get_or_create_trace(name, TrackType::Value, &observation.timestamp);
+        self.get_or_create_trace(name, TrackType::Value, &observation.timestamp);
+        let converted = self.in_reference_time(name, std::slice::from_ref(&observation));
+        let observations = converted
+            .as_deref()
+            .unwrap_or_else(|| std::slice::from_ref(&observation));
+        self.track_ingest(name, observations);
+        let hits = self.triggers.process(name, observations);
+        let alarms = self.alarms.process(name, observations, Instant::now());
+        let published = if self.is_subscribed(name) {
+            Some(NewObservations::Values(observations.to_vec()))
+        } else {
+            None
+        };
+        let trace = self.data.get_mut(name).unwrap();
         trace.add_value_observation(observation);
         self.notify_signal_changed(name);
+        if let Some(observations) = published {
+            self.publish_data(name, observations);
+        }
+        self.handle_trigger_hits(hits);
+        self.handle_alarms(alarms);
+        self.account_memory(name);
+        self.enforce_memory_budget();
     }
 
     /// Add a batch of values
     fn add_values(&mut self, name: &str, samples: Vec<Observation<Sample>>) {
         if !samples.is_empty() {
             let first_observation = samples.first().expect("Must have an observation here.");
-            let trace =
-                self.get_or_create_trace(name, TrackType::Value, &first_observation.timestamp);
+            self.get_or_create_trace(name, TrackType::Value, &first_observation.timestamp);
+            let converted = self.in_reference_time(name, &samples);
+            let observations = converted.as_deref().unwrap_or(&samples);
+            self.track_ingest(name, observations);
+            let hits = self.triggers.process(name, observations);
+            let alarms = self.alarms.process(name, observations, Instant::now());
+            let published = if self.is_subscribed(name) {
+                Some(NewObservations::Values(observations.to_vec()))
+            } else {
+                None
+            };
+            let trace = self.data.get_mut(name).unwrap();
             trace.add_value_observations(samples);
             self.notify_signal_changed(name);
+            if let Some(observations) = published {
+                self.publish_data(name, observations);
+            }
+            self.handle_trigger_hits(hits);
+            self.handle_alarms(alarms);
+            self.account_memory(name);
+            self.enforce_memory_budget();
         }
     }
 
     /// Add a text record.
     fn add_text(&mut self, name: &str, observation: Observation<Text>) {
-        let track = self.get_or_create_trace(name, TrackType::Text, &observation.timestamp);
+        self.get_or_create_trace(name, TrackType::Text, &observation.timestamp);
+        let converted = self.in_reference_time(name, std::slice::from_ref(&observation));
+        let observations = converted
+            .as_deref()
+            .unwrap_or_else(|| std::slice::from_ref(&observation));
+        self.track_ingest(name, observations);
+        let published = if self.is_subscribed(name) {
+            Some(NewObservations::Texts(observations.to_vec()))
+        } else {
+            None
+        };
+        if let Some(index) = self.text_indices.get_mut(name) {
+            index.add(&observation);
+        } else {
+            let mut index = TextIndex::default();
+            index.add(&observation);
+            self.text_indices.insert(name.to_owned(), index);
+        }
+        let track = self.data.get_mut(name).unwrap();
         track.add_text_observation(observation);
         self.notify_signal_changed(name);
+        if let Some(observations) = published {
+            self.publish_data(name, observations);
+        }
+        self.account_memory(name);
+        self.enforce_memory_budget();
     }
 
     fn add_profile_event(&mut self, name: &str, observation: Observation<ProfileEvent>) {
-        let track = self.get_or_create_trace(name, TrackType::Profile, &observation.timestamp);
+        self.get_or_create_trace(name, TrackType::Profile, &observation.timestamp);
+        let converted = self.in_reference_time(name, std::slice::from_ref(&observation));
+        let observations = converted
+            .as_deref()
+            .unwrap_or_else(|| std::slice::from_ref(&observation));
+        self.track_ingest(name, observations);
+        let published = if self.is_subscribed(name) {
+            Some(NewObservations::Profile(observations.to_vec()))
+        } else {
+            None
+        };
+        let track = self.data.get_mut(name).unwrap();
         track.add_profile_observation(observation);
         self.notify_signal_changed(name);
+        if let Some(observations) = published {
+            self.publish_data(name, observations);
+        }
+        self.account_memory(name);
+        self.enforce_memory_budget();
         // TODO
         // let track = self.get_or_create_trace(name, TrackType::Text, &observation.timestamp);
         // self.db.lock().unwrap().add_profile_event(name, event);
@@ -166,36 +527,322 @@ impl TsDbApi for TsDb {
     fn delete_all(&mut self) {
         self.data.clear();
         self.data.shrink_to_fit();
+        self.metadata.clear();
+        self.annotations.clear();
+        self.viewed.borrow_mut().clear();
+        self.text_indices.clear();
+        self.signal_memory.clear();
+        self.memory_total = 0;
+        self.ingest.clear();
+        self.frozen_clocks.clear();
         self.notify_delete_all();
     }
 
     /// Delete a single trace from the database.
     fn delete(&mut self, name: &str) {
-        self.data.remove(name);
-        self.delete_event(name);
+        if self.data.remove(name).is_some() {
+            self.metadata.remove(name);
+            self.viewed.borrow_mut().remove(name);
+            self.text_indices.remove(name);
+            if let Some(usage) = self.signal_memory.remove(name) {
+                self.memory_total -= usage;
+            }
+            self.ingest.remove(name);
+            self.frozen_clocks.remove(name);
+            self.notify_signal_deleted(name);
+        }
+    }
+
+    /// Delete all traces matching the given glob pattern.
+    fn delete_matching(&mut self, pattern: &str) {
+        for name in self.match_signal_names(pattern) {
+            self.delete(&name);
+        }
     }
 
     /// Query the given trace for data.
-    fn query(&self, name: &str, query: Query) -> Option<QueryResult> {
+    fn query(&self, name: &str, mut query: Query) -> Option<QueryResult> {
         if let Some(trace) = self.data.get(name) {
-            Some(trace.query(query))
+            let mut viewed = self.viewed.borrow_mut();
+            if let Some(moment) = viewed.get_mut(name) {
+                *moment = Instant::now();
+            } else {
+                viewed.insert(name.to_owned(), Instant::now());
+            }
+            let model = self.clock_model(name);
+            query.interval = source_timespan(model, &query.interval);
+            Some(retimed(model, trace.query(query)))
         } else {
             None
         }
     }
 
     fn quick_summary(&self, name: &str) -> Option<QuickSummary> {
-        self.data.get(name)?.quick_summary()
+        let summary = self.data.get(name)?.quick_summary();
+        retimed(self.clock_model(name), summary)
     }
 
     /// Get a summary for a certain timerange (or all time) the given trace.
     fn summary(&self, name: &str, timespan: Option<&TimeSpan>) -> Option<Summary> {
-        self.data.get(name)?.summary(timespan)
+        let trace = self.data.get(name)?;
+        let model = self.clock_model(name);
+        let timespan = timespan.map(|timespan| source_timespan(model, timespan));
+        retimed(model, trace.summary(timespan.as_ref()))
     }
 
     // Download raw samples.
     fn get_raw_samples(&self, name: &str) -> Option<Vec<Observation<Sample>>> {
-        self.data.get(name).map(|t| t.to_vec())
+        let samples = self.data.get(name)?.to_vec();
+        Some(retimed(self.clock_model(name), samples))
+    }
+
+    /// Download the raw samples within a time span.
+    fn get_raw_range(&self, name: &str, timespan: &TimeSpan) -> Option<Vec<Observation<Sample>>> {
+        if let Some(Track::Value(trace)) = self.data.get(name) {
+            let model = self.clock_model(name);
+            let timespan = source_timespan(model, timespan);
+            let samples = trace.find_all(&timespan, &|_| true, &|_| true, usize::MAX);
+            Some(retimed(model, samples))
+        } else {
+            None
+        }
+    }
+
+    /// Find the first value satisfying a predicate.
+    fn find_value(
+        &self,
+        name: &str,
+        predicate: ValuePredicate,
+        timespan: &TimeSpan,
+        direction: SearchDirection,
+    ) -> Option<Observation<Sample>> {
+        if let Some(Track::Value(trace)) = self.data.get(name) {
+            let model = self.clock_model(name);
+            let timespan = source_timespan(model, timespan);
+            retimed(model, trace.find_value(predicate, &timespan, direction))
+        } else {
+            None
+        }
+    }
+
+    /// Find the next or previous crossing of a level.
+    fn find_crossing(
+        &self,
+        name: &str,
+        level: f64,
+        edge: CrossingEdge,
+        timespan: &TimeSpan,
+        direction: SearchDirection,
+    ) -> Option<Observation<Sample>> {
+        if let Some(Track::Value(trace)) = self.data.get(name) {
+            let model = self.clock_model(name);
+            let timespan = source_timespan(model, timespan);
+            retimed(
+                model,
+                trace.find_crossing(level, edge, &timespan, direction),
+            )
+        } else {
+            None
+        }
+    }
+
+    /// Locate the minimum and maximum value in a timespan.
+    fn find_extremes(&self, name: &str, timespan: &TimeSpan) -> Option<Extremes> {
+        if let Some(Track::Value(trace)) = self.data.get(name) {
+            let model = self.clock_model(name);
+            retimed(
+                model,
+                trace.find_extremes(&source_timespan(model, timespan)),
+            )
+        } else {
+            None
+        }
+    }
+
+    /// Search the texts of all text traces matching a glob pattern.
+    ///
+    /// Returns at most `limit` hits, sorted by time.
+    fn search_text(
+        &self,
+        pattern: &str,
+        query: &TextQuery,
+        timespan: &TimeSpan,
+        limit: usize,
+    ) -> Vec<TextHit> {
+        let mut hits = vec![];
+        for name in self.match_signal_names(pattern) {
+            if let Some(Track::Text(trace)) = self.data.get(&name) {
+                let index = self.text_indices.get(&name);
+                let model = self.clock_model(&name);
+                let timespan = source_timespan(model, timespan);
+                for observation in trace.search(index, query, &timespan, limit) {
+                    hits.push(TextHit {
+                        name: name.clone(),
+                        timestamp: retimed(model, observation.timestamp),
+                        text: observation.value.text,
+                    });
+                }
+            }
+        }
+        hits.sort_by(|a, b| a.timestamp.amount.total_cmp(&b.timestamp.amount));
+        hits.truncate(limit);
+        hits
+    }
+
+    fn ingest_statistics(&self, name: &str) -> Option<IngestStatistics> {
+        self.ingest
+            .get(name)
+            .map(|tracker| tracker.statistics(Instant::now()))
+    }
+
+    /// Resample a value trace onto `count` evenly spaced moments.
+    fn resample(&self, name: &str, timespan: &TimeSpan, count: usize) -> Option<Resampled> {
+        if let Some(Track::Value(trace)) = self.data.get(name) {
+            let model = self.clock_model(name);
+            retimed(
+                model,
+                trace.resample(&source_timespan(model, timespan), count),
+            )
+        } else {
+            None
+        }
+    }
+
+    /// Compare two value traces within a timespan.
+    ///
+    /// Lags up to a quarter of the timespan are considered.
+    fn cross_statistics(&self, a: &str, b: &str, timespan: &TimeSpan) -> Option<CrossStatistics> {
+        let a = self.resample(a, timespan, CROSS_STATISTICS_POINTS)?;
+        let b = self.resample(b, timespan, CROSS_STATISTICS_POINTS)?;
+        cross_statistics(&a, &b, CROSS_STATISTICS_POINTS / 4)
+    }
+
+    /// Calculate the amplitude spectrum of a value trace within a timespan.
+    ///
+    /// The trace is resampled at about the average rate of its raw samples,
+    /// over the part of the timespan which holds samples. Padding beyond
+    /// the ends of the trace would add a flat line, and lower the rate.
+    fn spectrum(
+        &self,
+        name: &str,
+        timespan: &TimeSpan,
+        options: &SpectrumOptions,
+    ) -> Option<Spectrum> {
+        let summary = self.summary(name, Some(timespan))?;
+        let count = summary.count().min(MAX_SPECTRUM_POINTS);
+        let resampled = self.resample(name, summary.timespan(), count)?;
+        spectrum(&resampled, options)
+    }
+
+    /// Arm a trigger on a signal.
+    ///
+    /// The trigger is evaluated on all values added from now on.
+    fn arm_trigger(&mut self, trigger: Trigger) -> TriggerId {
+        self.triggers.arm(trigger)
+    }
+
+    fn disarm_trigger(&mut self, id: TriggerId) {
+        self.triggers.disarm(id);
+    }
+
+    fn get_trigger_hits(&self, id: TriggerId) -> Vec<TriggerHit> {
+        self.triggers.hits(id)
+    }
+
+    /// Add an annotation to the time line.
+    fn add_annotation(&mut self, mut annotation: Annotation) {
+        annotation.color = annotation.color.filter(|color| is_valid_color(color));
+        self.annotations.add(annotation);
+        self.notify_annotations_changed();
+    }
+
+    /// Get the annotations in the given timespan, or all annotations.
+    fn get_annotations(&self, timespan: Option<&TimeSpan>) -> Vec<Annotation> {
+        if let Some(timespan) = timespan {
+            self.annotations.query(timespan)
+        } else {
+            self.annotations.all()
+        }
+    }
+
+    /// Use a fixed clock model for a source.
+    fn set_clock_model(&mut self, source: &str, model: ClockModel) {
+        self.time_bases
+            .insert(source.to_owned(), TimeBase::manual(model));
+    }
+
+    /// Estimate the clock model of a source from the arrival times of its samples.
+    fn estimate_clock(&mut self, source: &str) {
+        self.time_bases
+            .insert(source.to_owned(), TimeBase::automatic());
+    }
+
+    /// Let signals matching a glob pattern use the time base of a source.
+    ///
+    /// Timestamps are stored in source time, and converted into reference
+    /// time when data is retrieved.
+    fn assign_clock(&mut self, pattern: &str, source: &str) {
+        self.clock_assignments
+            .push((GlobPattern::new(pattern), source.to_owned()));
+    }
+
+    fn get_clock_model(&self, source: &str) -> Option<ClockModel> {
+        self.time_bases.get(source).map(|t| t.model())
+    }
+
+    fn memory_usage(&self, name: &str) -> Option<MemoryUsage> {
+        let mut usage = self.data.get(name)?.memory_usage();
+        usage.index = self
+            .text_indices
+            .get(name)
+            .map_or(0, |index| index.memory_usage());
+        Some(usage)
+    }
+
+    fn total_memory_usage(&self) -> MemoryUsage {
+        let mut usage = MemoryUsage::default();
+        for name in self.data.keys() {
+            usage += self.memory_usage(name).unwrap_or_default();
+        }
+        usage
+    }
+
+    fn set_memory_budget(&mut self, budget: Option<MemoryBudget>) {
+        self.memory_budget = budget;
+        self.enforce_memory_budget();
+    }
+
+    fn add_alarm_rule(&mut self, rule: AlarmRule) {
+        self.alarms.add_rule(rule, Instant::now());
+    }
+
+    fn clear_alarm_rules(&mut self) {
+        self.alarms.clear_rules();
+    }
+
+    fn get_alarm_rules(&self) -> Vec<AlarmRule> {
+        self.alarms.rules()
+    }
+
+    /// Check for signals which stopped receiving samples.
+    fn check_alarms(&mut self) {
+        let alarms = self
+            .alarms
+            .check_timeouts(Instant::now(), &TimeStamp::new(wall_clock_time()));
+        self.handle_alarms(alarms);
+    }
+
+    /// Attach meta data to a signal.
+    ///
+    /// The meta data can be set before any data of the signal arrived.
+    fn set_metadata(&mut self, name: &str, mut metadata: SignalMetadata) {
+        metadata.color = metadata.color.filter(|color| is_valid_color(color));
+        self.metadata.insert(name.to_owned(), metadata);
+        self.notify_signal_changed(name);
+    }
+
+    fn get_metadata(&self, name: &str) -> Option<SignalMetadata> {
+        self.metadata.get(name).cloned()
     }
 
     /// Register a subscriber which will be notified of any change.
@@ -214,6 +861,11 @@ impl TsDbApi for TsDb {
         self.change_subscribers.push(subscriber);
     }
 
+    /// Register a subscriber which receives the new observations of matching signals.
+    fn register_data_subscriber(&mut self, subscriber: DataSubscriber) {
+        self.data_subscribers.push(subscriber);
+    }
+
     // Check if we have pending events, and emit them to queues.
     fn poll_events(&mut self) {
         for subscriber in &mut self.change_subscribers {
@@ -221,3 +873,28 @@ impl TsDbApi for TsDb {
         }
     }
 }
+
+/// The current time in seconds since the unix epoch.
+fn wall_clock_time() -> f64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or_default()
+}
+
+/// Convert data of a signal into reference time, if it has a clock model.
+fn retimed<T: Retime>(model: Option<ClockModel>, mut data: T) -> T {
+    if let Some(model) = model {
+        data.retime(&model);
+    }
+    data
+}
+
+/// Convert a timespan in reference time into the source time of a signal.
+fn source_timespan(model: Option<ClockModel>, timespan: &TimeSpan) -> TimeSpan {
+    if let Some(model) = model {
+        model.invert_timespan(timespan)
+    } else {
+        timespan.clone()
+    }
+}
@@ -1,8 +1,14 @@
 //! Thread usable handle. Wrapper around a database.
 
 use super::observations::{Observation, ProfileEvent, Sample, Text};
-use super::{ChangeSubscriber, DataChangeEvent};
+use super::{AlarmRule, Annotation, ChangeSubscriber, ClockModel, DataChangeEvent, SignalMetadata};
+use super::{CrossStatistics, Resampled, Spectrum, SpectrumOptions};
+use super::{CrossingEdge, Extremes, SearchDirection, ValuePredicate};
+use super::{DataBatch, DataSubscriber};
+use super::{IngestStatistics, MemoryBudget, MemoryUsage};
 use super::{Query, QueryResult, QuickSummary, Summary, TsDb, TsDbApi};
+use super::{TextHit, TextQuery};
+use super::{Trigger, TriggerHit, TriggerId};
 // use super::VoidDb;
 use crate::time::TimeSpan;
 use futures::channel::mpsc;
@@ -35,6 +41,16 @@ where
         self.db.lock().unwrap().get_signal_names()
     }
 
+    /// Get the direct children of a namespace prefix.
+    pub fn get_child_names(&self, prefix: &str) -> Vec<String> {
+        self.db.lock().unwrap().get_child_names(prefix)
+    }
+
+    /// Get all signal names matching a glob pattern, such as `robot/*/torque`.
+    pub fn match_signal_names(&self, pattern: &str) -> Vec<String> {
+        self.db.lock().unwrap().match_signal_names(pattern)
+    }
+
     /// Add a single observation.
     pub fn add_value(&self, name: &str, sample: Observation<Sample>) {
         self.db.lock().unwrap().add_value(name, sample);
@@ -62,6 +78,169 @@ where
         self.db.lock().unwrap().get_raw_samples(name)
     }
 
+    /// Get the raw samples of a signal within a time span.
+    pub fn get_raw_range(
+        &self,
+        name: &str,
+        timespan: &TimeSpan,
+    ) -> Option<Vec<Observation<Sample>>> {
+        self.db.lock().unwrap().get_raw_range(name, timespan)
+    }
+
+    /// Find the first value satisfying a predicate in the given direction.
+    pub fn find_value(
+        &self,
+        name: &str,
+        predicate: ValuePredicate,
+        timespan: &TimeSpan,
+        direction: SearchDirection,
+    ) -> Option<Observation<Sample>> {
+        self.db
+            .lock()
+            .unwrap()
+            .find_value(name, predicate, timespan, direction)
+    }
+
+    /// Find the next or previous crossing of a level.
+    pub fn find_crossing(
+        &self,
+        name: &str,
+        level: f64,
+        edge: CrossingEdge,
+        timespan: &TimeSpan,
+        direction: SearchDirection,
+    ) -> Option<Observation<Sample>> {
+        self.db
+            .lock()
+            .unwrap()
+            .find_crossing(name, level, edge, timespan, direction)
+    }
+
+    /// Locate the minimum and maximum value of a signal in a timespan.
+    pub fn find_extremes(&self, name: &str, timespan: &TimeSpan) -> Option<Extremes> {
+        self.db.lock().unwrap().find_extremes(name, timespan)
+    }
+
+    /// Search texts in all text signals matching a glob pattern.
+    pub fn search_text(
+        &self,
+        pattern: &str,
+        query: &TextQuery,
+        timespan: &TimeSpan,
+        limit: usize,
+    ) -> Vec<TextHit> {
+        self.db
+            .lock()
+            .unwrap()
+            .search_text(pattern, query, timespan, limit)
+    }
+
+    /// Rate, jitter, gaps and age of the observations of a signal.
+    pub fn ingest_statistics(&self, name: &str) -> Option<IngestStatistics> {
+        self.db.lock().unwrap().ingest_statistics(name)
+    }
+
+    pub fn resample(&self, name: &str, timespan: &TimeSpan, count: usize) -> Option<Resampled> {
+        self.db.lock().unwrap().resample(name, timespan, count)
+    }
+
+    /// Compute correlation, regression and lag between two signals.
+    pub fn cross_statistics(
+        &self,
+        a: &str,
+        b: &str,
+        timespan: &TimeSpan,
+    ) -> Option<CrossStatistics> {
+        self.db.lock().unwrap().cross_statistics(a, b, timespan)
+    }
+
+    /// Calculate the amplitude spectrum of a signal, see `TsDbApi::spectrum`.
+    pub fn spectrum(
+        &self,
+        name: &str,
+        timespan: &TimeSpan,
+        options: &SpectrumOptions,
+    ) -> Option<Spectrum> {
+        self.db.lock().unwrap().spectrum(name, timespan, options)
+    }
+
+    /// Arm a trigger, see `TsDbApi::arm_trigger`.
+    pub fn arm_trigger(&self, trigger: Trigger) -> TriggerId {
+        self.db.lock().unwrap().arm_trigger(trigger)
+    }
+
+    pub fn disarm_trigger(&self, id: TriggerId) {
+        self.db.lock().unwrap().disarm_trigger(id)
+    }
+
+    /// Get the most recent hits of a trigger.
+    pub fn get_trigger_hits(&self, id: TriggerId) -> Vec<TriggerHit> {
+        self.db.lock().unwrap().get_trigger_hits(id)
+    }
+
+    /// Add an annotation to the time line.
+    pub fn add_annotation(&self, annotation: Annotation) {
+        self.db.lock().unwrap().add_annotation(annotation)
+    }
+
+    /// Get annotations overlapping the given timespan, or all of them.
+    pub fn get_annotations(&self, timespan: Option<&TimeSpan>) -> Vec<Annotation> {
+        self.db.lock().unwrap().get_annotations(timespan)
+    }
+
+    /// Use a fixed clock model for a source.
+    pub fn set_clock_model(&self, source: &str, model: ClockModel) {
+        self.db.lock().unwrap().set_clock_model(source, model);
+    }
+
+    /// Estimate the clock model of a source automatically.
+    pub fn estimate_clock(&self, source: &str) {
+        self.db.lock().unwrap().estimate_clock(source);
+    }
+
+    /// Let signals matching a glob pattern use the time base of a source.
+    pub fn assign_clock(&self, pattern: &str, source: &str) {
+        self.db.lock().unwrap().assign_clock(pattern, source);
+    }
+
+    pub fn get_clock_model(&self, source: &str) -> Option<ClockModel> {
+        self.db.lock().unwrap().get_clock_model(source)
+    }
+
+    /// Estimated memory used by a single signal.
+    pub fn memory_usage(&self, name: &str) -> Option<MemoryUsage> {
+        self.db.lock().unwrap().memory_usage(name)
+    }
+
+    /// Estimated memory used by all signals.
+    pub fn total_memory_usage(&self) -> MemoryUsage {
+        self.db.lock().unwrap().total_memory_usage()
+    }
+
+    /// Limit the memory used by all signals, or remove the limit with `None`.
+    pub fn set_memory_budget(&self, budget: Option<MemoryBudget>) {
+        self.db.lock().unwrap().set_memory_budget(budget);
+    }
+
+    pub fn add_alarm_rule(&self, rule: AlarmRule) {
+        self.db.lock().unwrap().add_alarm_rule(rule)
+    }
+
+    pub fn clear_alarm_rules(&self) {
+        self.db.lock().unwrap().clear_alarm_rules()
+    }
+
+    pub fn get_alarm_rules(&self) -> Vec<AlarmRule> {
+        self.db.lock().unwrap().get_alarm_rules()
+    }
+
+    /// Check for alarms on signals which stopped receiving data.
+    ///
+    /// Call this on a regular interval.
+    pub fn check_alarms(&self) {
+        self.db.lock().unwrap().check_alarms()
+    }
+
     /// Grab a quick data summary.
     ///
     /// This summary includes:
@@ -81,11 +260,31 @@ where
         self.db.lock().unwrap().summary(name, timespan)
     }
 
+    /// Attach meta data, such as a unit, to a signal.
+    pub fn set_metadata(&self, name: &str, metadata: SignalMetadata) {
+        self.db.lock().unwrap().set_metadata(name, metadata);
+    }
+
+    /// Retrieve the meta data of a signal, if any was set.
+    pub fn get_metadata(&self, name: &str) -> Option<SignalMetadata> {
+        self.db.lock().unwrap().get_metadata(name)
+    }
+
     /// Delete all data from the database.
     pub fn delete_all(&self) {
         self.db.lock().unwrap().delete_all();
     }
 
+    /// Delete a single signal.
+    pub fn delete(&self, name: &str) {
+        self.db.lock().unwrap().delete(name);
+    }
+
+    /// Delete all signals matching a glob pattern.
+    pub fn delete_matching(&self, pattern: &str) {
+        self.db.lock().unwrap().delete_matching(pattern);
+    }
+
     /// Register database change handler.
     pub fn new_notify_queue(&self) -> mpsc::Receiver<DataChangeEvent> {
         let (sender, receiver) = mpsc::channel::<DataChangeEvent>(0);
@@ -98,6 +297,21 @@ where
         self.db.lock().unwrap().register_notifier(subscriber);
     }
 
+    /// Subscribe on the new observations of all signals matching a glob pattern.
+    ///
+    /// At most `capacity` batches are buffered. When the receiver lags behind,
+    /// batches are dropped, and the amount of dropped batches is reported
+    /// in the next batch.
+    pub fn new_data_queue(&self, pattern: &str, capacity: usize) -> mpsc::Receiver<DataBatch> {
+        let (subscriber, receiver) = DataSubscriber::new(pattern, capacity);
+        self.register_data_subscriber(subscriber);
+        receiver
+    }
+
+    pub fn register_data_subscriber(&self, subscriber: DataSubscriber) {
+        self.db.lock().unwrap().register_data_subscriber(subscriber);
+    }
+
     pub fn poll_events(&self) {
         self.db.lock().unwrap().poll_events();
     }
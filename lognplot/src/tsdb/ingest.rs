@@ -0,0 +1,140 @@
+//! Ingestion statistics per signal.
+//!
+//! The intervals between the timestamps of new observations are
+//! tracked, to show how fast a source publishes, how regular it is,
+//! and whether it dropped out for a while.
+
+use std::time::{Duration, Instant};
+
+/// Weight of a new interval in the running averages.
+const SMOOTHING: f64 = 0.05;
+
+/// How a signal receives its observations.
+#[derive(Debug, Clone)]
+pub struct IngestStatistics {
+    /// Amount of observations since the signal started.
+    pub count: usize,
+
+    /// Rate over the most recent observations, in Hz.
+    pub current_rate: f64,
+
+    /// Rate since the first observation, in Hz.
+    pub mean_rate: f64,
+
+    /// Standard deviation of the recent intervals between observations, in seconds.
+    pub jitter: f64,
+
+    /// Largest interval between two observations, in seconds.
+    pub max_gap: f64,
+
+    /// Time since the last observation arrived.
+    pub age: Duration,
+}
+
+/// Keeps running statistics of the observations of a single signal.
+#[derive(Debug, Clone)]
+pub struct IngestTracker {
+    count: usize,
+    first: f64,
+    last: f64,
+
+    /// Running average and variance of the intervals.
+    interval: f64,
+    variance: f64,
+
+    max_gap: f64,
+    last_arrival: Instant,
+}
+
+impl IngestTracker {
+    pub fn new(timestamp: f64, arrival: Instant) -> Self {
+        IngestTracker {
+            count: 1,
+            first: timestamp,
+            last: timestamp,
+            interval: 0.0,
+            variance: 0.0,
+            max_gap: 0.0,
+            last_arrival: arrival,
+        }
+    }
+
+    /// Register an observation with the given timestamp.
+    ///
+    /// A timestamp before the previous one means the signal restarted,
+    /// which resets the statistics.
+    pub fn observe(&mut self, timestamp: f64, arrival: Instant) {
+        let interval = timestamp - self.last;
+        if interval < 0.0 {
+            *self = IngestTracker::new(timestamp, arrival);
+            return;
+        }
+
+        self.count += 1;
+        self.last = timestamp;
+        self.last_arrival = arrival;
+        self.max_gap = self.max_gap.max(interval);
+
+        if self.count == 2 {
+            self.interval = interval;
+        } else {
+            let error = interval - self.interval;
+            self.interval += SMOOTHING * error;
+            self.variance = (1.0 - SMOOTHING) * (self.variance + SMOOTHING * error * error);
+        }
+    }
+
+    pub fn statistics(&self, now: Instant) -> IngestStatistics {
+        let current_rate = if self.interval > 0.0 {
+            1.0 / self.interval
+        } else {
+            0.0
+        };
+        let duration = self.last - self.first;
+        let mean_rate = if duration > 0.0 {
+            (self.count - 1) as f64 / duration
+        } else {
+            0.0
+        };
+
+        IngestStatistics {
+            count: self.count,
+            current_rate,
+            mean_rate,
+            jitter: self.variance.sqrt(),
+            max_gap: self.max_gap,
+            age: now.saturating_duration_since(self.last_arrival),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IngestTracker;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn rate_and_gap() {
+        let start = Instant::now();
+        let mut tracker = IngestTracker::new(0.0, start);
+
+        // 98 Hz, with a drop out of 3 seconds halfway:
+        let mut t = 0.0;
+        for i in 1..1000 {
+            t += if i == 500 { 3.0 } else { 1.0 / 98.0 };
+            tracker.observe(t, start);
+        }
+
+        let statistics = tracker.statistics(start + Duration::from_secs(2));
+        assert_eq!(statistics.count, 1000);
+        assert!((statistics.current_rate - 98.0).abs() < 0.1);
+        assert!(statistics.mean_rate < 80.0);
+        assert!(statistics.jitter < 1.0e-3);
+        assert!((statistics.max_gap - 3.0).abs() < 1.0e-9);
+        assert_eq!(statistics.age, Duration::from_secs(2));
+
+        // A restart of the signal resets the statistics:
+        tracker.observe(0.0, start);
+        assert_eq!(tracker.statistics(start).count, 1);
+    }
+}
@@ -0,0 +1,159 @@
+//! Memory accounting of the database.
+//!
+//! Each trace keeps an estimate of the memory it occupies. When a
+//! memory budget is set, the database reduces the least recently
+//! viewed traces until the total fits in the budget again.
+
+use super::observations::{Observation, ProfileEvent, Sample, Text};
+use crate::time::TimeStamp;
+use std::ops::{Add, AddAssign};
+
+/// Memory allocated on the heap by a value, besides its own size.
+pub trait HeapSize {
+    fn heap_size(&self) -> usize;
+}
+
+impl HeapSize for Sample {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+impl HeapSize for Text {
+    fn heap_size(&self) -> usize {
+        self.text.capacity()
+    }
+}
+
+impl HeapSize for ProfileEvent {
+    fn heap_size(&self) -> usize {
+        match self {
+            ProfileEvent::FunctionEnter { name } => name.capacity(),
+            ProfileEvent::FunctionExit => 0,
+        }
+    }
+}
+
+/// Estimated memory usage in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MemoryUsage {
+    /// Memory of the raw observations.
+    pub observations: usize,
+
+    /// Memory of the aggregations in the tree nodes.
+    pub aggregations: usize,
+
+    /// Memory of texts and function names.
+    pub strings: usize,
+
+    /// Memory of the search index of a text track.
+    pub index: usize,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.observations + self.aggregations + self.strings + self.index
+    }
+}
+
+impl Add for MemoryUsage {
+    type Output = MemoryUsage;
+
+    fn add(self, other: MemoryUsage) -> MemoryUsage {
+        MemoryUsage {
+            observations: self.observations + other.observations,
+            aggregations: self.aggregations + other.aggregations,
+            strings: self.strings + other.strings,
+            index: self.index + other.index,
+        }
+    }
+}
+
+impl AddAssign for MemoryUsage {
+    fn add_assign(&mut self, other: MemoryUsage) {
+        *self = *self + other;
+    }
+}
+
+/// What to do with a trace which must shrink.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EvictionPolicy {
+    /// Remove the oldest observations.
+    DropOldest,
+
+    /// Average pairs of the oldest observations.
+    ///
+    /// Text and profile traces cannot be averaged, their oldest
+    /// observations are dropped instead.
+    Downsample,
+}
+
+/// Upper limit on the memory used by all traces.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryBudget {
+    /// Limit in bytes.
+    pub limit: usize,
+
+    pub policy: EvictionPolicy,
+}
+
+impl MemoryBudget {
+    pub fn new(limit: usize, policy: EvictionPolicy) -> Self {
+        MemoryBudget { limit, policy }
+    }
+}
+
+/// Format an amount of bytes in a human readable way.
+pub fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut amount = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while amount >= 1024.0 && unit + 1 < UNITS.len() {
+        amount /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", amount, UNITS[unit])
+}
+
+/// Halve the amount of samples, by replacing each pair of samples by their mean.
+pub fn average_pairs(observations: Vec<Observation<Sample>>) -> Vec<Observation<Sample>> {
+    observations
+        .chunks(2)
+        .map(|pair| {
+            let n = pair.len() as f64;
+            let t = pair.iter().map(|o| o.timestamp.amount).sum::<f64>() / n;
+            let value = pair.iter().map(|o| o.value.value).sum::<f64>() / n;
+            Observation::new(TimeStamp::new(t), Sample::new(value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{average_pairs, format_bytes};
+    use crate::time::TimeStamp;
+    use crate::tsdb::observations::{Observation, Sample};
+
+    #[test]
+    fn pairwise_average() {
+        let observations = (0..5)
+            .map(|i| Observation::new(TimeStamp::new(i as f64), Sample::new(i as f64 * 2.0)))
+            .collect();
+        let averaged = average_pairs(observations);
+        let points: Vec<(f64, f64)> = averaged
+            .iter()
+            .map(|o| (o.timestamp.amount, o.value.value))
+            .collect();
+        assert_eq!(points, vec![(0.5, 1.0), (2.5, 5.0), (4.0, 8.0)]);
+    }
+
+    #[test]
+    fn byte_formatting() {
+        assert_eq!(format_bytes(100), "100 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MiB");
+    }
+}
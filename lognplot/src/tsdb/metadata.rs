@@ -0,0 +1,127 @@
+//! Signal meta data.
+//!
+//! Meta data describes what a signal means, for example
+//! its unit, so that it can be presented properly.
+
+use crate::style::Color;
+use std::str::FromStr;
+
+/// Descriptive information about a single signal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignalMetadata {
+    /// The unit of the signal, for example `V` or `rpm`.
+    pub unit: Option<String>,
+
+    /// A human readable description of the signal.
+    pub description: Option<String>,
+
+    /// Factor to multiply raw values with before displaying them.
+    pub scale: f64,
+
+    /// Offset to add to scaled values before displaying them.
+    pub offset: f64,
+
+    /// Preferred color to plot this signal with, for example `#1F77B4`.
+    pub color: Option<String>,
+
+    /// Free form tags attached to this signal.
+    pub tags: Vec<String>,
+
+    /// Observations further apart than this amount of seconds are
+    /// not connected when drawn, to make drop outs visible.
+    pub gap_threshold: Option<f64>,
+}
+
+impl Default for SignalMetadata {
+    fn default() -> Self {
+        SignalMetadata {
+            unit: None,
+            description: None,
+            scale: 1.0,
+            offset: 0.0,
+            color: None,
+            tags: vec![],
+            gap_threshold: None,
+        }
+    }
+}
+
+impl SignalMetadata {
+    /// Create meta data with only a unit.
+    pub fn with_unit(unit: &str) -> Self {
+        SignalMetadata {
+            unit: Some(unit.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    /// Convert a raw value into the value as it should be displayed.
+    pub fn display_value(&self, value: f64) -> f64 {
+        value * self.scale + self.offset
+    }
+
+    /// Convert a displayed value, such as a level picked in a chart, back into a raw value.
+    ///
+    /// Returns `None` when the scale is zero, since all raw values then look the same.
+    pub fn raw_value(&self, value: f64) -> Option<f64> {
+        if self.scale == 0.0 {
+            None
+        } else {
+            Some((value - self.offset) / self.scale)
+        }
+    }
+
+    /// Format a raw value for display, including the unit if any.
+    pub fn format_value(&self, value: f64) -> String {
+        let value = self.display_value(value);
+        if let Some(unit) = &self.unit {
+            format!("{} {}", value, unit)
+        } else {
+            value.to_string()
+        }
+    }
+
+    /// Test if this signal has the given tag.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+}
+
+/// Test if a color, such as `#1F77B4` or `red`, can be drawn.
+///
+/// Colors arrive from clients, and malformed ones are dropped
+/// before they reach the charts.
+pub(crate) fn is_valid_color(color: &str) -> bool {
+    if let Some(digits) = color.strip_prefix('#') {
+        digits.len() == 6 && digits.chars().all(|c| c.is_ascii_hexdigit())
+    } else {
+        Color::from_str(color).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_valid_color, SignalMetadata};
+
+    #[test]
+    fn display_scaling() {
+        let mut metadata = SignalMetadata::with_unit("V");
+        metadata.scale = 0.5;
+        metadata.offset = 1.0;
+        assert_eq!(metadata.display_value(4.0), 3.0);
+        assert_eq!(metadata.format_value(4.0), "3 V");
+        assert_eq!(SignalMetadata::default().format_value(4.0), "4");
+        assert_eq!(metadata.raw_value(3.0), Some(4.0));
+        metadata.scale = 0.0;
+        assert_eq!(metadata.raw_value(3.0), None);
+    }
+
+    #[test]
+    fn color_validation() {
+        assert!(is_valid_color("#1F77B4"));
+        assert!(is_valid_color("red"));
+        assert!(!is_valid_color("#zzzzzz"));
+        assert!(!is_valid_color("#ééé"));
+        assert!(!is_valid_color("purple"));
+    }
+}
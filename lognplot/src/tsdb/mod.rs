@@ -1,44 +1,74 @@
 //! Time series database, usable as a library.
 
+mod alarm;
+mod annotation;
 mod api;
 mod btree;
+mod clock;
 mod connection;
+mod correlation;
 mod db;
 mod handle;
+mod ingest;
+mod memory;
+mod metadata;
+mod namespace;
 mod notify;
 pub mod observations;
 mod query;
 mod query_result;
+mod resample;
+mod search;
+mod spectrum;
+mod subscription;
 mod summary;
+mod text_search;
 mod trace;
 mod track;
 mod track_type;
+mod trigger;
 mod void_db;
 
+pub use alarm::{Alarm, AlarmCondition, AlarmRule, ALARM_TRACK};
+pub use annotation::Annotation;
 pub use api::TsDbApi;
 use btree::Btree;
+pub use clock::{ClockEstimator, ClockModel, TimeBase};
+pub use correlation::{cross_statistics, CrossStatistics};
 pub use db::TsDb;
 pub use handle::TsDbHandle;
+pub use ingest::{IngestStatistics, IngestTracker};
+pub use memory::{format_bytes, EvictionPolicy, HeapSize, MemoryBudget, MemoryUsage};
+pub use metadata::SignalMetadata;
+pub use namespace::{glob_match, is_glob, GlobPattern, MAX_PATTERN_LENGTH, SEPARATOR};
 pub use void_db::VoidDb;
 
 pub use notify::{ChangeSubscriber, DataChangeEvent};
 pub use query::Query;
 pub use query_result::{QueryResult, RangeQueryResult};
+pub use resample::Resampled;
+pub use search::{CrossingEdge, Extremes, SearchDirection, ValuePredicate};
+pub use spectrum::{spectrum, Spectrum, SpectrumOptions, WindowFunction};
+pub use subscription::{DataBatch, DataSubscriber, NewObservations};
 
-pub use summary::{QuickSummary, Summary};
+pub use summary::{LastValue, QuickSummary, Summary};
+pub use text_search::{tokenize, TextHit, TextIndex, TextQuery};
 
 pub use trace::Trace;
 pub use track::Track;
 pub use track_type::TrackType;
+pub use trigger::{
+    Trigger, TriggerCondition, TriggerHit, TriggerId, TriggerMode, TRIGGER_TRACK_PREFIX,
+};
 
 #[cfg(test)]
 mod tests {
     use super::connection::Connection;
-    use super::observations::{Observation, Sample};
+    use super::observations::{Observation, Sample, Text};
     use super::query::Query;
-    use super::{TsDb, TsDbApi};
+    use super::{ClockModel, EvictionPolicy, MemoryBudget, SpectrumOptions, TsDb, TsDbApi};
     use crate::time::TimeModifiers;
-    use crate::time::TimeStamp;
+    use crate::time::{TimeSpan, TimeStamp};
 
     #[test]
     fn basic_usage() {
@@ -75,4 +105,122 @@ mod tests {
 
         db.close();
     }
+
+    #[test]
+    fn namespaces() {
+        let mut db = TsDb::default();
+        let ts = TimeStamp::from_seconds(0);
+        for name in &[
+            "robot/arm/joint1/torque",
+            "robot/arm/joint2/torque",
+            "robot/base",
+        ] {
+            db.add_value(name, Observation::new(ts.clone(), Sample::new(1.0)));
+        }
+
+        assert_eq!(vec!["robot"], db.get_child_names(""));
+        assert_eq!(
+            vec!["robot/arm/joint1/torque", "robot/arm/joint2/torque"],
+            db.match_signal_names("robot/*/joint*/torque")
+        );
+
+        db.delete_matching("robot/arm/**");
+        assert_eq!(vec!["robot/base"], db.get_signal_names());
+    }
+
+    #[test]
+    fn clock_model() {
+        let mut db = TsDb::default();
+        db.set_clock_model("target", ClockModel::new(100.0, 0.01));
+        db.assign_clock("target/**", "target");
+
+        let observation = Observation::new(TimeStamp::from_seconds(10), Sample::new(1.0));
+        db.add_value("target/speed", observation.clone());
+        db.add_value("pc/speed", observation);
+
+        let summary = db.quick_summary("target/speed").unwrap();
+        assert_eq!(110.1, summary.last_timestamp().amount);
+        let summary = db.quick_summary("pc/speed").unwrap();
+        assert_eq!(10.0, summary.last_timestamp().amount);
+
+        // The model is applied when data is retrieved:
+        db.set_clock_model("target", ClockModel::new(200.0, 0.0));
+        let summary = db.quick_summary("target/speed").unwrap();
+        assert_eq!(210.0, summary.last_timestamp().amount);
+        let timespan = TimeSpan::new(TimeStamp::new(209.0), TimeStamp::new(211.0));
+        let samples = db.get_raw_range("target/speed", &timespan).unwrap();
+        assert_eq!(1, samples.len());
+        assert_eq!(210.0, samples[0].timestamp.amount);
+    }
+
+    #[test]
+    fn memory_budget() {
+        let mut db = TsDb::default();
+        let samples = |offset: f64| {
+            (0..1000)
+                .map(|i| Observation::new(TimeStamp::new(offset + i as f64), Sample::new(1.0)))
+                .collect()
+        };
+        db.add_values("viewed", samples(0.0));
+        db.add_values("ignored", samples(0.0));
+
+        let usage = db.memory_usage("viewed").unwrap();
+        assert!(usage.observations > 0);
+        assert!(usage.aggregations > 0);
+        assert_eq!(usage.strings, 0);
+        let total = db.total_memory_usage().total();
+        assert_eq!(total, 2 * usage.total());
+
+        // Looking at a signal protects it from eviction:
+        let query = Query::create()
+            .start(TimeStamp::new(0.0))
+            .end(TimeStamp::new(1000.0))
+            .build();
+        db.query("viewed", query);
+        db.set_memory_budget(Some(MemoryBudget::new(
+            total * 3 / 4,
+            EvictionPolicy::Downsample,
+        )));
+        assert!(db.total_memory_usage().total() <= total * 3 / 4);
+        assert_eq!(db.quick_summary("viewed").unwrap().count, 1000);
+        let summary = db.quick_summary("ignored").unwrap();
+        assert!(summary.count < 1000);
+        assert!(summary.count > 250, "Only the excess is evicted");
+        assert_eq!(999.0, summary.last_timestamp().amount);
+
+        // New data keeps the total within the budget:
+        db.add_values("viewed", samples(1000.0));
+        assert!(db.total_memory_usage().total() <= total * 3 / 4);
+
+        // The search index of texts counts too:
+        db.add_text(
+            "log",
+            Observation::new(TimeStamp::new(0.0), Text::new("motor started".to_owned())),
+        );
+        let usage = db.memory_usage("log").unwrap();
+        assert!(usage.index > 0);
+        assert!(db.total_memory_usage().total() <= total * 3 / 4);
+    }
+
+    #[test]
+    fn spectrum_of_partial_view() {
+        // 4 seconds of 50 Hz, sampled at 1 kHz, in a view of 100 seconds:
+        let mut db = TsDb::default();
+        let samples = (0..4000)
+            .map(|i| {
+                let t = 10.0 + i as f64 * 0.001;
+                let value = (2.0 * std::f64::consts::PI * 50.0 * t).sin();
+                Observation::new(TimeStamp::new(t), Sample::new(value))
+            })
+            .collect();
+        db.add_values("wave", samples);
+
+        let view = TimeSpan::new(TimeStamp::new(0.0), TimeStamp::new(100.0));
+        let spectrum = db
+            .spectrum("wave", &view, &SpectrumOptions::default())
+            .unwrap();
+        let (frequency, magnitude) = spectrum.dominant_frequency().unwrap();
+        assert!((frequency - 50.0).abs() < 1.0);
+        assert!((magnitude - 1.0).abs() < 0.3);
+    }
 }
@@ -0,0 +1,255 @@
+//! Hierarchical signal names.
+//!
+//! Signals can be organized in a tree by using a separator
+//! in their names, for example `robot/arm/joint3/torque`.
+//! This module contains helpers to deal with those names.
+
+/// The separator between name segments.
+pub const SEPARATOR: char = '/';
+
+/// Longest glob pattern accepted from other processes.
+pub const MAX_PATTERN_LENGTH: usize = 256;
+
+/// Test if a signal name matches a glob pattern.
+///
+/// See `GlobPattern` for the supported wildcards. To match many
+/// names, compile the pattern once with `GlobPattern::new` instead.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    GlobPattern::new(pattern).matches(name)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GlobToken {
+    Char(char),
+
+    /// `?`
+    AnyChar,
+
+    /// `*`
+    Star,
+
+    /// `**`
+    DoubleStar,
+
+    /// Start of `**/`, which is followed by `**` and the separator. The
+    /// whole can be skipped, so that `a/**/b` matches `a/b` as well.
+    OptionalSegments,
+}
+
+/// A glob pattern, prepared for matching many names.
+///
+/// Supported wildcards:
+/// - `*` matches any part within a single name segment
+/// - `**` matches any amount of segments
+/// - `?` matches a single character, except the separator
+///
+/// Matching takes time proportional to the length of the name times
+/// the length of the pattern, whatever the wildcards.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlobPattern {
+    pattern: String,
+    tokens: Vec<GlobToken>,
+}
+
+/// Words of a set of states, which are stored on the stack.
+const INLINE_WORDS: usize = 4;
+
+impl GlobPattern {
+    pub fn new(pattern: &str) -> Self {
+        let mut tokens = vec![];
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            let token = match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    if chars.peek() == Some(&SEPARATOR) {
+                        chars.next();
+                        tokens.push(GlobToken::OptionalSegments);
+                        tokens.push(GlobToken::DoubleStar);
+                        GlobToken::Char(SEPARATOR)
+                    } else {
+                        GlobToken::DoubleStar
+                    }
+                }
+                '*' => GlobToken::Star,
+                '?' => GlobToken::AnyChar,
+                c => GlobToken::Char(c),
+            };
+            tokens.push(token);
+        }
+        GlobPattern {
+            pattern: pattern.to_owned(),
+            tokens,
+        }
+    }
+
+    /// The pattern as given.
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Test if a signal name matches this pattern.
+    pub fn matches(&self, name: &str) -> bool {
+        // One state per position in the pattern, plus the final state:
+        let words = self.tokens.len() / 64 + 1;
+        if words <= INLINE_WORDS {
+            let mut current = [0; INLINE_WORDS];
+            let mut next = [0; INLINE_WORDS];
+            self.run(name, &mut current[..words], &mut next[..words])
+        } else {
+            self.run(name, &mut vec![0; words], &mut vec![0; words])
+        }
+    }
+
+    /// Simulate the automaton of the pattern, following all possible
+    /// positions in the pattern at once, instead of backtracking.
+    fn run(&self, name: &str, current: &mut [u64], next: &mut [u64]) -> bool {
+        set_state(current, 0);
+        self.skip_empty(current);
+
+        for c in name.chars() {
+            next.iter_mut().for_each(|word| *word = 0);
+            for (state, token) in self.tokens.iter().enumerate() {
+                if !has_state(current, state) {
+                    continue;
+                }
+                match token {
+                    GlobToken::Char(expected) if c == *expected => set_state(next, state + 1),
+                    GlobToken::Char(_) => {}
+                    GlobToken::AnyChar if c != SEPARATOR => set_state(next, state + 1),
+                    GlobToken::Star if c != SEPARATOR => set_state(next, state),
+                    GlobToken::AnyChar | GlobToken::Star => {}
+                    GlobToken::DoubleStar => set_state(next, state),
+                    GlobToken::OptionalSegments => {}
+                }
+            }
+            self.skip_empty(next);
+
+            if next.iter().all(|word| *word == 0) {
+                return false;
+            }
+            current.copy_from_slice(next);
+        }
+
+        has_state(current, self.tokens.len())
+    }
+
+    /// Add the states reached by wildcards which match nothing.
+    fn skip_empty(&self, states: &mut [u64]) {
+        for (state, token) in self.tokens.iter().enumerate() {
+            if !has_state(states, state) {
+                continue;
+            }
+            match token {
+                GlobToken::Star | GlobToken::DoubleStar => set_state(states, state + 1),
+                GlobToken::OptionalSegments => {
+                    set_state(states, state + 1);
+                    set_state(states, state + 3);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn set_state(states: &mut [u64], state: usize) {
+    states[state / 64] |= 1 << (state % 64);
+}
+
+fn has_state(states: &[u64], state: usize) -> bool {
+    states[state / 64] & (1 << (state % 64)) != 0
+}
+
+/// Test if the given text contains glob wildcards.
+pub fn is_glob(text: &str) -> bool {
+    text.contains('*') || text.contains('?')
+}
+
+/// Determine the direct children of a prefix, given a set of signal names.
+///
+/// The children are returned as full paths, sorted by name.
+/// An empty prefix gives the top level entries.
+pub fn child_names<'n, I>(prefix: &str, names: I) -> Vec<String>
+where
+    I: Iterator<Item = &'n String>,
+{
+    let prefix = prefix.trim_end_matches(SEPARATOR);
+    let mut children: Vec<String> = names
+        .filter_map(|name| {
+            let rest = if prefix.is_empty() {
+                name.as_str()
+            } else {
+                name.strip_prefix(prefix)?.strip_prefix(SEPARATOR)?
+            };
+            let segment = rest.split(SEPARATOR).next()?;
+            if segment.is_empty() {
+                None
+            } else if prefix.is_empty() {
+                Some(segment.to_owned())
+            } else {
+                Some(format!("{}{}{}", prefix, SEPARATOR, segment))
+            }
+        })
+        .collect();
+    children.sort();
+    children.dedup();
+    children
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{child_names, glob_match, GlobPattern};
+
+    #[test]
+    fn glob_matching() {
+        assert!(glob_match(
+            "robot/*/joint*/torque",
+            "robot/arm/joint3/torque"
+        ));
+        assert!(!glob_match("robot/*/torque", "robot/arm/joint3/torque"));
+        assert!(glob_match("robot/**/torque", "robot/arm/joint3/torque"));
+        assert!(glob_match("robot/**/torque", "robot/torque"));
+        assert!(glob_match("**", "robot/arm"));
+        assert!(glob_match("joint?", "joint3"));
+        assert!(!glob_match("joint?", "joint"));
+        assert!(!glob_match("robot", "robot/arm"));
+        assert!(glob_match("a/**/b/**", "a/b/c/d"));
+        assert!(glob_match("**/torque", "torque"));
+        assert!(!glob_match("a/**/b", "a/xb"));
+        assert!(glob_match("*a*?", "xaay"));
+        assert!(!glob_match("*a*?", "xa/y"));
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "a"));
+    }
+
+    #[test]
+    fn no_backtracking_explosion() {
+        let pattern = GlobPattern::new(&"*a".repeat(30));
+        let name = "a".repeat(60) + "b";
+        assert!(!pattern.matches(&name));
+
+        let pattern = GlobPattern::new(&"**a".repeat(100));
+        assert!(pattern.matches(&"a".repeat(100)));
+        assert!(!pattern.matches(&"a".repeat(99)));
+    }
+
+    #[test]
+    fn children() {
+        let names: Vec<String> = vec![
+            "robot/arm/joint1/torque".to_owned(),
+            "robot/arm/joint3/torque".to_owned(),
+            "robot/base".to_owned(),
+            "speed".to_owned(),
+        ];
+        assert_eq!(child_names("", names.iter()), vec!["robot", "speed"]);
+        assert_eq!(
+            child_names("robot", names.iter()),
+            vec!["robot/arm", "robot/base"]
+        );
+        assert_eq!(
+            child_names("robot/arm/", names.iter()),
+            vec!["robot/arm/joint1", "robot/arm/joint3"]
+        );
+        assert!(child_names("rob", names.iter()).is_empty());
+    }
+}
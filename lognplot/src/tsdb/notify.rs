@@ -3,6 +3,7 @@
 //! This module is used by the GUI to respond to changes
 //! which happen in the database.
 
+use super::{Alarm, TriggerHit};
 use futures::channel::mpsc;
 use std::collections::HashSet;
 
@@ -34,6 +35,30 @@ impl ChangeSubscriber {
         self.emit_event();
     }
 
+    /// Notification of a deleted signal
+    pub fn notify_signal_deleted(&mut self, name: &str) {
+        self.event.add_deleted_signal(name);
+        self.emit_event();
+    }
+
+    /// Notification of a trigger which fired.
+    pub fn notify_trigger(&mut self, hit: &TriggerHit) {
+        self.event.add_trigger_hit(hit);
+        self.emit_event();
+    }
+
+    /// Notification of a raised alarm.
+    pub fn notify_alarm(&mut self, alarm: &Alarm) {
+        self.event.add_alarm(alarm);
+        self.emit_event();
+    }
+
+    /// Notification of added annotations.
+    pub fn notify_annotations_changed(&mut self) {
+        self.event.annotations_changed = true;
+        self.emit_event();
+    }
+
     /// Notification that all data was deleted.
     pub fn notify_delete_all(&mut self) {
         self.event.add_delete_all();
@@ -80,6 +105,16 @@ impl ChangeSubscriber {
 pub struct DataChangeEvent {
     pub new_signals: HashSet<String>,
     pub changed_signals: HashSet<String>,
+    pub deleted_signals: HashSet<String>,
+
+    /// The latest hit of each trigger which fired. All hits of a trigger
+    /// can be retrieved from the database.
+    pub trigger_hits: Vec<TriggerHit>,
+
+    /// The latest alarm raised by each rule. All alarms are logged in
+    /// the alarm track.
+    pub alarms: Vec<Alarm>,
+    pub annotations_changed: bool,
     pub delete_all: bool,
 }
 
@@ -88,12 +123,22 @@ impl DataChangeEvent {
         DataChangeEvent {
             new_signals: HashSet::new(),
             changed_signals: HashSet::new(),
+            deleted_signals: HashSet::new(),
+            trigger_hits: vec![],
+            alarms: vec![],
+            annotations_changed: false,
             delete_all: false,
         }
     }
 
     fn is_empty(&self) -> bool {
-        self.new_signals.is_empty() && self.changed_signals.is_empty() && !self.delete_all
+        self.new_signals.is_empty()
+            && self.changed_signals.is_empty()
+            && self.deleted_signals.is_empty()
+            && self.trigger_hits.is_empty()
+            && self.alarms.is_empty()
+            && !self.annotations_changed
+            && !self.delete_all
     }
 
     fn add_new_signal(&mut self, name: &str) {
@@ -104,12 +149,89 @@ impl DataChangeEvent {
         self.changed_signals.insert(name.to_owned());
     }
 
+    fn add_deleted_signal(&mut self, name: &str) {
+        // A signal which is added and deleted within a single event was never there:
+        if !self.new_signals.remove(name) {
+            self.deleted_signals.insert(name.to_owned());
+        }
+        self.changed_signals.remove(name);
+    }
+
+    fn add_trigger_hit(&mut self, hit: &TriggerHit) {
+        // Keep a single hit per trigger, while the subscriber is not polling:
+        if let Some(pending) = self.trigger_hits.iter_mut().find(|h| h.id == hit.id) {
+            *pending = hit.clone();
+        } else {
+            self.trigger_hits.push(hit.clone());
+        }
+    }
+
+    fn add_alarm(&mut self, alarm: &Alarm) {
+        // Alarms of the same rule share signal and message:
+        if let Some(pending) = self
+            .alarms
+            .iter_mut()
+            .find(|a| a.signal == alarm.signal && a.message == alarm.message)
+        {
+            *pending = alarm.clone();
+        } else {
+            self.alarms.push(alarm.clone());
+        }
+    }
+
     /// Add a delete all signals event
     fn add_delete_all(&mut self) {
         // Drop all signals added so far:
         self.new_signals.clear();
         self.changed_signals.clear();
+        self.deleted_signals.clear();
 
         self.delete_all = true;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::DataChangeEvent;
+    use crate::time::TimeStamp;
+    use crate::tsdb::{Alarm, TriggerHit};
+
+    fn hit(id: usize, timestamp: f64) -> TriggerHit {
+        TriggerHit {
+            id,
+            signal: "x".to_owned(),
+            timestamp: TimeStamp::new(timestamp),
+            value: 1.0,
+            window: None,
+            message: "x rising edge at 1".to_owned(),
+        }
+    }
+
+    #[test]
+    fn coalesce_trigger_hits() {
+        let mut event = DataChangeEvent::new();
+        for i in 0..1000 {
+            event.add_trigger_hit(&hit(i % 2, i as f64));
+        }
+        let timestamps: Vec<f64> = event
+            .trigger_hits
+            .iter()
+            .map(|h| h.timestamp.amount)
+            .collect();
+        assert_eq!(vec![998.0, 999.0], timestamps);
+    }
+
+    #[test]
+    fn coalesce_alarms() {
+        let mut event = DataChangeEvent::new();
+        for i in 0..1000 {
+            event.add_alarm(&Alarm {
+                signal: "battery_voltage".to_owned(),
+                timestamp: TimeStamp::new(i as f64),
+                message: "battery_voltage below 10.5 for 2 s".to_owned(),
+            });
+        }
+        assert_eq!(1, event.alarms.len());
+        assert_eq!(999.0, event.alarms[0].timestamp.amount);
+    }
+}
@@ -57,7 +57,7 @@ where
     fn from(reference: &Aggregation<V, M>) -> Self {
         let timespan = reference.timespan.clone();
         let metrics = reference.metrics.clone();
-        Aggregation::new(timespan, metrics, 1)
+        Aggregation::new(timespan, metrics, reference.count)
     }
 }
 
@@ -18,6 +18,9 @@ impl Sample {
 
 /// Metrics collected about a certain trace
 /// This can be used during query.
+///
+/// NaN and infinite values are counted as invalid, and
+/// are otherwise left out of the metrics.
 #[derive(Debug, Clone)]
 pub struct SampleMetrics {
     /// The minimum value of all samples
@@ -26,10 +29,10 @@ pub struct SampleMetrics {
     /// The maximum value of all samples
     pub max: f64,
 
-    /// The first observed value
+    /// The first valid value, NaN if there is none.
     pub first: f64,
 
-    /// The last measured value
+    /// The last valid value, NaN if there is none.
     pub last: f64,
 
     /// The mean of all values.
@@ -41,26 +44,55 @@ pub struct SampleMetrics {
     /// See also: https://en.wikipedia.org/wiki/Algorithms_for_calculating_variance#Welford's_online_algorithm
     m2: f64,
 
-    /// The total number of samples
+    /// The number of valid samples
     pub count: usize,
+
+    /// The number of NaN or infinite samples
+    pub invalid: usize,
 }
 
 impl SampleMetrics {
     /// Create metrics from a single value!
     fn from_value(value: f64) -> Self {
-        SampleMetrics {
-            min: value,
-            max: value,
-            mean: value,
-            first: value,
-            last: value,
-            m2: 0.0,
-            count: 1,
+        if value.is_finite() {
+            SampleMetrics {
+                min: value,
+                max: value,
+                mean: value,
+                first: value,
+                last: value,
+                m2: 0.0,
+                count: 1,
+                invalid: 0,
+            }
+        } else {
+            SampleMetrics {
+                min: f64::INFINITY,
+                max: f64::NEG_INFINITY,
+                mean: f64::NAN,
+                first: f64::NAN,
+                last: f64::NAN,
+                m2: 0.0,
+                count: 0,
+                invalid: 1,
+            }
         }
     }
 
     /// Include a single observation into the mix:
     fn inject_value(&mut self, value: f64) {
+        if !value.is_finite() {
+            self.invalid += 1;
+            return;
+        }
+
+        if self.count == 0 {
+            let invalid = self.invalid;
+            *self = SampleMetrics::from_value(value);
+            self.invalid = invalid;
+            return;
+        }
+
         // These updates are trivial:
         self.min = self.min.min(value);
         self.max = self.max.max(value);
@@ -108,6 +140,29 @@ impl SampleMetrics {
     pub fn stddev(&self) -> f64 {
         self.variance().sqrt()
     }
+
+    /// Metrics of the values multiplied by scale, with offset added.
+    pub fn scaled(&self, scale: f64, offset: f64) -> Self {
+        if self.count == 0 {
+            return self.clone();
+        }
+
+        let (min, max) = if scale < 0.0 {
+            (self.max, self.min)
+        } else {
+            (self.min, self.max)
+        };
+        SampleMetrics {
+            min: min * scale + offset,
+            max: max * scale + offset,
+            first: self.first * scale + offset,
+            last: self.last * scale + offset,
+            mean: self.mean * scale + offset,
+            m2: self.m2 * scale * scale,
+            count: self.count,
+            invalid: self.invalid,
+        }
+    }
 }
 
 impl From<Sample> for SampleMetrics {
@@ -126,6 +181,18 @@ impl Metrics<Sample> for SampleMetrics {
 
     /// Include other metrics into this metrics.
     fn include(&mut self, metrics: &SampleMetrics) {
+        self.invalid += metrics.invalid;
+        if metrics.count == 0 {
+            return;
+        }
+
+        if self.count == 0 {
+            let invalid = self.invalid;
+            *self = metrics.clone();
+            self.invalid = invalid;
+            return;
+        }
+
         self.min = self.min.min(metrics.min);
         self.max = self.max.max(metrics.max);
 
@@ -188,4 +255,37 @@ mod tests {
         assert_eq!(metrics.variance(), 2.0);
         almost_equal(metrics.stddev(), 1.414213562373, 1.0e-9);
     }
+
+    #[test]
+    fn invalid_values() {
+        let values = [f64::NAN, 1.0, f64::INFINITY, 3.0];
+        let metrics = SampleMetrics::from_values(&values).unwrap();
+        assert_eq!(metrics.count, 2);
+        assert_eq!(metrics.invalid, 2);
+        assert_eq!(metrics.min, 1.0);
+        assert_eq!(metrics.max, 3.0);
+        assert_eq!(metrics.first, 1.0);
+        assert_eq!(metrics.last, 3.0);
+        assert_eq!(metrics.mean(), 2.0);
+        assert_eq!(metrics.variance(), 1.0);
+
+        let mut nothing_valid = SampleMetrics::from(Sample::new(f64::NAN));
+        assert!(nothing_valid.mean().is_nan());
+        nothing_valid.include(&metrics);
+        assert_eq!(nothing_valid.count, 2);
+        assert_eq!(nothing_valid.invalid, 3);
+        assert_eq!(nothing_valid.mean(), 2.0);
+    }
+
+    #[test]
+    fn scaled_metrics() {
+        let metrics = SampleMetrics::from_values(&[1.0, 2.0, 3.0]).unwrap();
+        let scaled = metrics.scaled(-2.0, 1.0);
+        assert_eq!(scaled.min, -5.0);
+        assert_eq!(scaled.max, -1.0);
+        assert_eq!(scaled.first, -1.0);
+        assert_eq!(scaled.last, -5.0);
+        assert_eq!(scaled.mean(), -3.0);
+        assert_eq!(scaled.stddev(), 2.0 * metrics.stddev());
+    }
 }
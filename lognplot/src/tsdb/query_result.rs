@@ -1,6 +1,7 @@
 use super::observations::{
     Aggregation, CountMetrics, Metrics, Observation, ProfileEvent, Sample, SampleMetrics, Text,
 };
+use crate::time::TimeStamp;
 
 /// This holds the result of a query to the database.
 /// The result can be several things, depending upon query type.
@@ -45,3 +46,120 @@ where
         }
     }
 }
+
+impl RangeQueryResult<Sample, SampleMetrics> {
+    /// Insert a NaN sample halfway each gap longer than the threshold.
+    ///
+    /// Renderers break lines at NaN samples, so gaps in the data
+    /// are not drawn as straight lines.
+    pub fn mark_gaps(&mut self, threshold: f64) {
+        match self {
+            RangeQueryResult::Observations(observations) => {
+                let mut marked = Vec::with_capacity(observations.len());
+                for observation in observations.drain(..) {
+                    if let Some(previous) = marked.last() {
+                        let previous: &Observation<Sample> = previous;
+                        if let Some(gap) =
+                            gap_marker(&previous.timestamp, &observation.timestamp, threshold)
+                        {
+                            marked.push(gap);
+                        }
+                    }
+                    marked.push(observation);
+                }
+                *observations = marked;
+            }
+            RangeQueryResult::Aggregations(aggregations) => {
+                let mut marked = Vec::with_capacity(aggregations.len());
+                for aggregation in aggregations.drain(..) {
+                    if let Some(previous) = marked.last() {
+                        let previous: &Aggregation<Sample, SampleMetrics> = previous;
+                        if let Some(gap) = gap_marker(
+                            &previous.timespan.end,
+                            &aggregation.timespan.start,
+                            threshold,
+                        ) {
+                            marked.push(Aggregation::from(gap));
+                        }
+                    }
+                    marked.push(aggregation);
+                }
+                *aggregations = marked;
+            }
+        }
+    }
+
+    /// Multiply all values by scale, and add offset.
+    pub fn scale(&mut self, scale: f64, offset: f64) {
+        match self {
+            RangeQueryResult::Observations(observations) => {
+                for observation in observations {
+                    observation.value.value = observation.value.value * scale + offset;
+                }
+            }
+            RangeQueryResult::Aggregations(aggregations) => {
+                for aggregation in aggregations {
+                    let metrics = aggregation.metrics().scaled(scale, offset);
+                    *aggregation =
+                        Aggregation::new(aggregation.timespan.clone(), metrics, aggregation.count);
+                }
+            }
+        }
+    }
+}
+
+/// A NaN sample between two moments which are too far apart.
+fn gap_marker(
+    previous: &TimeStamp,
+    next: &TimeStamp,
+    threshold: f64,
+) -> Option<Observation<Sample>> {
+    if next.amount - previous.amount > threshold {
+        let middle = (previous.amount + next.amount) / 2.0;
+        Some(Observation::new(
+            TimeStamp::new(middle),
+            Sample::new(f64::NAN),
+        ))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RangeQueryResult;
+    use crate::time::TimeStamp;
+    use crate::tsdb::observations::{Aggregation, Observation, Sample, SampleMetrics};
+
+    #[test]
+    fn gaps_are_marked() {
+        let observations = [0.0, 1.0, 5.0, 6.0]
+            .iter()
+            .map(|t| Observation::new(TimeStamp::new(*t), Sample::new(1.0)))
+            .collect::<Vec<_>>();
+
+        let mut result = RangeQueryResult::Observations(observations.clone());
+        result.mark_gaps(2.0);
+        if let RangeQueryResult::Observations(marked) = result {
+            assert_eq!(marked.len(), 5);
+            assert_eq!(marked[2].timestamp.amount, 3.0);
+            assert!(marked[2].value.value.is_nan());
+        } else {
+            panic!("Expected observations");
+        }
+
+        let aggregations: Vec<Aggregation<Sample, SampleMetrics>> = observations
+            .chunks(2)
+            .map(|pair| Aggregation::from_observations(pair).unwrap())
+            .collect();
+        let mut result = RangeQueryResult::Aggregations(aggregations);
+        result.mark_gaps(2.0);
+        if let RangeQueryResult::Aggregations(marked) = result {
+            assert_eq!(marked.len(), 3);
+            assert_eq!(marked[1].metrics().count, 0);
+            assert_eq!(marked[1].metrics().invalid, 1);
+        } else {
+            panic!("Expected aggregations");
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! Resampling of value traces onto a regular time grid.
+//!
+//! Many calculations, such as correlations and spectra, need
+//! values at evenly spaced moments. Resampling uses the tree
+//! to fetch data at about the required resolution, and
+//! interpolates linearly between the retrieved points.
+
+use super::observations::{Sample, SampleMetrics};
+use super::{Query, RangeQueryResult, Trace};
+use crate::time::{TimeSpan, TimeStamp};
+
+/// Values of a signal at regular intervals.
+#[derive(Debug, Clone)]
+pub struct Resampled {
+    /// Time of the first value.
+    pub start: TimeStamp,
+
+    /// Time between two values, in seconds.
+    pub step: f64,
+
+    pub values: Vec<f64>,
+}
+
+impl Resampled {
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The amount of values per second.
+    pub fn sample_rate(&self) -> f64 {
+        1.0 / self.step
+    }
+}
+
+impl Trace<Sample, SampleMetrics> {
+    /// Resample the values within a timespan onto `count` evenly spaced moments.
+    ///
+    /// Where the trace contains many more samples than requested, the
+    /// mean of aggregated samples is used instead of the raw samples.
+    pub fn resample(&self, timespan: &TimeSpan, count: usize) -> Option<Resampled> {
+        if count < 2 {
+            return None;
+        }
+
+        let query = Query::create().span(timespan).amount(count * 2).build();
+        let points: Vec<(f64, f64)> = match self.query(query) {
+            RangeQueryResult::Observations(observations) => observations
+                .iter()
+                .map(|o| (o.timestamp.amount, o.value.value))
+                .collect(),
+            RangeQueryResult::Aggregations(aggregations) => aggregations
+                .iter()
+                .map(|a| (a.timespan.middle_timestamp().amount, a.metrics().mean()))
+                .collect(),
+        };
+
+        let step = (timespan.end.amount - timespan.start.amount) / (count - 1) as f64;
+        let values = interpolate(&points, timespan.start.amount, step, count)?;
+        Some(Resampled {
+            start: timespan.start.clone(),
+            step,
+            values,
+        })
+    }
+}
+
+/// Linearly interpolate sorted points onto a regular grid.
+///
+/// Grid points before the first, or after the last point
+/// take the value of the nearest point.
+fn interpolate(points: &[(f64, f64)], start: f64, step: f64, count: usize) -> Option<Vec<f64>> {
+    let first = points.first()?;
+    let last = points.last()?;
+
+    let mut values = Vec::with_capacity(count);
+    let mut index = 0;
+    for i in 0..count {
+        let t = start + step * i as f64;
+        let value = if t <= first.0 {
+            first.1
+        } else if t >= last.0 {
+            last.1
+        } else {
+            // Advance to the segment containing t:
+            while points[index + 1].0 < t {
+                index += 1;
+            }
+            let (t0, v0) = points[index];
+            let (t1, v1) = points[index + 1];
+            if t1 > t0 {
+                v0 + (v1 - v0) * (t - t0) / (t1 - t0)
+            } else {
+                v1
+            }
+        };
+        values.push(value);
+    }
+    Some(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::interpolate;
+
+    #[test]
+    fn linear_interpolation() {
+        let points = [(1.0, 10.0), (2.0, 20.0), (4.0, 0.0)];
+        let values = interpolate(&points, 0.0, 0.5, 10).unwrap();
+        assert_eq!(
+            values,
+            vec![10.0, 10.0, 10.0, 15.0, 20.0, 15.0, 10.0, 5.0, 0.0, 0.0]
+        );
+        assert!(interpolate(&[], 0.0, 1.0, 3).is_none());
+    }
+}
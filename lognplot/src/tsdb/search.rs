@@ -0,0 +1,267 @@
+//! Search for values in value traces.
+//!
+//! Each node in the tree knows the minimum and maximum value
+//! below it. Searches use this to skip over large parts of a
+//! trace which cannot contain a match.
+
+use super::observations::{Aggregation, Observation, Sample, SampleMetrics};
+use super::Trace;
+use crate::time::TimeSpan;
+
+/// The direction in which to search through time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchDirection {
+    /// Search from the start of the timespan towards the end.
+    Forward,
+
+    /// Search from the end of the timespan towards the start.
+    Backward,
+}
+
+/// A condition on a single value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValuePredicate {
+    /// Value is larger than the level.
+    Above(f64),
+
+    /// Value is larger than, or equal to the level.
+    AtLeast(f64),
+
+    /// Value is smaller than the level.
+    Below(f64),
+
+    /// Value is smaller than, or equal to the level.
+    AtMost(f64),
+
+    /// Value lies within the given inclusive range.
+    Between(f64, f64),
+}
+
+impl ValuePredicate {
+    /// Test if a value satisfies this predicate.
+    pub fn matches(&self, value: f64) -> bool {
+        match *self {
+            ValuePredicate::Above(level) => value > level,
+            ValuePredicate::AtLeast(level) => value >= level,
+            ValuePredicate::Below(level) => value < level,
+            ValuePredicate::AtMost(level) => value <= level,
+            ValuePredicate::Between(low, high) => low <= value && value <= high,
+        }
+    }
+
+    /// Test if any value within the metrics min / max range could match.
+    fn may_match(&self, metrics: &SampleMetrics) -> bool {
+        match *self {
+            ValuePredicate::Above(level) => metrics.max > level,
+            ValuePredicate::AtLeast(level) => metrics.max >= level,
+            ValuePredicate::Below(level) => metrics.min < level,
+            ValuePredicate::AtMost(level) => metrics.min <= level,
+            ValuePredicate::Between(low, high) => metrics.max >= low && metrics.min <= high,
+        }
+    }
+}
+
+/// Which kind of level crossing to look for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CrossingEdge {
+    /// Going from below the level to at or above the level.
+    Rising,
+
+    /// Going from above the level to at or below the level.
+    Falling,
+
+    /// Either a rising or a falling crossing.
+    Any,
+}
+
+/// The locations of the minimum and maximum value of a trace.
+#[derive(Debug, Clone)]
+pub struct Extremes {
+    pub min: Observation<Sample>,
+    pub max: Observation<Sample>,
+}
+
+impl Trace<Sample, SampleMetrics> {
+    /// Find the first value in the search direction which satisfies the predicate.
+    pub fn find_value(
+        &self,
+        predicate: ValuePredicate,
+        timespan: &TimeSpan,
+        direction: SearchDirection,
+    ) -> Option<Observation<Sample>> {
+        self.find(
+            timespan,
+            direction,
+            &|aggregation: &Aggregation<Sample, SampleMetrics>| {
+                predicate.may_match(aggregation.metrics())
+            },
+            &|observation: &Observation<Sample>| predicate.matches(observation.value.value),
+        )
+    }
+
+    /// Find the next crossing of a level in the search direction.
+    ///
+    /// The returned observation is the first sample past the level.
+    pub fn find_crossing(
+        &self,
+        level: f64,
+        edge: CrossingEdge,
+        timespan: &TimeSpan,
+        direction: SearchDirection,
+    ) -> Option<Observation<Sample>> {
+        let rising = || {
+            self.find_transition(
+                ValuePredicate::Below(level),
+                ValuePredicate::AtLeast(level),
+                timespan,
+                direction,
+            )
+        };
+        let falling = || {
+            self.find_transition(
+                ValuePredicate::Above(level),
+                ValuePredicate::AtMost(level),
+                timespan,
+                direction,
+            )
+        };
+
+        match edge {
+            CrossingEdge::Rising => rising(),
+            CrossingEdge::Falling => falling(),
+            CrossingEdge::Any => match (rising(), falling()) {
+                (Some(rising), Some(falling)) => {
+                    let rising_first = rising.timestamp < falling.timestamp;
+                    if rising_first == (direction == SearchDirection::Forward) {
+                        Some(rising)
+                    } else {
+                        Some(falling)
+                    }
+                }
+                (rising, falling) => rising.or(falling),
+            },
+        }
+    }
+
+    /// Find a sample satisfying `after` which directly follows a
+    /// sample satisfying `before`.
+    ///
+    /// `before` and `after` must be each others complement.
+    fn find_transition(
+        &self,
+        before: ValuePredicate,
+        after: ValuePredicate,
+        timespan: &TimeSpan,
+        direction: SearchDirection,
+    ) -> Option<Observation<Sample>> {
+        match direction {
+            SearchDirection::Forward => {
+                let start = self.find_value(before, timespan, direction)?;
+                let rest = TimeSpan::new(start.timestamp, timespan.end.clone());
+                self.find_value(after, &rest, direction)
+            }
+            SearchDirection::Backward => {
+                // Find the last sample before the last transition,
+                // the transition sample is the one right after it.
+                let end = self.find_value(after, timespan, direction)?;
+                let head = TimeSpan::new(timespan.start.clone(), end.timestamp.clone());
+                let start = self.find_value(before, &head, direction)?;
+                let between = TimeSpan::new(start.timestamp.clone(), end.timestamp);
+                self.find(
+                    &between,
+                    SearchDirection::Forward,
+                    &|_: &Aggregation<Sample, SampleMetrics>| true,
+                    &|observation: &Observation<Sample>| observation.timestamp > start.timestamp,
+                )
+            }
+        }
+    }
+
+    /// Locate the first occurrences of the minimum and maximum value in a timespan.
+    pub fn find_extremes(&self, timespan: &TimeSpan) -> Option<Extremes> {
+        let summary = self.summary(Some(timespan))?;
+        let metrics = summary.metrics();
+        let min = self.find_value(
+            ValuePredicate::AtMost(metrics.min),
+            timespan,
+            SearchDirection::Forward,
+        )?;
+        let max = self.find_value(
+            ValuePredicate::AtLeast(metrics.max),
+            timespan,
+            SearchDirection::Forward,
+        )?;
+        Some(Extremes { min, max })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CrossingEdge, SearchDirection, ValuePredicate};
+    use crate::time::{TimeSpan, TimeStamp};
+    use crate::tsdb::observations::{Observation, Sample, SampleMetrics};
+    use crate::tsdb::Trace;
+
+    /// Create a triangle wave going from 0 up to 9 and back, a few times.
+    fn triangle_trace() -> Trace<Sample, SampleMetrics> {
+        let mut trace = Trace::default();
+        for i in 0..100 {
+            let value = (9 - ((i % 18) - 9_isize).abs()) as f64;
+            trace.add_observation(Observation::new(
+                TimeStamp::from_seconds(i),
+                Sample::new(value),
+            ));
+        }
+        trace
+    }
+
+    fn time_of(observation: Option<Observation<Sample>>) -> f64 {
+        observation.unwrap().timestamp.amount
+    }
+
+    #[test]
+    fn first_and_last_value() {
+        let trace = triangle_trace();
+        let all = TimeSpan::from_seconds(0, 100);
+        let predicate = ValuePredicate::AtLeast(8.5);
+        let first = trace.find_value(predicate, &all, SearchDirection::Forward);
+        let last = trace.find_value(predicate, &all, SearchDirection::Backward);
+        assert_eq!(time_of(first), 9.0);
+        assert_eq!(time_of(last), 99.0);
+
+        let none = trace.find_value(ValuePredicate::Above(9.0), &all, SearchDirection::Forward);
+        assert!(none.is_none());
+    }
+
+    #[test]
+    fn crossings() {
+        let trace = triangle_trace();
+        let span = TimeSpan::from_seconds(10, 60);
+        let forward = SearchDirection::Forward;
+        let backward = SearchDirection::Backward;
+
+        // Values after t=10: 8, 7, ... 0 (t=18), 1, ...
+        let rising = trace.find_crossing(4.5, CrossingEdge::Rising, &span, forward);
+        assert_eq!(time_of(rising), 23.0);
+        let falling = trace.find_crossing(4.5, CrossingEdge::Falling, &span, forward);
+        assert_eq!(time_of(falling), 14.0);
+        let any = trace.find_crossing(4.5, CrossingEdge::Any, &span, forward);
+        assert_eq!(time_of(any), 14.0);
+
+        // Values before t=60: ..., 0 (t=54), 1, ... 6 (t=60)
+        let rising = trace.find_crossing(4.5, CrossingEdge::Rising, &span, backward);
+        assert_eq!(time_of(rising), 59.0);
+        let falling = trace.find_crossing(4.5, CrossingEdge::Falling, &span, backward);
+        assert_eq!(time_of(falling), 50.0);
+    }
+
+    #[test]
+    fn extremes() {
+        let trace = triangle_trace();
+        let span = TimeSpan::from_seconds(20, 40);
+        let extremes = trace.find_extremes(&span).unwrap();
+        assert_eq!(extremes.min.timestamp.amount, 36.0);
+        assert_eq!(extremes.max.timestamp.amount, 27.0);
+        assert_eq!(extremes.max.value.value, 9.0);
+    }
+}
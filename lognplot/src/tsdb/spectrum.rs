@@ -0,0 +1,271 @@
+//! Amplitude spectrum of a signal.
+//!
+//! The spectrum is calculated with Welch's method: the resampled
+//! signal is split into overlapping segments, each segment is
+//! windowed and transformed, and the power of all segments is averaged.
+
+use super::resample::Resampled;
+use std::f64::consts::PI;
+
+/// Window function applied to each segment before the transform.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowFunction {
+    Rectangular,
+    Hann,
+    Hamming,
+}
+
+impl WindowFunction {
+    fn coefficients(self, size: usize) -> Vec<f64> {
+        let n = (size - 1).max(1) as f64;
+        (0..size)
+            .map(|i| {
+                let x = 2.0 * PI * i as f64 / n;
+                match self {
+                    WindowFunction::Rectangular => 1.0,
+                    WindowFunction::Hann => 0.5 - 0.5 * x.cos(),
+                    WindowFunction::Hamming => 0.54 - 0.46 * x.cos(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Parameters of the spectrum calculation.
+#[derive(Debug, Clone)]
+pub struct SpectrumOptions {
+    pub window: WindowFunction,
+
+    /// Amount of samples per segment, rounded down to a power of two.
+    pub segment_size: usize,
+
+    /// Fraction of overlap between segments, between 0 and 1.
+    pub overlap: f64,
+}
+
+impl Default for SpectrumOptions {
+    fn default() -> Self {
+        SpectrumOptions {
+            window: WindowFunction::Hann,
+            segment_size: 1024,
+            overlap: 0.5,
+        }
+    }
+}
+
+/// Single sided amplitude spectrum.
+#[derive(Debug, Clone)]
+pub struct Spectrum {
+    /// Distance between two frequency bins, in Hz.
+    pub resolution: f64,
+
+    /// Amplitude per frequency bin, starting at 0 Hz.
+    pub magnitudes: Vec<f64>,
+
+    /// Amount of segments which were averaged.
+    pub segments: usize,
+}
+
+impl Spectrum {
+    /// The frequency of the given bin.
+    pub fn frequency(&self, bin: usize) -> f64 {
+        bin as f64 * self.resolution
+    }
+
+    /// Iterate over frequency and amplitude pairs.
+    pub fn bins(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.magnitudes
+            .iter()
+            .enumerate()
+            .map(move |(bin, magnitude)| (self.frequency(bin), *magnitude))
+    }
+
+    /// Find the frequency with the largest amplitude, ignoring the DC component.
+    ///
+    /// The frequency is refined by fitting a parabola through the peak bin and its neighbours.
+    pub fn dominant_frequency(&self) -> Option<(f64, f64)> {
+        let (peak, magnitude) = self.magnitudes.iter().enumerate().skip(1).fold(
+            None,
+            |best: Option<(usize, f64)>, (bin, magnitude)| match best {
+                Some((_, best_magnitude)) if best_magnitude >= *magnitude => best,
+                _ => Some((bin, *magnitude)),
+            },
+        )?;
+
+        let offset = if peak + 1 < self.magnitudes.len() {
+            let left = self.magnitudes[peak - 1];
+            let right = self.magnitudes[peak + 1];
+            let denominator = left - 2.0 * magnitude + right;
+            if denominator.abs() > 0.0 {
+                0.5 * (left - right) / denominator
+            } else {
+                0.0
+            }
+        } else {
+            0.0
+        };
+
+        Some(((peak as f64 + offset) * self.resolution, magnitude))
+    }
+}
+
+/// Calculate the amplitude spectrum of a resampled signal.
+pub fn spectrum(signal: &Resampled, options: &SpectrumOptions) -> Option<Spectrum> {
+    let segment_size = largest_power_of_two(options.segment_size.min(signal.len()));
+    if segment_size < 4 {
+        return None;
+    }
+
+    let window = options.window.coefficients(segment_size);
+    let window_sum: f64 = window.iter().sum();
+    let overlap = options.overlap.clamp(0.0, 0.9);
+    let hop = ((segment_size as f64 * (1.0 - overlap)) as usize).max(1);
+
+    let bins = segment_size / 2 + 1;
+    let mut power = vec![0.0; bins];
+    let mut segments = 0;
+    let mut start = 0;
+    while start + segment_size <= signal.len() {
+        let segment = &signal.values[start..start + segment_size];
+
+        // Remove the mean, so the DC leakage does not hide low frequencies:
+        let mean = segment.iter().sum::<f64>() / segment_size as f64;
+        let mut re: Vec<f64> = segment
+            .iter()
+            .zip(&window)
+            .map(|(v, w)| (v - mean) * w)
+            .collect();
+        let mut im = vec![0.0; segment_size];
+        fft(&mut re, &mut im);
+
+        for (bin, p) in power.iter_mut().enumerate() {
+            *p += re[bin] * re[bin] + im[bin] * im[bin];
+        }
+        segments += 1;
+        start += hop;
+    }
+
+    // Scale to single sided amplitudes:
+    let magnitudes = power
+        .iter()
+        .enumerate()
+        .map(|(bin, p)| {
+            let amplitude = (p / segments as f64).sqrt() / window_sum;
+            if bin == 0 || bin == bins - 1 {
+                amplitude
+            } else {
+                amplitude * 2.0
+            }
+        })
+        .collect();
+
+    Some(Spectrum {
+        resolution: signal.sample_rate() / segment_size as f64,
+        magnitudes,
+        segments,
+    })
+}
+
+fn largest_power_of_two(n: usize) -> usize {
+    if n == 0 {
+        0
+    } else {
+        1 << (usize::BITS - 1 - n.leading_zeros())
+    }
+}
+
+/// In place radix 2 fast fourier transform.
+///
+/// The length of the input must be a power of two.
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+    assert!(n.is_power_of_two());
+    assert_eq!(n, im.len());
+
+    // Bit reversal permutation:
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    // Butterflies:
+    let mut length = 2;
+    while length <= n {
+        let angle = -2.0 * PI / length as f64;
+        for start in (0..n).step_by(length) {
+            for k in 0..length / 2 {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let a = start + k;
+                let b = a + length / 2;
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        length <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{spectrum, SpectrumOptions, WindowFunction};
+    use crate::time::TimeStamp;
+    use crate::tsdb::resample::Resampled;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn dominant_sine() {
+        // 2 V at 50 Hz plus 0.5 V at 120 Hz, sampled at 1 kHz:
+        let step = 0.001;
+        let values = (0..4096)
+            .map(|i| {
+                let t = i as f64 * step;
+                2.0 * (2.0 * PI * 50.0 * t).sin() + 0.5 * (2.0 * PI * 120.0 * t).sin() + 3.0
+            })
+            .collect();
+        let signal = Resampled {
+            start: TimeStamp::new(0.0),
+            step,
+            values,
+        };
+
+        let options = SpectrumOptions::default();
+        let result = spectrum(&signal, &options).unwrap();
+        assert_eq!(result.magnitudes.len(), 513);
+        assert_eq!(result.segments, 7);
+
+        let (frequency, magnitude) = result.dominant_frequency().unwrap();
+        assert!((frequency - 50.0).abs() < 0.5);
+        assert!((magnitude - 2.0).abs() < 0.5);
+
+        // The rectangular window is exact for a bin centered frequency:
+        let signal = Resampled {
+            values: (0..4096)
+                .map(|i| (2.0 * PI * 125.0 * i as f64 * step).cos())
+                .collect(),
+            ..signal
+        };
+        let options = SpectrumOptions {
+            window: WindowFunction::Rectangular,
+            segment_size: 1000,
+            overlap: 0.0,
+        };
+        let result = spectrum(&signal, &options).unwrap();
+        assert_eq!(result.segments, 8);
+        let bin = (125.0 / result.resolution).round() as usize;
+        assert!((result.magnitudes[bin] - 1.0).abs() < 1.0e-9);
+        assert!(result.magnitudes[bin + 1] < 1.0e-9);
+    }
+}
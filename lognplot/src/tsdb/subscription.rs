@@ -0,0 +1,142 @@
+//! Subscriptions on the data of selected signals.
+//!
+//! Other than the `ChangeSubscriber`, which only reports which
+//! signals changed, a `DataSubscriber` receives the newly added
+//! observations themselves, but only of signals matching a pattern.
+
+use super::namespace::GlobPattern;
+use super::observations::{Observation, ProfileEvent, Sample, Text};
+use futures::channel::mpsc;
+
+/// Observations which were added to a signal.
+#[derive(Debug, Clone)]
+pub enum NewObservations {
+    Values(Vec<Observation<Sample>>),
+    Texts(Vec<Observation<Text>>),
+    Profile(Vec<Observation<ProfileEvent>>),
+}
+
+impl NewObservations {
+    pub fn len(&self) -> usize {
+        match self {
+            NewObservations::Values(observations) => observations.len(),
+            NewObservations::Texts(observations) => observations.len(),
+            NewObservations::Profile(observations) => observations.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A batch of new observations of a single signal.
+#[derive(Debug, Clone)]
+pub struct DataBatch {
+    /// The signal to which the observations were added.
+    pub name: String,
+
+    pub observations: NewObservations,
+
+    /// Amount of batches dropped before this batch, because the
+    /// receiver did not keep up. Zero when nothing was lost.
+    pub dropped: usize,
+}
+
+/// Delivers new observations of signals matching a glob pattern.
+#[derive(Debug)]
+pub struct DataSubscriber {
+    pattern: GlobPattern,
+    channel: mpsc::Sender<DataBatch>,
+    dropped: usize,
+    connected: bool,
+}
+
+impl DataSubscriber {
+    /// Create a subscriber for signals matching the pattern, and a
+    /// queue which buffers at most `capacity` batches.
+    pub fn new(pattern: &str, capacity: usize) -> (Self, mpsc::Receiver<DataBatch>) {
+        // The channel has room for one extra message per sender.
+        let (sender, receiver) = mpsc::channel(capacity.saturating_sub(1));
+        let subscriber = DataSubscriber {
+            pattern: GlobPattern::new(pattern),
+            channel: sender,
+            dropped: 0,
+            connected: true,
+        };
+        (subscriber, receiver)
+    }
+
+    /// Test if this subscriber is interested in the given signal.
+    pub fn matches(&self, name: &str) -> bool {
+        self.connected && self.pattern.matches(name)
+    }
+
+    /// Test if the receiving end of the queue still exists.
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Send new observations of a signal.
+    ///
+    /// When the queue is full, the batch is dropped, and counted
+    /// in the next batch which fits in the queue.
+    pub fn send(&mut self, name: &str, observations: NewObservations) {
+        let batch = DataBatch {
+            name: name.to_owned(),
+            observations,
+            dropped: self.dropped,
+        };
+
+        match self.channel.try_send(batch) {
+            Ok(()) => {
+                self.dropped = 0;
+            }
+            Err(err) => {
+                if err.is_full() {
+                    self.dropped += 1;
+                } else {
+                    self.connected = false;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DataSubscriber, NewObservations};
+    use crate::time::TimeStamp;
+    use crate::tsdb::observations::{Observation, Sample};
+
+    fn values(count: usize) -> NewObservations {
+        let observation = Observation::new(TimeStamp::from_seconds(0), Sample::new(1.0));
+        NewObservations::Values(vec![observation; count])
+    }
+
+    #[test]
+    fn overflow_is_reported() {
+        let (mut subscriber, mut receiver) = DataSubscriber::new("robot/**", 2);
+        assert!(subscriber.matches("robot/arm/torque"));
+        assert!(!subscriber.matches("battery"));
+
+        for count in 1..=4 {
+            subscriber.send("robot/arm/torque", values(count));
+        }
+
+        let batch = receiver.try_next().unwrap().unwrap();
+        assert_eq!(batch.observations.len(), 1);
+        assert_eq!(batch.dropped, 0);
+        receiver.try_next().unwrap().unwrap();
+        assert!(receiver.try_next().is_err());
+
+        subscriber.send("robot/arm/torque", values(5));
+        let batch = receiver.try_next().unwrap().unwrap();
+        assert_eq!(batch.observations.len(), 5);
+        assert_eq!(batch.dropped, 2);
+
+        drop(receiver);
+        subscriber.send("robot/arm/torque", values(1));
+        assert!(!subscriber.is_connected());
+    }
+}
@@ -0,0 +1,275 @@
+//! Full text search over text tracks.
+//!
+//! Each text track has an inverted index, from lower case words to
+//! the moments at which they occur. Word queries, and substring
+//! queries which contain complete words, only visit the texts at
+//! those moments. Other queries scan the whole track.
+
+use super::observations::{CountMetrics, Observation, Text};
+use super::Trace;
+use crate::time::{TimeSpan, TimeStamp};
+use regex::Regex;
+use std::collections::HashMap;
+
+/// What to search for in texts.
+#[derive(Debug, Clone)]
+pub enum TextQuery {
+    /// Texts containing this exact piece of text.
+    Substring(String),
+
+    /// Texts matching a regular expression.
+    Regex(Regex),
+
+    /// Texts containing all of these words, ignoring case.
+    Tokens(Vec<String>),
+}
+
+impl TextQuery {
+    pub fn substring(text: &str) -> Self {
+        TextQuery::Substring(text.to_owned())
+    }
+
+    pub fn regex(pattern: &str) -> Result<Self, regex::Error> {
+        Ok(TextQuery::Regex(Regex::new(pattern)?))
+    }
+
+    /// Search for texts containing all words in the given text.
+    pub fn tokens(text: &str) -> Self {
+        TextQuery::Tokens(tokenize(text).collect())
+    }
+
+    /// Test if a text matches this query.
+    pub fn matches(&self, text: &str) -> bool {
+        match self {
+            TextQuery::Substring(substring) => text.contains(substring.as_str()),
+            TextQuery::Regex(regex) => regex.is_match(text),
+            TextQuery::Tokens(tokens) => {
+                let words: Vec<String> = tokenize(text).collect();
+                tokens.iter().all(|token| words.contains(token))
+            }
+        }
+    }
+
+    /// Words which each matching text must contain.
+    fn required_tokens(&self) -> Vec<String> {
+        match self {
+            TextQuery::Substring(substring) => {
+                // The words at the edges may be part of longer words:
+                let mut tokens: Vec<String> = tokenize(substring).collect();
+                if substring.ends_with(is_token_char) {
+                    tokens.pop();
+                }
+                if substring.starts_with(is_token_char) && !tokens.is_empty() {
+                    tokens.remove(0);
+                }
+                tokens
+            }
+            TextQuery::Regex(_) => vec![],
+            TextQuery::Tokens(tokens) => tokens.clone(),
+        }
+    }
+}
+
+/// A text observation which matched a search.
+#[derive(Debug, Clone)]
+pub struct TextHit {
+    /// Name of the signal containing the text.
+    pub name: String,
+
+    pub timestamp: TimeStamp,
+
+    pub text: String,
+}
+
+/// Split a text into lower case words.
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !is_token_char(c))
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+}
+
+fn is_token_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Inverted index of a single text track.
+#[derive(Debug, Default)]
+pub struct TextIndex {
+    /// Per word, the sorted moments of texts containing it.
+    postings: HashMap<String, Vec<TimeStamp>>,
+
+    /// Estimate of the memory used by the postings.
+    bytes: usize,
+}
+
+impl TextIndex {
+    /// Add a text, which must not be older than the texts added before.
+    pub fn add(&mut self, observation: &Observation<Text>) {
+        for token in tokenize(&observation.value.text) {
+            if !self.postings.contains_key(&token) {
+                self.bytes += posting_size(&token, 0);
+            }
+            let moments = self.postings.entry(token).or_default();
+            if moments.last() != Some(&observation.timestamp) {
+                moments.push(observation.timestamp.clone());
+                self.bytes += std::mem::size_of::<TimeStamp>();
+            }
+        }
+    }
+
+    /// Estimate the memory used by this index.
+    pub fn memory_usage(&self) -> usize {
+        self.bytes
+    }
+
+    /// The moments of texts which contain all given words.
+    pub fn candidates(&self, tokens: &[String]) -> Vec<TimeStamp> {
+        let mut lists = vec![];
+        for token in tokens {
+            if let Some(moments) = self.postings.get(token) {
+                lists.push(moments);
+            } else {
+                return vec![];
+            }
+        }
+        lists.sort_by_key(|moments| moments.len());
+
+        let (shortest, others) = if let Some((shortest, others)) = lists.split_first() {
+            (shortest, others)
+        } else {
+            return vec![];
+        };
+        shortest
+            .iter()
+            .filter(|moment| {
+                others.iter().all(|moments| {
+                    moments
+                        .binary_search_by(|m| m.amount.total_cmp(&moment.amount))
+                        .is_ok()
+                })
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Forget texts before the given moment, after the oldest texts were evicted.
+    pub fn prune_before(&mut self, timestamp: &TimeStamp) {
+        for moments in self.postings.values_mut() {
+            let first_kept = moments
+                .iter()
+                .position(|moment| moment >= timestamp)
+                .unwrap_or(moments.len());
+            moments.drain(..first_kept);
+        }
+        self.postings.retain(|_, moments| !moments.is_empty());
+        self.bytes = self
+            .postings
+            .iter()
+            .map(|(token, moments)| posting_size(token, moments.len()))
+            .sum();
+    }
+}
+
+/// Memory used by the moments of a word in the index.
+fn posting_size(token: &str, moments: usize) -> usize {
+    std::mem::size_of::<(String, Vec<TimeStamp>)>()
+        + token.len()
+        + moments * std::mem::size_of::<TimeStamp>()
+}
+
+impl Trace<Text, CountMetrics> {
+    /// Find up to `limit` texts matching a query, oldest first.
+    pub fn search(
+        &self,
+        index: Option<&TextIndex>,
+        query: &TextQuery,
+        timespan: &TimeSpan,
+        limit: usize,
+    ) -> Vec<Observation<Text>> {
+        let predicate = |observation: &Observation<Text>| query.matches(&observation.value.text);
+        let tokens = query.required_tokens();
+        match index {
+            Some(index) if !tokens.is_empty() => {
+                let mut found = vec![];
+                for moment in index.candidates(&tokens) {
+                    if found.len() >= limit {
+                        break;
+                    }
+                    if timespan.contains(&moment) {
+                        let moment = TimeSpan::new(moment.clone(), moment);
+                        found.extend(self.find_all(
+                            &moment,
+                            &|_| true,
+                            &predicate,
+                            limit - found.len(),
+                        ));
+                    }
+                }
+                found
+            }
+            _ => self.find_all(timespan, &|_| true, &predicate, limit),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TextIndex, TextQuery};
+    use crate::time::{TimeSpan, TimeStamp};
+    use crate::tsdb::observations::{CountMetrics, Observation, Text};
+    use crate::tsdb::Trace;
+
+    #[test]
+    fn indexed_search() {
+        let texts = [
+            "open(\"/etc/passwd\", O_RDONLY) = 3",
+            "read(3, ...) = 512",
+            "Connection refused by peer",
+            "open(\"/tmp/x\", O_RDONLY) = -1 ENOENT",
+            "close(3) = 0",
+        ];
+        let mut trace = Trace::<Text, CountMetrics>::default();
+        let mut index = TextIndex::default();
+        for (i, text) in texts.iter().enumerate() {
+            let observation =
+                Observation::new(TimeStamp::new(i as f64), Text::new(text.to_string()));
+            index.add(&observation);
+            trace.add_observation(observation);
+        }
+
+        let all = TimeSpan::new(TimeStamp::new(0.0), TimeStamp::new(10.0));
+        let moments = |query: &TextQuery| -> Vec<f64> {
+            let indexed = trace.search(Some(&index), query, &all, 10);
+            let scanned = trace.search(None, query, &all, 10);
+            assert_eq!(indexed.len(), scanned.len());
+            indexed.iter().map(|o| o.timestamp.amount).collect()
+        };
+
+        assert_eq!(moments(&TextQuery::tokens("o_rdonly OPEN")), vec![0.0, 3.0]);
+        assert_eq!(moments(&TextQuery::substring("n refused b")), vec![2.0]);
+        assert_eq!(moments(&TextQuery::substring("(3")), vec![1.0, 4.0]);
+        assert_eq!(
+            moments(&TextQuery::regex(r"= -?\d+$").unwrap()),
+            vec![0.0, 1.0, 4.0]
+        );
+        assert!(moments(&TextQuery::tokens("missing")).is_empty());
+
+        // Limits and time spans are respected:
+        let query = TextQuery::tokens("open");
+        assert_eq!(trace.search(Some(&index), &query, &all, 1).len(), 1);
+        let late = TimeSpan::new(TimeStamp::new(1.0), TimeStamp::new(10.0));
+        assert_eq!(trace.search(Some(&index), &query, &late, 10).len(), 1);
+
+        index.prune_before(&TimeStamp::new(1.0));
+        assert_eq!(index.candidates(&["open".to_owned()]).len(), 1);
+
+        // A text without a valid moment does not bring down the search:
+        let observation = Observation::new(
+            TimeStamp::new(f64::NAN),
+            Text::new("open(3) = 0".to_owned()),
+        );
+        index.add(&observation);
+        let words = ["open".to_owned(), "0".to_owned()];
+        assert_eq!(index.candidates(&words).len(), 1);
+    }
+}
@@ -4,8 +4,9 @@
 //! or leaf chunks, with real data.
 //! Also: keep track of certain metrics, such as min, max and sum.
 
+use super::memory::{HeapSize, MemoryUsage};
 use super::observations::{Aggregation, Metrics, Observation};
-use super::{Btree, Query, RangeQueryResult};
+use super::{Btree, Query, RangeQueryResult, SearchDirection};
 use crate::time::TimeSpan;
 
 /// A trace is a single signal with a history in time.
@@ -17,17 +18,24 @@ where
     tree: Btree<V, M>,
     count: usize,
     last: Option<Observation<V>>,
+
+    /// Heap memory of the observations, such as texts.
+    string_bytes: usize,
 }
 
 impl<V, M> Trace<V, M>
 where
-    V: Clone,
+    V: Clone + HeapSize,
     M: Metrics<V> + From<V> + Clone,
 {
     /// Add a vector of values to this trace.
     pub fn add_observations(&mut self, observations: Vec<Observation<V>>) {
         if !observations.is_empty() {
             self.count += observations.len();
+            self.string_bytes += observations
+                .iter()
+                .map(|o| o.value.heap_size())
+                .sum::<usize>();
             self.last = Some(
                 observations
                     .last()
@@ -41,6 +49,7 @@ where
     /// Add a single observation.
     pub fn add_observation(&mut self, observation: Observation<V>) {
         self.count += 1;
+        self.string_bytes += observation.value.heap_size();
         self.last = Some(observation.clone());
         self.tree.append_sample(observation);
     }
@@ -50,6 +59,11 @@ where
         self.tree.query_range(&query.interval, query.amount)
     }
 
+    /// The amount of observations in this trace.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
     pub fn quick_summary(&self) -> Option<(usize, Observation<V>)> {
         if let Some(last) = &self.last {
             Some((self.count, last.clone()))
@@ -69,6 +83,68 @@ where
     pub fn to_vec(&self) -> Vec<Observation<V>> {
         self.tree.to_vec()
     }
+
+    /// Search for an observation, see `Btree::find`.
+    pub fn find<P, F>(
+        &self,
+        timespan: &TimeSpan,
+        direction: SearchDirection,
+        may_contain: &P,
+        predicate: &F,
+    ) -> Option<Observation<V>>
+    where
+        P: Fn(&Aggregation<V, M>) -> bool,
+        F: Fn(&Observation<V>) -> bool,
+    {
+        self.tree.find(timespan, direction, may_contain, predicate)
+    }
+
+    /// Search for all observations matching a predicate, see `Btree::find_all`.
+    pub fn find_all<P, F>(
+        &self,
+        timespan: &TimeSpan,
+        may_contain: &P,
+        predicate: &F,
+        limit: usize,
+    ) -> Vec<Observation<V>>
+    where
+        P: Fn(&Aggregation<V, M>) -> bool,
+        F: Fn(&Observation<V>) -> bool,
+    {
+        self.tree.find_all(timespan, may_contain, predicate, limit)
+    }
+
+    /// Estimate the memory used by this trace.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let (observations, aggregations) = Btree::<V, M>::memory_estimate(self.count);
+        MemoryUsage {
+            observations,
+            aggregations,
+            strings: self.string_bytes,
+            index: 0,
+        }
+    }
+
+    /// Replace the oldest `amount` observations by the result of `reduce`.
+    ///
+    /// The tree is rebuilt from the remaining observations. Returns
+    /// false when this did not make the trace any smaller.
+    pub fn reduce_oldest<F>(&mut self, amount: usize, reduce: F) -> bool
+    where
+        F: FnOnce(Vec<Observation<V>>) -> Vec<Observation<V>>,
+    {
+        let mut observations = self.to_vec();
+        let newest = observations.split_off(amount.min(observations.len()));
+        let reduced = reduce(observations);
+        if reduced.len() + newest.len() >= self.count {
+            return false;
+        }
+
+        *self = Self::default();
+        self.add_observations(reduced);
+        self.add_observations(newest);
+        true
+    }
 }
 
 impl<V, M> Default for Trace<V, M>
@@ -83,6 +159,7 @@ where
             tree,
             count: 0,
             last: None,
+            string_bytes: 0,
         }
     }
 }
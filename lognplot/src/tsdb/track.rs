@@ -1,3 +1,4 @@
+use super::memory::{average_pairs, EvictionPolicy, MemoryUsage};
 use super::observations::{CountMetrics, Observation, ProfileEvent, Sample, SampleMetrics, Text};
 use super::trace::Trace;
 use super::TrackType;
@@ -93,6 +94,50 @@ impl Track {
         }
     }
 
+    pub fn memory_usage(&self) -> MemoryUsage {
+        match self {
+            Track::Value(trace) => trace.memory_usage(),
+            Track::Text(trace) => trace.memory_usage(),
+            Track::Profile(trace) => trace.memory_usage(),
+        }
+    }
+
+    /// The amount of observations in this track.
+    pub fn count(&self) -> usize {
+        match self {
+            Track::Value(trace) => trace.count(),
+            Track::Text(trace) => trace.count(),
+            Track::Profile(trace) => trace.count(),
+        }
+    }
+
+    /// Shrink the oldest part of this track according to the policy,
+    /// to free about the given amount of bytes.
+    ///
+    /// Returns false when the track cannot be reduced any further.
+    pub fn reduce(&mut self, policy: EvictionPolicy, bytes: usize) -> bool {
+        let count = self.count();
+        let usage = self.memory_usage().total();
+        if count == 0 || usage == 0 {
+            return false;
+        }
+
+        // Assume each observation takes the same amount of memory:
+        let amount = (count as f64 * bytes as f64 / usage as f64).ceil() as usize;
+        let amount = amount.clamp(1, count);
+        match self {
+            Track::Value(trace) => {
+                // Averaging pairs removes half of the observations it is applied to:
+                (policy == EvictionPolicy::Downsample
+                    && 2 * amount <= count
+                    && trace.reduce_oldest(2 * amount, average_pairs))
+                    || trace.reduce_oldest(amount, |_| vec![])
+            }
+            Track::Text(trace) => trace.reduce_oldest(amount, |_| vec![]),
+            Track::Profile(trace) => trace.reduce_oldest(amount, |_| vec![]),
+        }
+    }
+
     pub fn to_vec(&self) -> Vec<Observation<Sample>> {
         if let Track::Value(trace) = self {
             trace.to_vec()
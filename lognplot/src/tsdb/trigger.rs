@@ -0,0 +1,368 @@
+//! Oscilloscope style triggers.
+//!
+//! Triggers are armed on a signal, and evaluated on every
+//! value which is added to the database. Each hit is recorded
+//! as a marker, and sent to the change subscribers.
+
+use super::observations::{Observation, Sample};
+use super::CrossingEdge;
+use crate::time::{TimeSpan, TimeStamp};
+use std::collections::HashMap;
+
+/// Prefix of the text tracks which receive a marker for each trigger hit.
+pub const TRIGGER_TRACK_PREFIX: &str = "triggers";
+
+/// Amount of hits to remember per trigger.
+const MAX_HITS: usize = 100;
+
+pub type TriggerId = usize;
+
+/// The condition on which a trigger fires.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TriggerCondition {
+    /// The signal crosses a level.
+    Edge { level: f64, edge: CrossingEdge },
+
+    /// The signal stays above (positive) or below the level for
+    /// longer than the given width in seconds. Fires at the end of the pulse.
+    PulseWidth {
+        level: f64,
+        positive: bool,
+        min_width: f64,
+    },
+
+    /// The signal enters, or leaves, the window between low and high.
+    Window { low: f64, high: f64, enter: bool },
+}
+
+/// How often a trigger fires.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TriggerMode {
+    /// Fire on every hit.
+    Normal,
+
+    /// Fire once, and disarm afterwards.
+    Single,
+
+    /// Fire on every hit. Viewers should keep on
+    /// updating, even when no hits occur.
+    Auto,
+}
+
+/// A trigger definition.
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    /// The signal to watch.
+    pub signal: String,
+
+    pub condition: TriggerCondition,
+
+    pub mode: TriggerMode,
+
+    /// Seconds to capture before the trigger moment.
+    pub pre_trigger: f64,
+
+    /// Seconds to capture after the trigger moment.
+    /// This is also the hold off time before the trigger fires again.
+    pub post_trigger: f64,
+}
+
+impl Trigger {
+    pub fn new(signal: &str, condition: TriggerCondition) -> Self {
+        Trigger {
+            signal: signal.to_owned(),
+            condition,
+            mode: TriggerMode::Normal,
+            pre_trigger: 0.0,
+            post_trigger: 0.0,
+        }
+    }
+
+    /// Describe the trigger condition in a few words.
+    fn describe(&self) -> String {
+        match &self.condition {
+            TriggerCondition::Edge { level, edge } => format!("{:?} edge at {}", edge, level),
+            TriggerCondition::PulseWidth {
+                level,
+                positive,
+                min_width,
+            } => format!(
+                "{} pulse at {} longer than {} s",
+                if *positive { "positive" } else { "negative" },
+                level,
+                min_width
+            ),
+            TriggerCondition::Window { low, high, enter } => format!(
+                "{} window {} .. {}",
+                if *enter { "entering" } else { "leaving" },
+                low,
+                high
+            ),
+        }
+    }
+}
+
+/// A single moment at which a trigger fired.
+#[derive(Debug, Clone)]
+pub struct TriggerHit {
+    pub id: TriggerId,
+    pub signal: String,
+    pub timestamp: TimeStamp,
+    pub value: f64,
+
+    /// The pre / post trigger capture window, if any.
+    pub window: Option<TimeSpan>,
+
+    /// Text for the marker of this hit.
+    pub message: String,
+}
+
+/// A trigger together with its evaluation state.
+#[derive(Debug)]
+struct ArmedTrigger {
+    trigger: Trigger,
+    armed: bool,
+    previous: Option<f64>,
+    pulse_start: Option<TimeStamp>,
+    hold_off_until: Option<TimeStamp>,
+    hits: Vec<TriggerHit>,
+}
+
+impl ArmedTrigger {
+    fn new(trigger: Trigger) -> Self {
+        ArmedTrigger {
+            trigger,
+            armed: true,
+            previous: None,
+            pulse_start: None,
+            hold_off_until: None,
+            hits: vec![],
+        }
+    }
+
+    /// Evaluate the trigger condition on a new observation.
+    fn evaluate(&mut self, observation: &Observation<Sample>) -> bool {
+        let value = observation.value.value;
+        let previous = self.previous.replace(value);
+        let previous = if let Some(previous) = previous {
+            previous
+        } else {
+            return false;
+        };
+
+        match self.trigger.condition {
+            TriggerCondition::Edge { level, edge } => {
+                let rising = previous < level && value >= level;
+                let falling = previous > level && value <= level;
+                match edge {
+                    CrossingEdge::Rising => rising,
+                    CrossingEdge::Falling => falling,
+                    CrossingEdge::Any => rising || falling,
+                }
+            }
+            TriggerCondition::PulseWidth {
+                level,
+                positive,
+                min_width,
+            } => {
+                let active = |v: f64| if positive { v > level } else { v < level };
+                if !active(previous) && active(value) {
+                    self.pulse_start = Some(observation.timestamp.clone());
+                    false
+                } else if active(previous) && !active(value) {
+                    if let Some(start) = self.pulse_start.take() {
+                        observation.timestamp.amount - start.amount > min_width
+                    } else {
+                        false
+                    }
+                } else {
+                    false
+                }
+            }
+            TriggerCondition::Window { low, high, enter } => {
+                let inside = |v: f64| low <= v && v <= high;
+                if enter {
+                    !inside(previous) && inside(value)
+                } else {
+                    inside(previous) && !inside(value)
+                }
+            }
+        }
+    }
+
+    fn process(&mut self, id: TriggerId, observation: &Observation<Sample>) -> Option<TriggerHit> {
+        let condition_met = self.evaluate(observation);
+
+        if let Some(hold_off_until) = &self.hold_off_until {
+            if &observation.timestamp < hold_off_until {
+                return None;
+            }
+            self.hold_off_until = None;
+        }
+
+        if !(self.armed && condition_met) {
+            return None;
+        }
+
+        let timestamp = observation.timestamp.clone();
+        let window = if self.trigger.pre_trigger > 0.0 || self.trigger.post_trigger > 0.0 {
+            Some(TimeSpan::new(
+                timestamp.clone() - self.trigger.pre_trigger,
+                timestamp.clone() + self.trigger.post_trigger,
+            ))
+        } else {
+            None
+        };
+        let hit = TriggerHit {
+            id,
+            signal: self.trigger.signal.clone(),
+            timestamp: timestamp.clone(),
+            value: observation.value.value,
+            window,
+            message: format!("Trigger {}: {}", id, self.trigger.describe()),
+        };
+
+        self.hold_off_until = Some(timestamp + self.trigger.post_trigger);
+        if self.trigger.mode == TriggerMode::Single {
+            self.armed = false;
+        }
+
+        if self.hits.len() >= MAX_HITS {
+            self.hits.remove(0);
+        }
+        self.hits.push(hit.clone());
+        Some(hit)
+    }
+}
+
+/// Evaluates all armed triggers on incoming data.
+#[derive(Debug, Default)]
+pub struct TriggerEngine {
+    triggers: HashMap<TriggerId, ArmedTrigger>,
+    next_id: TriggerId,
+}
+
+impl TriggerEngine {
+    /// Arm a new trigger, returns an id to refer to the trigger.
+    pub fn arm(&mut self, trigger: Trigger) -> TriggerId {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.triggers.insert(id, ArmedTrigger::new(trigger));
+        id
+    }
+
+    /// Remove a trigger.
+    pub fn disarm(&mut self, id: TriggerId) {
+        self.triggers.remove(&id);
+    }
+
+    /// Get the most recent hits of a trigger.
+    pub fn hits(&self, id: TriggerId) -> Vec<TriggerHit> {
+        self.triggers
+            .get(&id)
+            .map(|t| t.hits.clone())
+            .unwrap_or_default()
+    }
+
+    /// Feed new observations of a signal into the triggers.
+    pub fn process(&mut self, name: &str, observations: &[Observation<Sample>]) -> Vec<TriggerHit> {
+        let mut hits = vec![];
+        for (id, trigger) in &mut self.triggers {
+            if trigger.trigger.signal == name {
+                for observation in observations {
+                    if let Some(hit) = trigger.process(*id, observation) {
+                        hits.push(hit);
+                    }
+                }
+            }
+        }
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CrossingEdge, Trigger, TriggerCondition, TriggerEngine, TriggerMode};
+    use crate::time::TimeStamp;
+    use crate::tsdb::observations::{Observation, Sample};
+
+    fn observations(values: &[f64]) -> Vec<Observation<Sample>> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| Observation::new(TimeStamp::from_seconds(i as isize), Sample::new(*v)))
+            .collect()
+    }
+
+    fn hit_times(engine: &mut TriggerEngine, trigger: Trigger, values: &[f64]) -> Vec<f64> {
+        let id = engine.arm(trigger);
+        let hits = engine.process("x", &observations(values));
+        engine.disarm(id);
+        hits.iter().map(|h| h.timestamp.amount).collect()
+    }
+
+    #[test]
+    fn edge_trigger() {
+        let mut engine = TriggerEngine::default();
+        let values = [0.0, 2.0, 0.0, 2.0, 0.0];
+        let rising = TriggerCondition::Edge {
+            level: 1.0,
+            edge: CrossingEdge::Rising,
+        };
+        let mut trigger = Trigger::new("x", rising);
+        assert_eq!(
+            hit_times(&mut engine, trigger.clone(), &values),
+            vec![1.0, 3.0]
+        );
+
+        trigger.mode = TriggerMode::Single;
+        assert_eq!(hit_times(&mut engine, trigger.clone(), &values), vec![1.0]);
+
+        trigger.mode = TriggerMode::Normal;
+        trigger.post_trigger = 2.5;
+        assert_eq!(hit_times(&mut engine, trigger, &values), vec![1.0]);
+
+        let other = Trigger::new(
+            "y",
+            TriggerCondition::Window {
+                low: 1.0,
+                high: 3.0,
+                enter: true,
+            },
+        );
+        assert!(hit_times(&mut engine, other, &values).is_empty());
+    }
+
+    #[test]
+    fn pulse_width_trigger() {
+        let mut engine = TriggerEngine::default();
+        let values = [0.0, 2.0, 0.0, 2.0, 2.0, 2.0, 0.0];
+        let condition = TriggerCondition::PulseWidth {
+            level: 1.0,
+            positive: true,
+            min_width: 2.0,
+        };
+        let trigger = Trigger::new("x", condition);
+        assert_eq!(hit_times(&mut engine, trigger, &values), vec![6.0]);
+    }
+
+    #[test]
+    fn window_trigger() {
+        let mut engine = TriggerEngine::default();
+        let values = [0.0, 2.0, 5.0, 2.0, 0.0];
+        let condition = TriggerCondition::Window {
+            low: 1.0,
+            high: 3.0,
+            enter: false,
+        };
+        let mut trigger = Trigger::new("x", condition);
+        trigger.pre_trigger = 1.0;
+        let id = engine.arm(trigger);
+        let hits = engine.process("x", &observations(&values));
+        assert_eq!(hits.len(), 2);
+        let window = hits[0].window.as_ref().unwrap();
+        assert_eq!(window.start.amount, 1.0);
+        assert_eq!(window.end.amount, 2.0);
+        assert_eq!(engine.hits(id).len(), 2);
+    }
+}
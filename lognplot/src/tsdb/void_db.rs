@@ -1,14 +1,21 @@
 //! A dummy database implementation, which does nothing, and drops all data into void.
-//! 
-//! 
-
+//!
+//!
 
+use super::handle::{make_handle, LockedTsDb};
 use super::observations::{Observation, ProfileEvent, Sample, Text};
 use super::ChangeSubscriber;
+use super::DataSubscriber;
+use super::SignalMetadata;
+use super::{AlarmRule, Annotation, ClockModel};
+use super::{CrossStatistics, Resampled, Spectrum, SpectrumOptions};
+use super::{CrossingEdge, Extremes, SearchDirection, ValuePredicate};
+use super::{IngestStatistics, MemoryBudget, MemoryUsage};
 use super::{Query, QueryResult};
 use super::{QuickSummary, Summary};
+use super::{TextHit, TextQuery};
+use super::{Trigger, TriggerHit, TriggerId};
 use crate::time::TimeSpan;
-use super::handle::{LockedTsDb, make_handle};
 
 use super::TsDbApi;
 
@@ -35,11 +42,20 @@ impl TsDbApi for VoidDb {
 
     fn delete_all(&mut self) {}
     fn delete(&mut self, _name: &str) {}
+    fn delete_matching(&mut self, _pattern: &str) {}
 
     fn get_signal_names(&self) -> Vec<String> {
         vec![]
     }
 
+    fn get_child_names(&self, _prefix: &str) -> Vec<String> {
+        vec![]
+    }
+
+    fn match_signal_names(&self, _pattern: &str) -> Vec<String> {
+        vec![]
+    }
+
     fn quick_summary(&self, _name: &str) -> Option<QuickSummary> {
         None
     }
@@ -52,11 +68,127 @@ impl TsDbApi for VoidDb {
         None
     }
 
+    fn get_raw_range(&self, _name: &str, _timespan: &TimeSpan) -> Option<Vec<Observation<Sample>>> {
+        None
+    }
+
+    fn find_value(
+        &self,
+        _name: &str,
+        _predicate: ValuePredicate,
+        _timespan: &TimeSpan,
+        _direction: SearchDirection,
+    ) -> Option<Observation<Sample>> {
+        None
+    }
+
+    fn find_crossing(
+        &self,
+        _name: &str,
+        _level: f64,
+        _edge: CrossingEdge,
+        _timespan: &TimeSpan,
+        _direction: SearchDirection,
+    ) -> Option<Observation<Sample>> {
+        None
+    }
+
+    fn find_extremes(&self, _name: &str, _timespan: &TimeSpan) -> Option<Extremes> {
+        None
+    }
+
+    fn search_text(
+        &self,
+        _pattern: &str,
+        _query: &TextQuery,
+        _timespan: &TimeSpan,
+        _limit: usize,
+    ) -> Vec<TextHit> {
+        vec![]
+    }
+
+    fn ingest_statistics(&self, _name: &str) -> Option<IngestStatistics> {
+        None
+    }
+
+    fn resample(&self, _name: &str, _timespan: &TimeSpan, _count: usize) -> Option<Resampled> {
+        None
+    }
+
+    fn cross_statistics(
+        &self,
+        _a: &str,
+        _b: &str,
+        _timespan: &TimeSpan,
+    ) -> Option<CrossStatistics> {
+        None
+    }
+
+    fn spectrum(
+        &self,
+        _name: &str,
+        _timespan: &TimeSpan,
+        _options: &SpectrumOptions,
+    ) -> Option<Spectrum> {
+        None
+    }
+
+    fn arm_trigger(&mut self, _trigger: Trigger) -> TriggerId {
+        0
+    }
+
+    fn disarm_trigger(&mut self, _id: TriggerId) {}
+
+    fn get_trigger_hits(&self, _id: TriggerId) -> Vec<TriggerHit> {
+        vec![]
+    }
+
+    fn add_annotation(&mut self, _annotation: Annotation) {}
+
+    fn get_annotations(&self, _timespan: Option<&TimeSpan>) -> Vec<Annotation> {
+        vec![]
+    }
+
+    fn set_clock_model(&mut self, _source: &str, _model: ClockModel) {}
+    fn estimate_clock(&mut self, _source: &str) {}
+    fn assign_clock(&mut self, _pattern: &str, _source: &str) {}
+
+    fn get_clock_model(&self, _source: &str) -> Option<ClockModel> {
+        None
+    }
+
+    fn memory_usage(&self, _name: &str) -> Option<MemoryUsage> {
+        None
+    }
+
+    fn total_memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::default()
+    }
+
+    fn set_memory_budget(&mut self, _budget: Option<MemoryBudget>) {}
+
+    fn add_alarm_rule(&mut self, _rule: AlarmRule) {}
+
+    fn clear_alarm_rules(&mut self) {}
+
+    fn get_alarm_rules(&self) -> Vec<AlarmRule> {
+        vec![]
+    }
+
+    fn check_alarms(&mut self) {}
+
     fn query(&self, _name: &str, _query: Query) -> Option<QueryResult> {
         None
     }
 
+    fn set_metadata(&mut self, _name: &str, _metadata: SignalMetadata) {}
+
+    fn get_metadata(&self, _name: &str) -> Option<SignalMetadata> {
+        None
+    }
+
     // notifications
     fn register_notifier(&mut self, _subscriber: ChangeSubscriber) {}
+    fn register_data_subscriber(&mut self, _subscriber: DataSubscriber) {}
     fn poll_events(&mut self) {}
 }
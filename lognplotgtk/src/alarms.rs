@@ -0,0 +1,44 @@
+//! Alarm rules configuration and alerts.
+//!
+//! Alarm rules are loaded from a JSON file containing a list of rules, for example:
+//!
+//! ```json
+//! [
+//!     {"signal": "battery_voltage", "type": "below", "level": 10.5, "duration": 2.0},
+//!     {"name": "Heartbeat lost", "signal": "heartbeat", "type": "stale", "timeout": 1.0}
+//! ]
+//! ```
+
+use gio::prelude::*;
+use lognplot::tsdb::{Alarm, AlarmRule, TsDbHandle};
+use std::path::Path;
+
+/// Load alarm rules from a JSON file into the database.
+pub fn load_alarm_rules(db: &TsDbHandle, filename: &Path) -> std::io::Result<()> {
+    let f = std::fs::File::open(filename)?;
+    let rules: Vec<AlarmRule> = serde_json::from_reader(f)?;
+    info!("Loaded {} alarm rules from {:?}", rules.len(), filename);
+    for rule in rules {
+        db.add_alarm_rule(rule);
+    }
+    Ok(())
+}
+
+/// Show an alarm as a desktop notification.
+pub fn show_alarm(alarm: &Alarm) {
+    if let Some(application) = gio::Application::default() {
+        let notification = gio::Notification::new("lognplot alarm");
+        notification.set_body(Some(&alarm.message));
+        notification.set_priority(gio::NotificationPriority::Urgent);
+        application.send_notification(Some("lognplot-alarm"), &notification);
+    }
+}
+
+/// Setup a timer to check for signals which stopped receiving samples.
+pub fn setup_alarm_timer(db: TsDbHandle) {
+    let tick = move || {
+        db.check_alarms();
+        gtk::prelude::Continue(true)
+    };
+    glib::timeout_add_local(std::time::Duration::from_millis(250), tick);
+}
@@ -5,19 +5,22 @@ use gtk::prelude::*;
 
 use std::cell::RefCell;
 use std::rc::Rc;
-use std::time::Instant;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
 use crate::session::DashBoardItem;
 use crate::state::GuiStateHandle;
-use crate::time_tracker::TimeTracker;
 use lognplot::chart::{Chart, Curve, CurveData};
 use lognplot::geometry::Size;
 use lognplot::render::{draw_chart, CairoCanvas, ChartLayout, ChartOptions};
 use lognplot::render::{x_pixel_to_domain, x_pixels_to_domain, y_pixel_to_domain};
-use lognplot::time::{TimeSpan, TimeStamp};
+use lognplot::style::Color;
+use lognplot::time::{TimeSpan, TimeStamp, TimeTracker};
 use lognplot::tracer::{AnyTracer, Tracer};
-use lognplot::tsdb::DataChangeEvent;
 use lognplot::tsdb::TsDbHandle;
+use lognplot::tsdb::{Annotation, CrossStatistics, DataChangeEvent, SpectrumOptions};
+use lognplot::tsdb::{CrossingEdge, SearchDirection};
+use lognplot::tsdb::{Trigger, TriggerCondition, TriggerHit, TriggerId, TriggerMode};
 use std::sync::Arc;
 
 /// category10 color wheel
@@ -28,6 +31,9 @@ const CATEGORY10_COLORS: &[&str] = &[
     "#BCBD22", "#17BECF",
 ];
 
+/// In auto scope mode, sweep freely when no trigger occurred for this long.
+const SCOPE_AUTO_TIMEOUT: Duration = Duration::from_secs(1);
+
 pub type ChartStateHandle = Rc<RefCell<ChartState>>;
 
 pub struct ChartState {
@@ -44,6 +50,32 @@ pub struct ChartState {
     draw_area: gtk::DrawingArea,
     id: String,
     time_estimator: TimeTracker,
+    scope: Option<ScopeState>,
+
+    /// Show the spectrum of the signals in view, instead of the signals.
+    spectrum_mode: bool,
+
+    /// The last calculated spectrum, which is reused until the
+    /// signals, the range in view or their data change.
+    spectrum: Option<SpectrumCache>,
+
+    /// Incremented on each change of the data of the signals in this chart.
+    data_generation: u64,
+}
+
+/// A spectrum chart, and what it was calculated from.
+struct SpectrumCache {
+    signals: Vec<String>,
+    view: (f64, f64),
+    data_generation: u64,
+    chart: Chart,
+}
+
+/// Oscilloscope like view, which shows the capture window of a trigger.
+struct ScopeState {
+    mode: TriggerMode,
+    trigger: Option<(TriggerId, Trigger)>,
+    last_hit: Instant,
 }
 
 impl ChartState {
@@ -56,6 +88,7 @@ impl ChartState {
     ) -> Self {
         let mut chart = Chart::default();
         chart.set_title(id);
+        chart.show_annotations(db.clone());
         let color_wheel: Vec<String> = CATEGORY10_COLORS.iter().map(|s| (*s).to_string()).collect();
 
         info!("Chart id: {}", id);
@@ -74,6 +107,10 @@ impl ChartState {
             draw_area,
             id: id.to_owned(),
             time_estimator: TimeTracker::new(perf_tracer, id),
+            scope: None,
+            spectrum_mode: false,
+            spectrum: None,
+            data_generation: 0,
         }
     }
 
@@ -81,15 +118,21 @@ impl ChartState {
         Rc::new(RefCell::new(self))
     }
 
-    pub fn id(&self) -> &str {
-        &self.id
-    }
-
     pub fn add_curve(&mut self, name: &str) {
         // self.chart.add_curve(Curve::new());
         if !self.chart.has_signal(name) {
             let tsdb_data = CurveData::trace(name, self.db.clone());
-            let color = self.next_color();
+
+            // Prefer the color given in the signal meta data:
+            let preferred_color = self
+                .db
+                .get_metadata(name)
+                .and_then(|m| m.color)
+                .filter(|c| Color::from_str(c).is_ok());
+            let color = match preferred_color {
+                Some(color) => color,
+                None => self.next_color(),
+            };
             let curve2 = Curve::new(tsdb_data, &color);
 
             self.chart.add_curve(curve2);
@@ -128,6 +171,219 @@ impl ChartState {
         self.repaint();
     }
 
+    /// Move cursor 1 to the next or previous crossing of the level under the mouse.
+    pub fn jump_to_crossing(&mut self, direction: SearchDirection) {
+        debug!("Search {:?} for crossing", direction);
+        if self.chart.jump_to_crossing(direction) {
+            self.handle_x_axis_change();
+        } else {
+            info!("No crossing found");
+        }
+    }
+
+    /// Pan to a moment in time, and put cursor 1 there.
+    pub fn jump_to(&mut self, timestamp: TimeStamp) {
+        self.disable_tailing();
+        self.chart.jump_to(timestamp);
+        self.handle_x_axis_change();
+    }
+
+    /// Put the cursors at the minimum and maximum of the first signal in view.
+    pub fn mark_extremes(&mut self) {
+        self.chart.mark_extremes();
+        self.repaint();
+    }
+
+    /// Switch between showing signals over time, and their spectrum.
+    ///
+    /// The spectrum is calculated over the time range in view.
+    pub fn toggle_spectrum_mode(&mut self) {
+        self.spectrum_mode = !self.spectrum_mode;
+        info!("Spectrum mode: {}", self.spectrum_mode);
+        self.repaint();
+    }
+
+    /// Calculate the spectrum of the signals in view again, when needed.
+    fn update_spectrum(&mut self) {
+        let signals: Vec<String> = self.chart.curves.iter().map(|c| c.name()).collect();
+        let view = (self.chart.x_axis.begin(), self.chart.x_axis.end());
+        let data_generation = self.data_generation;
+        let valid = self.spectrum.as_ref().map_or(false, |cache| {
+            cache.signals == signals
+                && cache.view == view
+                && cache.data_generation == data_generation
+        });
+        if !valid {
+            self.spectrum = Some(SpectrumCache {
+                signals,
+                view,
+                data_generation,
+                chart: self.chart.spectrum_chart(&SpectrumOptions::default()),
+            });
+        }
+    }
+
+    /// Compare the first two signals in the chart, between cursor 1 and 2.
+    ///
+    /// Returns the names of the signals and their statistics, if any.
+    pub fn cross_statistics(&self) -> Option<(String, String, Option<CrossStatistics>)> {
+        let cursor1 = self.chart.cursor1.clone()?;
+        let cursor2 = self.chart.cursor2.clone()?;
+        let timespan = if cursor1 < cursor2 {
+            TimeSpan::new(cursor1, cursor2)
+        } else {
+            TimeSpan::new(cursor2, cursor1)
+        };
+
+        if self.chart.curves.len() < 2 {
+            return None;
+        }
+        let a = self.chart.curves[0].name();
+        let b = self.chart.curves[1].name();
+        let statistics = self.db.cross_statistics(&a, &b, &timespan);
+        Some((a, b, statistics))
+    }
+
+    /// Add a bookmark at the mouse cursor, or spanning cursor 1 and 2 when both are set.
+    pub fn add_bookmark(&mut self) {
+        let title = format!("Bookmark {}", self.db.get_annotations(None).len() + 1);
+        let annotation = match (&self.chart.cursor1, &self.chart.cursor2, &self.chart.cursor) {
+            (Some(cursor1), Some(cursor2), _) => {
+                let (start, end) = if cursor1 < cursor2 {
+                    (cursor1.clone(), cursor2.clone())
+                } else {
+                    (cursor2.clone(), cursor1.clone())
+                };
+                Annotation::span(TimeSpan::new(start, end), &title)
+            }
+            (_, _, Some((timestamp, _))) => Annotation::point(timestamp.clone(), &title),
+            _ => {
+                return;
+            }
+        };
+        info!("Adding {}", title);
+        self.db.add_annotation(annotation);
+    }
+
+    /// Select the scope mode, or disable scope mode with `None`.
+    ///
+    /// An armed trigger is re-armed with the new mode.
+    pub fn set_scope_mode(&mut self, mode: Option<TriggerMode>) {
+        let trigger = self.disarm_scope_trigger();
+        self.scope = mode.map(|mode| ScopeState {
+            mode,
+            trigger: None,
+            last_hit: Instant::now(),
+        });
+        if let Some(trigger) = trigger {
+            self.arm_trigger(trigger);
+        }
+    }
+
+    /// Arm a rising edge trigger on the first signal, at the level under the mouse.
+    ///
+    /// The capture window is the current width of the x-axis.
+    pub fn arm_scope_trigger(&mut self) {
+        let level = if let Some((_, level)) = &self.chart.cursor {
+            *level
+        } else {
+            return;
+        };
+        let curve = if let Some(curve) = self.chart.curves.first() {
+            curve
+        } else {
+            info!("No signal to trigger on");
+            return;
+        };
+        let signal = curve.name();
+
+        // The cursor level is in displayed units, trigger on raw units:
+        let level = if let Some(level) = curve.data.raw_level(level) {
+            level
+        } else {
+            info!(
+                "Cannot trigger on {}, it is displayed with zero scale",
+                signal
+            );
+            return;
+        };
+
+        let condition = TriggerCondition::Edge {
+            level,
+            edge: CrossingEdge::Rising,
+        };
+        let mut trigger = Trigger::new(&signal, condition);
+        let half_domain = self.chart.x_axis.domain() / 2.0;
+        trigger.pre_trigger = half_domain;
+        trigger.post_trigger = half_domain;
+
+        self.disarm_scope_trigger();
+        self.arm_trigger(trigger);
+    }
+
+    fn arm_trigger(&mut self, mut trigger: Trigger) {
+        if let Some(scope) = &mut self.scope {
+            info!("Arm trigger on {} in {:?} mode", trigger.signal, scope.mode);
+            trigger.mode = scope.mode;
+            let id = self.db.arm_trigger(trigger.clone());
+            scope.trigger = Some((id, trigger));
+            scope.last_hit = Instant::now();
+            self.tailing = None;
+        }
+    }
+
+    /// Disarm the scope trigger, since nobody waits for its hits after the chart is closed.
+    pub fn close(&mut self) {
+        self.disarm_scope_trigger();
+        self.scope = None;
+    }
+
+    fn disarm_scope_trigger(&mut self) -> Option<Trigger> {
+        let (id, trigger) = self.scope.as_mut()?.trigger.take()?;
+        self.db.disarm_trigger(id);
+        Some(trigger)
+    }
+
+    /// Show the capture window of a trigger hit, when we are waiting for it.
+    fn handle_trigger_hit(&mut self, hit: &TriggerHit) {
+        if let Some(scope) = &mut self.scope {
+            if let Some((id, _)) = &scope.trigger {
+                if *id == hit.id {
+                    scope.last_hit = Instant::now();
+                    if let Some(window) = &hit.window {
+                        self.chart
+                            .x_axis
+                            .set_limits(window.start.amount, window.end.amount);
+                        self.chart.fit_y_axis();
+                        self.repaint();
+                    }
+                }
+            }
+        }
+    }
+
+    /// In auto scope mode, show the latest data when no trigger occurs.
+    fn do_scope_auto(&mut self) {
+        let window = if let Some(ScopeState {
+            mode: TriggerMode::Auto,
+            trigger: Some((_, trigger)),
+            last_hit,
+        }) = &self.scope
+        {
+            if last_hit.elapsed() > SCOPE_AUTO_TIMEOUT {
+                Some(trigger.pre_trigger + trigger.post_trigger)
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        if let Some(window) = window {
+            self.zoom_to_last(window);
+        }
+    }
+
     pub fn get_session_item(&self) -> DashBoardItem {
         (&self.chart).into()
     }
@@ -143,13 +399,20 @@ impl ChartState {
 
     /// Handle data change event from database.
     pub fn handle_event(&mut self, event: &DataChangeEvent) {
+        for hit in &event.trigger_hits {
+            self.handle_trigger_hit(hit);
+        }
+
         // Check if we must update the chart:
         let update = event.delete_all
+            || event.annotations_changed
             || event
                 .changed_signals
                 .iter()
+                .chain(event.deleted_signals.iter())
                 .any(|n| self.chart.has_signal(n));
         if update {
+            self.data_generation += 1;
             if let Some(last_time) = self.chart.get_last_timestamp() {
                 self.time_estimator.update(last_time.amount);
             }
@@ -300,6 +563,7 @@ impl ChartState {
         if let Some(x) = self.tailing {
             self.zoom_to_last(x);
         }
+        self.do_scope_auto();
     }
 
     /// X axis has changed, either sync all axis, or redraw.
@@ -343,12 +607,23 @@ impl ChartState {
 
         let t1 = Instant::now();
 
-        draw_chart(
-            &self.chart,
-            &mut canvas2,
-            &mut self.chart_layout,
-            &self.chart_options,
-        );
+        if self.spectrum_mode {
+            self.update_spectrum();
+            let spectrum = self.spectrum.as_ref().unwrap();
+            draw_chart(
+                &spectrum.chart,
+                &mut canvas2,
+                &mut self.chart_layout,
+                &self.chart_options,
+            );
+        } else {
+            draw_chart(
+                &self.chart,
+                &mut canvas2,
+                &mut self.chart_layout,
+                &self.chart_options,
+            );
+        }
 
         let t2 = Instant::now();
         let draw_duration = t2 - t1;
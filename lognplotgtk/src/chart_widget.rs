@@ -6,6 +6,8 @@ use gtk::prelude::*;
 
 use super::chart_state::{ChartState, ChartStateHandle};
 use crate::state::GuiStateHandle;
+use crate::statistics_dialog::show_cross_statistics;
+use lognplot::tsdb::{SearchDirection, TriggerMode};
 
 /// Create new chart area with extra buttons around it
 /// to enable splitting in vertical and horizontal direction
@@ -18,7 +20,8 @@ pub fn create_new_chart_area(app_state: &GuiStateHandle, parent_box: gtk::Box) {
     // generate new unique chart id based on amount of charts so far:
     let chart_id = format!("chart{}", app_state.borrow().num_charts() + 1);
 
-    let chart_state1 = setup_drawing_area(draw_area, app_state.clone(), &chart_id);
+    let scope_combo = create_scope_combo();
+    let chart_state1 = setup_drawing_area(draw_area, app_state.clone(), &chart_id, &scope_combo);
 
     // Create split buttons:
     let box2 = gtk::Box::new(gtk::Orientation::Horizontal, 0);
@@ -37,6 +40,7 @@ pub fn create_new_chart_area(app_state: &GuiStateHandle, parent_box: gtk::Box) {
     let button_clear_plot = gtk::Button::new();
     button_clear_plot.set_label("Clear plot");
     box2.pack_start(&button_clear_plot, false, false, 0);
+    box2.pack_start(&scope_combo, false, false, 0);
 
     box1.pack_start(&box2, false, false, 0);
     box1.show_all();
@@ -51,20 +55,48 @@ pub fn create_new_chart_area(app_state: &GuiStateHandle, parent_box: gtk::Box) {
         split_chart(&app_state, &box1, gtk::Orientation::Horizontal);
     }));
 
-    button_close.connect_clicked(clone!(@strong box1 => move |_| {
-        info!("Close chart!");
-        close_chart(&box1);
-    }));
+    button_close.connect_clicked(
+        clone!(@strong app_state, @strong box1, @strong chart_state1 => move |_| {
+            info!("Close chart!");
+            chart_state1.borrow_mut().close();
+            app_state.borrow_mut().delete_chart(&chart_state1);
+            close_chart(&box1);
+        }),
+    );
 
     button_clear_plot.connect_clicked(clone!(@strong chart_state1 => move |_| {
         chart_state1.borrow_mut().clear_curves();
     }));
 
+    scope_combo.connect_changed(clone!(@strong chart_state1 => move |combo| {
+        let mode = match combo.active_id().as_deref() {
+            Some("normal") => Some(TriggerMode::Normal),
+            Some("single") => Some(TriggerMode::Single),
+            Some("auto") => Some(TriggerMode::Auto),
+            _ => None,
+        };
+        chart_state1.borrow_mut().set_scope_mode(mode);
+    }));
+
     parent_box.pack_start(&box1, true, true, 0);
 
     app_state.borrow_mut().add_chart(chart_state1.clone());
 }
 
+/// Create a selection box for the oscilloscope mode of a chart.
+fn create_scope_combo() -> gtk::ComboBoxText {
+    let scope_combo = gtk::ComboBoxText::new();
+    scope_combo.append(Some("off"), "Scope off");
+    scope_combo.append(Some("normal"), "Scope normal");
+    scope_combo.append(Some("single"), "Scope single");
+    scope_combo.append(Some("auto"), "Scope auto");
+    scope_combo.set_active_id(Some("off"));
+    scope_combo.set_tooltip_text(Some(
+        "Press 't' in the chart to trigger on the level under the mouse",
+    ));
+    scope_combo
+}
+
 /// Takes care of splitting the chart into two areas
 /// This is done by determining the containing box parent,
 /// and removing this box from the parent, placing a new
@@ -150,6 +182,7 @@ fn setup_drawing_area(
     draw_area: gtk::DrawingArea,
     app_state: GuiStateHandle,
     chart_id: &str,
+    scope_combo: &gtk::ComboBoxText,
 ) -> ChartStateHandle {
     // Always get mouse pointer motion:
     draw_area.add_events(gdk::EventMask::ENTER_NOTIFY_MASK);
@@ -257,7 +290,25 @@ fn setup_drawing_area(
     }));
 
     // Connect key event:
-    draw_area.connect_key_press_event(clone!(@strong chart_state => move |_, key| {
+    let scope_combo = scope_combo.clone();
+    draw_area.connect_key_press_event(clone!(@strong chart_state => move |draw_area, key| {
+            if key.keyval() == gdk::keys::constants::c {
+                // Release the chart state before running the dialog.
+                let result = chart_state.borrow().cross_statistics();
+                let top_level = draw_area.toplevel().and_then(|w| w.downcast::<gtk::Window>().ok());
+                show_cross_statistics(top_level.as_ref(), result);
+                return Inhibit(true);
+            }
+
+            if key.keyval() == gdk::keys::constants::t {
+                // Switch on scope mode first, this borrows the chart state.
+                if scope_combo.active_id().as_deref() == Some("off") {
+                    scope_combo.set_active_id(Some("normal"));
+                }
+                chart_state.borrow_mut().arm_scope_trigger();
+                return Inhibit(true);
+            }
+
             let mut chart = chart_state.borrow_mut();
 
             chart.disable_tailing();
@@ -298,6 +349,21 @@ fn setup_drawing_area(
                 gdk::keys::constants::_2 => {
                     chart.set_cursor2();
                 }
+                gdk::keys::constants::n => {
+                    chart.jump_to_crossing(SearchDirection::Forward);
+                }
+                gdk::keys::constants::p => {
+                    chart.jump_to_crossing(SearchDirection::Backward);
+                }
+                gdk::keys::constants::x => {
+                    chart.mark_extremes();
+                }
+                gdk::keys::constants::b => {
+                    chart.add_bookmark();
+                }
+                gdk::keys::constants::f => {
+                    chart.toggle_spectrum_mode();
+                }
                 other_key => {
                     println!("Key! {:?}", other_key);
                 }
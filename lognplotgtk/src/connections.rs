@@ -0,0 +1,115 @@
+//! Panel listing the clients connected to the server.
+//!
+//! The statistics are refreshed every second. The selected client can
+//! be disconnected with the kick button.
+
+use gtk::prelude::*;
+use lognplot::net::{PeerId, PeerInfo, PeerRegistry};
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+/// Column holding the id of a peer, as a number.
+const ID_COLUMN: i32 = 0;
+
+pub fn setup_connections(builder: &gtk::Builder, peers: PeerRegistry) {
+    let view: gtk::TreeView = builder.object("connections_view").unwrap();
+    let kick_button: gtk::Button = builder.object("kick_button").unwrap();
+
+    // Columns: id, client, address, connected, bytes, samples, errors, idle
+    let model = gtk::ListStore::new(&[
+        u64::static_type(),
+        String::static_type(),
+        String::static_type(),
+        String::static_type(),
+        String::static_type(),
+        String::static_type(),
+        String::static_type(),
+        String::static_type(),
+    ]);
+    view.set_model(Some(&model));
+    setup_columns(builder);
+
+    kick_button.connect_clicked(clone!(@strong model, @strong peers => move |_| {
+        if let Some((_, iter)) = view.selection().selected() {
+            if let Ok(peer) = model.value(&iter, ID_COLUMN).get::<u64>() {
+                peers.kick(peer);
+                refresh(&model, &peers);
+            }
+        }
+    }));
+
+    refresh(&model, &peers);
+    let tick = move || {
+        refresh(&model, &peers);
+        gtk::prelude::Continue(true)
+    };
+    glib::timeout_add_local(std::time::Duration::from_secs(1), tick);
+}
+
+/// Update the rows in place, so that the selection is kept.
+fn refresh(model: &gtk::ListStore, peers: &PeerRegistry) {
+    let mut peers: BTreeMap<PeerId, PeerInfo> = peers
+        .peers()
+        .into_iter()
+        .map(|info| (info.id, info))
+        .collect();
+
+    if let Some(iter) = model.iter_first() {
+        loop {
+            let peer = model.value(&iter, ID_COLUMN).get::<u64>().unwrap();
+            let more = if let Some(info) = peers.remove(&peer) {
+                set_peer_columns(model, &iter, &info);
+                model.iter_next(&iter)
+            } else {
+                model.remove(&iter)
+            };
+            if !more {
+                break;
+            }
+        }
+    }
+
+    for info in peers.values() {
+        let iter = model.append();
+        set_peer_columns(model, &iter, info);
+    }
+}
+
+fn set_peer_columns(model: &gtk::ListStore, iter: &gtk::TreeIter, info: &PeerInfo) {
+    let now = SystemTime::now();
+    let seconds_since = |time: SystemTime| {
+        now.duration_since(time)
+            .map(|duration| duration.as_secs_f64())
+            .unwrap_or(0.0)
+    };
+    model.set(
+        iter,
+        &[
+            (ID_COLUMN as u32, &info.id),
+            (1, &info.client.clone().unwrap_or_default()),
+            (2, &info.address),
+            (3, &format!("{:.0} s", seconds_since(info.connected))),
+            (4, &info.bytes.to_string()),
+            (5, &info.samples.to_string()),
+            (6, &info.errors.to_string()),
+            (7, &format!("{:.1} s", seconds_since(info.last_activity))),
+        ],
+    );
+}
+
+fn setup_columns(builder: &gtk::Builder) {
+    for (column_id, index) in &[
+        ("column_peer_client", 1),
+        ("column_peer_address", 2),
+        ("column_peer_connected", 3),
+        ("column_peer_bytes", 4),
+        ("column_peer_samples", 5),
+        ("column_peer_errors", 6),
+        ("column_peer_idle", 7),
+    ] {
+        let column: gtk::TreeViewColumn = builder.object(column_id).unwrap();
+        let cell = gtk::CellRendererText::new();
+        column.pack_start(&cell, true);
+        column.add_attribute(&cell, "text", *index);
+    }
+}
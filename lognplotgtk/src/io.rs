@@ -7,10 +7,11 @@
 use super::error_dialog::show_error;
 use super::GuiStateHandle;
 use gtk::prelude::*;
-use lognplot::time::TimeStamp;
+use lognplot::time::{TimeSpan, TimeStamp};
 use lognplot::tsdb::observations::{Observation, Sample};
-use lognplot::tsdb::TsDbHandle;
+use lognplot::tsdb::{Annotation, SignalMetadata, TsDbHandle};
 use std::path::Path;
+use std::str::FromStr;
 
 /// Popup a dialog and export data as HDF5 format.
 pub fn save_data_as_hdf5(top_level: &gtk::Window, app_state: &GuiStateHandle) {
@@ -68,16 +69,19 @@ pub fn load_data_from_hdf5(top_level: &gtk::Window, app_state: &GuiStateHandle)
     }
 }
 
-pub fn export_data(db: TsDbHandle, filename: &Path) -> hdf5::Result<()> {
+/// Export the signals matching the glob pattern into a HDF5 file.
+///
+/// Use `**` as pattern to export all signals.
+pub fn export_data(db: TsDbHandle, filename: &Path, pattern: &str) -> hdf5::Result<()> {
     let file = hdf5::File::create(filename)?;
-    export_db(db, &file)
+    export_db(db, &file, pattern)
 }
 
-/// Export all signals from the database into a HDF5 file.
-fn export_db(db: TsDbHandle, file: &hdf5::File) -> hdf5::Result<()> {
+/// Export signals matching a pattern from the database into a HDF5 file.
+fn export_db(db: TsDbHandle, file: &hdf5::File, pattern: &str) -> hdf5::Result<()> {
     let group = file.create_group("my_datorz")?;
 
-    let signal_names = db.get_signal_names();
+    let signal_names = db.match_signal_names(pattern);
     for signal_name in signal_names {
         // db
         if let Some(data) = db.get_raw_samples(&signal_name) {
@@ -96,16 +100,173 @@ fn export_db(db: TsDbHandle, file: &hdf5::File) -> hdf5::Result<()> {
             let signal = ndarray::arr2(&data);
 
             // Create hdf5 dataset for this signal:
-            group
+            let dataset = group
                 .new_dataset_builder()
                 .with_data(&signal)
                 .create(signal_name.as_str())?;
+
+            if let Some(metadata) = db.get_metadata(&signal_name) {
+                export_metadata(&dataset, &metadata)?;
+            }
+        }
+    }
+
+    export_annotations(&db, file)?;
+
+    Ok(())
+}
+
+/// Store annotations as a dataset of time spans, with titles and colors as attributes.
+fn export_annotations(db: &TsDbHandle, file: &hdf5::File) -> hdf5::Result<()> {
+    let annotations = db.get_annotations(None);
+    if annotations.is_empty() {
+        return Ok(());
+    }
+
+    let timespans: Vec<[f64; 2]> = annotations
+        .iter()
+        .map(|a| [a.timespan.start.amount, a.timespan.end.amount])
+        .collect();
+    let dataset = file
+        .new_dataset_builder()
+        .with_data(&ndarray::arr2(&timespans))
+        .create("annotations")?;
+
+    let titles = annotations
+        .iter()
+        .map(|a| to_hdf5_string(&a.title))
+        .collect::<hdf5::Result<Vec<_>>>()?;
+    dataset
+        .new_attr_builder()
+        .with_data(titles.as_slice())
+        .create("titles")?;
+
+    // Annotations without color are stored as an empty string.
+    let colors = annotations
+        .iter()
+        .map(|a| to_hdf5_string(a.color.as_deref().unwrap_or_default()))
+        .collect::<hdf5::Result<Vec<_>>>()?;
+    dataset
+        .new_attr_builder()
+        .with_data(colors.as_slice())
+        .create("colors")?;
+
+    Ok(())
+}
+
+/// Load annotations, if any, from the file.
+fn import_annotations(db: &TsDbHandle, file: &hdf5::File) -> hdf5::Result<()> {
+    let dataset = if let Ok(dataset) = file.dataset("annotations") {
+        dataset
+    } else {
+        return Ok(());
+    };
+
+    let timespans = dataset.read_2d::<f64>()?;
+    let titles = dataset
+        .attr("titles")?
+        .read_raw::<hdf5::types::VarLenUnicode>()?;
+    let colors = dataset
+        .attr("colors")?
+        .read_raw::<hdf5::types::VarLenUnicode>()?;
+
+    for ((row, title), color) in timespans.rows().into_iter().zip(titles).zip(colors) {
+        let timespan = TimeSpan::new(TimeStamp::new(row[0]), TimeStamp::new(row[1]));
+        let mut annotation = Annotation::span(timespan, title.as_str());
+        if !color.as_str().is_empty() {
+            annotation.color = Some(color.as_str().to_owned());
         }
+        db.add_annotation(annotation);
     }
 
     Ok(())
 }
 
+/// Store signal meta data as attributes on the dataset.
+fn export_metadata(dataset: &hdf5::Dataset, metadata: &SignalMetadata) -> hdf5::Result<()> {
+    if let Some(unit) = &metadata.unit {
+        write_string_attribute(dataset, "unit", unit)?;
+    }
+
+    if let Some(description) = &metadata.description {
+        write_string_attribute(dataset, "description", description)?;
+    }
+
+    if let Some(color) = &metadata.color {
+        write_string_attribute(dataset, "color", color)?;
+    }
+
+    dataset
+        .new_attr_builder()
+        .with_data(&[metadata.scale, metadata.offset][..])
+        .create("scale_offset")?;
+
+    if let Some(gap_threshold) = metadata.gap_threshold {
+        dataset
+            .new_attr_builder()
+            .with_data(&[gap_threshold][..])
+            .create("gap_threshold")?;
+    }
+
+    if !metadata.tags.is_empty() {
+        let tags = metadata
+            .tags
+            .iter()
+            .map(|t| to_hdf5_string(t))
+            .collect::<hdf5::Result<Vec<_>>>()?;
+        dataset
+            .new_attr_builder()
+            .with_data(tags.as_slice())
+            .create("tags")?;
+    }
+
+    Ok(())
+}
+
+fn write_string_attribute(dataset: &hdf5::Dataset, name: &str, value: &str) -> hdf5::Result<()> {
+    let value = to_hdf5_string(value)?;
+    dataset
+        .new_attr_builder()
+        .with_data(&[value][..])
+        .create(name)?;
+    Ok(())
+}
+
+fn to_hdf5_string(value: &str) -> hdf5::Result<hdf5::types::VarLenUnicode> {
+    hdf5::types::VarLenUnicode::from_str(value).map_err(|e| hdf5::Error::from(e.to_string()))
+}
+
+/// Load signal meta data from the attributes of a dataset.
+///
+/// Files without meta data result in `None`.
+fn import_metadata(dataset: &hdf5::Dataset) -> Option<SignalMetadata> {
+    let read_strings = |name: &str| -> Option<Vec<String>> {
+        let attr = dataset.attr(name).ok()?;
+        let values = attr.read_raw::<hdf5::types::VarLenUnicode>().ok()?;
+        Some(values.iter().map(|v| v.as_str().to_owned()).collect())
+    };
+
+    let scale_offset = dataset.attr("scale_offset").ok()?.read_raw::<f64>().ok()?;
+    if scale_offset.len() != 2 {
+        return None;
+    }
+
+    let first_string = |name: &str| read_strings(name).and_then(|v| v.into_iter().next());
+    Some(SignalMetadata {
+        unit: first_string("unit"),
+        description: first_string("description"),
+        scale: scale_offset[0],
+        offset: scale_offset[1],
+        color: first_string("color"),
+        tags: read_strings("tags").unwrap_or_default(),
+        gap_threshold: dataset
+            .attr("gap_threshold")
+            .ok()
+            .and_then(|attr| attr.read_raw::<f64>().ok())
+            .and_then(|values| values.first().copied()),
+    })
+}
+
 /// Import data from file into the database.
 pub fn import_data(db: TsDbHandle, filename: &Path) -> hdf5::Result<()> {
     let file = hdf5::File::open(filename)?;
@@ -136,20 +297,26 @@ fn import_data_inner(db: TsDbHandle, file: &hdf5::File) -> hdf5::Result<()> {
             }
 
             db.add_values(&name, samples);
+
+            if let Some(metadata) = import_metadata(&dataset) {
+                db.set_metadata(&name, metadata);
+            }
         } else {
             warn!("Skipping signal due to shape: {:?}", shape);
         }
     }
 
+    import_annotations(&db, file)?;
+
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::{export_db, import_data_inner};
-    use lognplot::time::TimeStamp;
+    use lognplot::time::{TimeSpan, TimeStamp};
     use lognplot::tsdb::observations::{Observation, Sample};
-    use lognplot::tsdb::{TsDb, TsDbApi};
+    use lognplot::tsdb::{Annotation, SignalMetadata, TsDb, TsDbApi};
 
     #[test]
     fn export_test() -> hdf5::Result<()> {
@@ -165,11 +332,16 @@ mod tests {
             db.add_value(trace_name, observation);
         }
 
+        db.set_metadata(trace_name, SignalMetadata::with_unit("V"));
+        let mut annotation = Annotation::span(TimeSpan::from_seconds(2, 4), "step 1");
+        annotation.color = Some("#FF7F0E".to_owned());
+        db.add_annotation(annotation.clone());
+
         let db_handle = db.into_handle();
 
         // Export data:
         let file = hdf5::File::create("export_test.h5")?;
-        export_db(db_handle, &file)?;
+        export_db(db_handle, &file, "**")?;
 
         // Import the data back:
         let db2_handle = TsDb::default().into_handle();
@@ -177,6 +349,11 @@ mod tests {
 
         assert_eq!(vec![trace_name], db2_handle.get_signal_names());
         assert_eq!(7, db2_handle.quick_summary(trace_name).unwrap().count);
+        assert_eq!(
+            Some(SignalMetadata::with_unit("V")),
+            db2_handle.get_metadata(trace_name)
+        );
+        assert_eq!(vec![annotation], db2_handle.get_annotations(None));
         Ok(())
     }
 }
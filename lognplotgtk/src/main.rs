@@ -4,8 +4,10 @@ extern crate log;
 #[macro_use]
 extern crate glib;
 
+mod alarms;
 mod chart_state;
 mod chart_widget;
+mod connections;
 mod error_dialog;
 
 #[cfg(feature = "hdf5")]
@@ -17,11 +19,12 @@ mod resources;
 mod session;
 mod signal_repository;
 mod state;
-mod time_tracker;
+mod statistics_dialog;
+mod text_search;
 
-use lognplot::net::run_server;
+use lognplot::net::{start_relay, start_server, RelayOptions, ServerOptions, TlsOptions};
 use lognplot::tracer::AnyTracer;
-use lognplot::tsdb::TsDb;
+use lognplot::tsdb::{EvictionPolicy, MemoryBudget, TsDb};
 use std::sync::Arc;
 
 pub use state::{GuiState, GuiStateHandle};
@@ -55,6 +58,83 @@ fn main() {
                 .help("Port to listen on")
                 .default_value("12345"),
         )
+        .arg(
+            clap::Arg::with_name("udp-port")
+                .long("udp-port")
+                .takes_value(true)
+                .help("Also receive data in UDP datagrams on the given port."),
+        )
+        .arg(
+            clap::Arg::with_name("unix-socket")
+                .long("unix-socket")
+                .takes_value(true)
+                .help("Also accept local connections on a unix domain socket at the given path."),
+        )
+        .arg(
+            clap::Arg::with_name("bind")
+                .long("bind")
+                .takes_value(true)
+                .help("Address to listen on, instead of all addresses."),
+        )
+        .arg(
+            clap::Arg::with_name("tls-cert")
+                .long("tls-cert")
+                .takes_value(true)
+                .requires("tls-key")
+                .help("Encrypt connections with the certificate in the given PEM file."),
+        )
+        .arg(
+            clap::Arg::with_name("tls-key")
+                .long("tls-key")
+                .takes_value(true)
+                .requires("tls-cert")
+                .help("Private key of the certificate, in a PEM file."),
+        )
+        .arg(
+            clap::Arg::with_name("tls-client-ca")
+                .long("tls-client-ca")
+                .takes_value(true)
+                .requires("tls-cert")
+                .help("Only accept clients with a certificate signed by the authorities in the given PEM file."),
+        )
+        .arg(
+            clap::Arg::with_name("token")
+                .long("token")
+                .takes_value(true)
+                .conflicts_with("udp-port")
+                .help("Only accept clients which present the given token."),
+        )
+        .arg(
+            clap::Arg::with_name("relay")
+                .long("relay")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Forward incoming data to the lognplot server at the given address (host:port)."),
+        )
+        .arg(
+            clap::Arg::with_name("relay-signals")
+                .long("relay-signals")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .requires("relay")
+                .help("Only forward signals matching the glob pattern, instead of all signals."),
+        )
+        .arg(
+            clap::Arg::with_name("relay-prefix")
+                .long("relay-prefix")
+                .takes_value(true)
+                .requires("relay")
+                .help("Put the given prefix in front of the names of forwarded signals."),
+        )
+        .arg(
+            clap::Arg::with_name("relay-token")
+                .long("relay-token")
+                .takes_value(true)
+                .requires("relay")
+                .help("Token to present to the servers data is forwarded to."),
+        )
         .arg(
             clap::Arg::with_name("meta-trace")
                 .long("meta-trace")
@@ -66,6 +146,34 @@ fn main() {
                 .takes_value(true)
                 .help("Trace internal performance metrics to the given address (host:port)."),
         )
+        .arg(
+            clap::Arg::with_name("alarms")
+                .long("alarms")
+                .takes_value(true)
+                .help("Load alarm rules from the given JSON file."),
+        )
+        .arg(
+            clap::Arg::with_name("estimate-clock")
+                .long("estimate-clock")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Convert timestamps of signals matching the glob pattern into wall clock time, using an estimate of the clock of their source."),
+        )
+        .arg(
+            clap::Arg::with_name("memory-limit")
+                .long("memory-limit")
+                .takes_value(true)
+                .help("Limit the memory used by signal data to the given amount of MiB."),
+        )
+        .arg(
+            clap::Arg::with_name("eviction")
+                .long("eviction")
+                .takes_value(true)
+                .possible_values(&["downsample", "drop-oldest"])
+                .default_value("downsample")
+                .help("How to reduce signals which were not viewed recently, when the memory limit is exceeded."),
+        )
         .get_matches();
 
     let verbosity = matches.occurrences_of("v");
@@ -83,6 +191,21 @@ fn main() {
             .expect("port value must be present"),
     )
     .unwrap_or(12345);
+    let udp_port = matches
+        .value_of("udp-port")
+        .map(|udp_port| u16::from_str(udp_port).expect("UDP port must be a number"));
+    let unix_path = matches
+        .value_of("unix-socket")
+        .map(std::path::PathBuf::from);
+    let bind_address = matches.value_of("bind").map(|address| {
+        std::net::IpAddr::from_str(address).expect("Bind address must be an IP address")
+    });
+    let tls = matches.value_of("tls-cert").map(|certificate| TlsOptions {
+        certificate: certificate.into(),
+        key: matches.value_of("tls-key").unwrap().into(),
+        client_ca_certificates: matches.value_of("tls-client-ca").map(Into::into),
+    });
+    let token = matches.value_of("token").map(str::to_owned);
 
     simple_logger::init_with_level(log_level).unwrap();
 
@@ -96,13 +219,8 @@ fn main() {
         let addr = matches.value_of("meta-trace-remote").unwrap();
         info!("Setting up meta tracing to remote: {:?}", addr);
         // let address = std::net::SocketAddr::from_str(addr);
-        match lognplot::net::TcpClient::new(addr) {
-            Ok(client) => Arc::new(AnyTracer::new_tcp(client)),
-            Err(err) => {
-                error!("Error setting up remote tracing: {:?}", err);
-                Arc::new(AnyTracer::new_void())
-            }
-        }
+        let client = lognplot::net::BufferedClient::new(addr);
+        Arc::new(AnyTracer::new_tcp(client))
     } else if matches.is_present("meta-trace") {
         info!("Setting up meta tracing");
         Arc::new(AnyTracer::new_db(db_handle.clone()))
@@ -110,7 +228,68 @@ fn main() {
         Arc::new(AnyTracer::new_void())
     };
 
-    let stop_token = run_server(db_handle.clone(), port, perf_tracer.clone());
-    mainwindow::open_gui(db_handle, perf_tracer);
+    if let Some(filename) = matches.value_of("alarms") {
+        if let Err(err) = alarms::load_alarm_rules(&db_handle, std::path::Path::new(filename)) {
+            error!("Error loading alarm rules from {}: {}", filename, err);
+        }
+    }
+
+    if let Some(patterns) = matches.values_of("estimate-clock") {
+        for pattern in patterns {
+            info!("Estimating clock of signals matching {}", pattern);
+            db_handle.estimate_clock(pattern);
+            db_handle.assign_clock(pattern, pattern);
+        }
+    }
+
+    if let Some(limit) = matches.value_of("memory-limit") {
+        match usize::from_str(limit) {
+            Ok(mebibytes) => {
+                let policy = match matches.value_of("eviction") {
+                    Some("drop-oldest") => EvictionPolicy::DropOldest,
+                    _ => EvictionPolicy::Downsample,
+                };
+                info!("Limiting memory use to {} MiB", mebibytes);
+                db_handle.set_memory_budget(Some(MemoryBudget::new(mebibytes << 20, policy)));
+            }
+            Err(err) => {
+                error!("Invalid memory limit {}: {}", limit, err);
+            }
+        }
+    }
+
+    let options = ServerOptions {
+        port,
+        udp_port,
+        unix_path,
+        bind_address,
+        tls,
+        token,
+    };
+    let stop_token = match start_server(db_handle.clone(), options, perf_tracer.clone()) {
+        Ok(stop_token) => stop_token,
+        Err(err) => {
+            error!("Error starting server: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let relay = matches.values_of("relay").map(|upstreams| {
+        let mut options = RelayOptions {
+            upstreams: upstreams.map(str::to_owned).collect(),
+            prefix: matches.value_of("relay-prefix").map(str::to_owned),
+            ..RelayOptions::default()
+        };
+        if let Some(patterns) = matches.values_of("relay-signals") {
+            options.patterns = patterns.map(str::to_owned).collect();
+        }
+        options.client.token = matches.value_of("relay-token").map(str::to_owned);
+        start_relay(db_handle.clone(), options)
+    });
+
+    mainwindow::open_gui(db_handle, perf_tracer, stop_token.peer_registry());
+    if let Some(relay) = relay {
+        relay.stop();
+    }
     stop_token.stop();
 }
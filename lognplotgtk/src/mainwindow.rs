@@ -1,16 +1,20 @@
+use super::alarms::{setup_alarm_timer, show_alarm};
 use super::chart_widget::create_new_chart_area;
+use super::connections::setup_connections;
 use super::io::{load_data_from_hdf5, save_data_as_hdf5};
 use super::session::{load_session, save_session};
 use super::signal_repository::setup_signal_repository;
+use super::text_search::setup_text_search;
 use super::{GuiState, GuiStateHandle};
 use gio::prelude::*;
 use gtk::prelude::*;
 use gtk::Application;
-use lognplot::tracer::AnyTracer;
+use lognplot::net::PeerRegistry;
+use lognplot::tracer::{AnyTracer, Tracer};
 use lognplot::tsdb::TsDbHandle;
 use std::sync::Arc;
 
-pub fn open_gui(db_handle: TsDbHandle, perf_tracer: Arc<AnyTracer>) {
+pub fn open_gui(db_handle: TsDbHandle, perf_tracer: Arc<AnyTracer>, peers: PeerRegistry) {
     let app_state = GuiState::new(db_handle, perf_tracer).into_handle();
 
     let application = Application::new(
@@ -18,12 +22,12 @@ pub fn open_gui(db_handle: TsDbHandle, perf_tracer: Arc<AnyTracer>) {
         gio::ApplicationFlags::NON_UNIQUE,
     );
 
-    application.connect_activate(move |app| build_ui(app, app_state.clone()));
+    application.connect_activate(move |app| build_ui(app, app_state.clone(), peers.clone()));
 
     application.run();
 }
 
-fn build_ui(app: &gtk::Application, app_state: GuiStateHandle) {
+fn build_ui(app: &gtk::Application, app_state: GuiStateHandle, peers: PeerRegistry) {
     // First we get the file content.
     let glade_src = include_str!("gui.glade");
     // Then we call the Builder call.
@@ -31,11 +35,15 @@ fn build_ui(app: &gtk::Application, app_state: GuiStateHandle) {
 
     // Connect the data set tree:
     setup_signal_repository(&builder, app_state.clone());
+    setup_text_search(&builder, app_state.clone());
+    setup_connections(&builder, peers);
 
     setup_chart_area(&builder, app_state.clone());
     setup_menus(&builder, app_state.clone());
     setup_toolbar_buttons(&builder, app_state.clone());
     setup_tailing_timer(app_state.clone());
+    setup_alarm_timer(app_state.borrow().db.clone());
+    setup_ingest_meta_trace(&app_state);
     setup_notify_change(app_state);
 
     // Connect application to window:
@@ -232,6 +240,9 @@ fn setup_notify_change(app_state: GuiStateHandle) {
         while let Some(event) = receiver.next().await {
             // println!("Event: {:?}", event);
             app_state.borrow().handle_event(&event);
+            for alarm in &event.alarms {
+                show_alarm(alarm);
+            }
 
             // Delay to emulate rate limiting of events.
             glib::timeout_future_with_priority(
@@ -255,3 +266,37 @@ fn setup_tailing_timer(app_state: GuiStateHandle) {
     };
     glib::timeout_add_local(std::time::Duration::from_millis(57), tick);
 }
+
+/// Trace the ingestion statistics of all signals as meta signals.
+///
+/// Only done when meta tracing is enabled.
+fn setup_ingest_meta_trace(app_state: &GuiStateHandle) {
+    let perf_tracer = app_state.borrow().get_perf_tracer();
+    if let AnyTracer::Void = *perf_tracer {
+        return;
+    }
+
+    let db = app_state.borrow().db.clone();
+    let tick = move || {
+        let now = std::time::Instant::now();
+        for name in db.get_signal_names() {
+            // Do not trace the meta signals themselves:
+            if name.starts_with("META.") {
+                continue;
+            }
+            if let Some(statistics) = db.ingest_statistics(&name) {
+                let prefix = format!("META.ingest.{}", name);
+                perf_tracer.log_metric(&format!("{}.rate", prefix), now, statistics.current_rate);
+                perf_tracer.log_metric(&format!("{}.jitter", prefix), now, statistics.jitter);
+                perf_tracer.log_metric(&format!("{}.max_gap", prefix), now, statistics.max_gap);
+                perf_tracer.log_metric(
+                    &format!("{}.age", prefix),
+                    now,
+                    statistics.age.as_secs_f64(),
+                );
+            }
+        }
+        gtk::prelude::Continue(true)
+    };
+    glib::timeout_add_local(std::time::Duration::from_secs(1), tick);
+}
@@ -4,14 +4,31 @@ use std::collections::HashMap;
 // TODO
 // use crate::error_dialog::show_error;
 use crate::state::GuiStateHandle;
-use lognplot::tsdb::{DataChangeEvent, TsDbHandle};
+use lognplot::tsdb::IngestStatistics;
+use lognplot::tsdb::SEPARATOR;
+use lognplot::tsdb::{format_bytes, glob_match, is_glob, DataChangeEvent, LastValue, TsDbHandle};
+
+/// Column holding the full signal name, or an empty string for a namespace folder.
+const SIGNAL_NAME_COLUMN: i32 = 5;
+
+/// Column holding the estimated memory use of the signal.
+const MEMORY_COLUMN: i32 = 6;
+
+/// Columns with the ingestion statistics of the signal.
+const RATE_COLUMN: i32 = 7;
+const JITTER_COLUMN: i32 = 8;
+const MAX_GAP_COLUMN: i32 = 9;
+const AGE_COLUMN: i32 = 10;
 
 pub struct SignalBrowser {
     model: gtk::TreeStore,
     db: TsDbHandle,
 
     // Mapping from signal name to row:
-    model_map: HashMap<String, i32>,
+    model_map: HashMap<String, gtk::TreeIter>,
+
+    // Mapping from namespace prefix to folder row:
+    folder_map: HashMap<String, gtk::TreeIter>,
 }
 
 impl SignalBrowser {
@@ -20,6 +37,7 @@ impl SignalBrowser {
         if event.delete_all {
             self.delete_all();
         }
+        self.delete_signals(event.deleted_signals.iter());
         self.add_new_signals(event.new_signals.iter()).await;
         self.update_signals(event.changed_signals.iter()).await;
     }
@@ -31,11 +49,29 @@ impl SignalBrowser {
     {
         let mut updates = 0;
         for signal_name in new_signals {
-            let iter = self.model.append(None);
-            let row = self.model_map.len() as i32;
-            self.model_map.insert(signal_name.clone(), row);
-            self.model
-                .set(&iter, &[(0, signal_name), (1, &"-"), (2, &"-")]);
+            if self.model_map.contains_key(signal_name) {
+                continue;
+            }
+
+            let (parent, leaf_name) = self.get_or_create_folder(signal_name);
+            let iter = self.model.append(parent.as_ref());
+            self.model_map.insert(signal_name.clone(), iter.clone());
+            self.model.set(
+                &iter,
+                &[
+                    (0, &leaf_name),
+                    (1, &"-"),
+                    (2, &"-"),
+                    (3, &""),
+                    (4, &""),
+                    (SIGNAL_NAME_COLUMN as u32, signal_name),
+                    (MEMORY_COLUMN as u32, &"-"),
+                    (RATE_COLUMN as u32, &"-"),
+                    (JITTER_COLUMN as u32, &"-"),
+                    (MAX_GAP_COLUMN as u32, &"-"),
+                    (AGE_COLUMN as u32, &"-"),
+                ],
+            );
 
             updates += 1;
             if updates > 50 {
@@ -51,6 +87,54 @@ impl SignalBrowser {
         }
     }
 
+    /// Find the folder row in which the given signal belongs.
+    ///
+    /// Folders are created for each namespace level in the signal name.
+    /// Returns the folder and the last part of the signal name.
+    fn get_or_create_folder(&mut self, signal_name: &str) -> (Option<gtk::TreeIter>, String) {
+        let mut segments: Vec<&str> = signal_name.split(SEPARATOR).collect();
+        let leaf_name = segments.pop().unwrap_or_default();
+        if leaf_name.is_empty() {
+            return (None, signal_name.to_owned());
+        }
+
+        let mut parent: Option<gtk::TreeIter> = None;
+        let mut prefix = String::new();
+        for segment in segments {
+            if !prefix.is_empty() {
+                prefix.push(SEPARATOR);
+            }
+            prefix.push_str(segment);
+
+            let folder = if let Some(folder) = self.folder_map.get(&prefix) {
+                folder.clone()
+            } else {
+                let folder = self.model.append(parent.as_ref());
+                self.model.set(
+                    &folder,
+                    &[
+                        (0, &segment),
+                        (1, &""),
+                        (2, &""),
+                        (3, &""),
+                        (4, &""),
+                        (SIGNAL_NAME_COLUMN as u32, &""),
+                        (MEMORY_COLUMN as u32, &""),
+                        (RATE_COLUMN as u32, &""),
+                        (JITTER_COLUMN as u32, &""),
+                        (MAX_GAP_COLUMN as u32, &""),
+                        (AGE_COLUMN as u32, &""),
+                    ],
+                );
+                self.folder_map.insert(prefix.clone(), folder.clone());
+                folder
+            };
+            parent = Some(folder);
+        }
+
+        (parent, leaf_name.to_owned())
+    }
+
     /// Update existing signals in the model
     async fn update_signals<'a, I>(&self, changed_signals: I)
     where
@@ -58,14 +142,34 @@ impl SignalBrowser {
     {
         let mut updates = 0;
         for signal_name in changed_signals {
-            if let Some(summary) = self.db.quick_summary(&signal_name) {
-                let row = self.model_map[signal_name];
-                let path: gtk::TreePath = gtk::TreePath::from_indicesv(&[row]);
-                if let Some(iter2) = self.model.iter(&path) {
-                    self.model
-                        .set_value(&iter2, 1, &summary.count.to_string().to_value());
-                    self.model
-                        .set_value(&iter2, 2, &summary.last_value().to_value());
+            if let (Some(summary), Some(iter2)) = (
+                self.db.quick_summary(&signal_name),
+                self.model_map.get(signal_name),
+            ) {
+                let metadata = self.db.get_metadata(&signal_name).unwrap_or_default();
+                let last_value = match &summary.last {
+                    LastValue::Value(observation) => metadata.format_value(observation.value.value),
+                    _ => summary.last_value(),
+                };
+                self.model
+                    .set_value(iter2, 1, &summary.count.to_string().to_value());
+                self.model.set_value(iter2, 2, &last_value.to_value());
+                self.model
+                    .set_value(iter2, 3, &metadata.unit.unwrap_or_default().to_value());
+                self.model.set_value(
+                    iter2,
+                    4,
+                    &metadata.description.unwrap_or_default().to_value(),
+                );
+                if let Some(usage) = self.db.memory_usage(&signal_name) {
+                    self.model.set_value(
+                        iter2,
+                        MEMORY_COLUMN as u32,
+                        &format_bytes(usage.total()).to_value(),
+                    );
+                }
+                if let Some(statistics) = self.db.ingest_statistics(&signal_name) {
+                    set_ingest_columns(&self.model, iter2, &statistics);
                 }
                 updates += 1;
                 if updates > 50 {
@@ -84,19 +188,55 @@ impl SignalBrowser {
         debug!("Updates: {}", updates);
     }
 
+    /// Remove deleted signals, and folders which became empty.
+    fn delete_signals<'a, I>(&mut self, deleted_signals: I)
+    where
+        I: Iterator<Item = &'a String>,
+    {
+        for signal_name in deleted_signals {
+            if let Some(iter) = self.model_map.remove(signal_name) {
+                self.model.remove(&iter);
+            }
+
+            // Walk up the namespace, and remove empty folders:
+            let mut prefix: &str = signal_name;
+            while let Some(pos) = prefix.rfind(SEPARATOR) {
+                prefix = &prefix[..pos];
+                if let Some(folder) = self.folder_map.get(prefix) {
+                    if self.model.iter_has_child(folder) {
+                        break;
+                    }
+                    self.model.remove(folder);
+                    self.folder_map.remove(prefix);
+                }
+            }
+        }
+    }
+
     /// Delete all signals from the model
     fn delete_all(&mut self) {
         self.model.clear();
         self.model_map.clear();
+        self.folder_map.clear();
     }
 }
 
 /// Prepare a widget with a list of available signals.
 pub fn setup_signal_repository(builder: &gtk::Builder, app_state: GuiStateHandle) {
+    // Columns: name, size, last value, unit, description, full signal name, memory,
+    // rate, jitter, max gap, age
     let model = gtk::TreeStore::new(&[
         String::static_type(),
         String::static_type(),
         String::static_type(),
+        String::static_type(),
+        String::static_type(),
+        String::static_type(),
+        String::static_type(),
+        String::static_type(),
+        String::static_type(),
+        String::static_type(),
+        String::static_type(),
     ]);
 
     setup_columns(builder);
@@ -108,11 +248,13 @@ pub fn setup_signal_repository(builder: &gtk::Builder, app_state: GuiStateHandle
     setup_key_press_handler(&tree_view, app_state.clone());
 
     let db = { app_state.borrow().db.clone() };
+    setup_age_timer(model.clone(), db.clone());
 
     let signal_browser = SignalBrowser {
         model,
         db,
         model_map: HashMap::new(),
+        folder_map: HashMap::new(),
     };
 
     setup_notify_change(signal_browser);
@@ -143,10 +285,61 @@ fn setup_notify_change(mut signal_pane: SignalBrowser) {
     });
 }
 
+fn set_ingest_columns(model: &gtk::TreeStore, iter: &gtk::TreeIter, statistics: &IngestStatistics) {
+    model.set(
+        iter,
+        &[
+            (
+                RATE_COLUMN as u32,
+                &format!("{:.1} Hz", statistics.current_rate),
+            ),
+            (
+                JITTER_COLUMN as u32,
+                &format!("{:.2} ms", statistics.jitter * 1.0e3),
+            ),
+            (
+                MAX_GAP_COLUMN as u32,
+                &format!("{:.3} s", statistics.max_gap),
+            ),
+            (
+                AGE_COLUMN as u32,
+                &format!("{:.1} s", statistics.age.as_secs_f64()),
+            ),
+        ],
+    );
+}
+
+/// Refresh the age of all signals periodically, also when no new data arrives.
+fn setup_age_timer(model: gtk::TreeStore, db: TsDbHandle) {
+    let tick = move || {
+        model.foreach(|m, _, iter| {
+            let signal_name = get_signal_name(m, iter);
+            if !signal_name.is_empty() {
+                if let Some(statistics) = db.ingest_statistics(&signal_name) {
+                    model.set_value(
+                        iter,
+                        AGE_COLUMN as u32,
+                        &format!("{:.1} s", statistics.age.as_secs_f64()).to_value(),
+                    );
+                }
+            }
+            false
+        });
+        gtk::prelude::Continue(true)
+    };
+    glib::timeout_add_local(std::time::Duration::from_secs(1), tick);
+}
+
 fn setup_columns(builder: &gtk::Builder) {
     let name_column: gtk::TreeViewColumn = builder.object("column_name").unwrap();
     let size_column: gtk::TreeViewColumn = builder.object("column_size").unwrap();
     let last_value_column: gtk::TreeViewColumn = builder.object("column_last_value").unwrap();
+    let unit_column: gtk::TreeViewColumn = builder.object("column_unit").unwrap();
+    let memory_column: gtk::TreeViewColumn = builder.object("column_memory").unwrap();
+    let rate_column: gtk::TreeViewColumn = builder.object("column_rate").unwrap();
+    let jitter_column: gtk::TreeViewColumn = builder.object("column_jitter").unwrap();
+    let max_gap_column: gtk::TreeViewColumn = builder.object("column_max_gap").unwrap();
+    let age_column: gtk::TreeViewColumn = builder.object("column_age").unwrap();
 
     let cell = gtk::CellRendererText::new();
     name_column.pack_start(&cell, true);
@@ -159,6 +352,34 @@ fn setup_columns(builder: &gtk::Builder) {
     let cell = gtk::CellRendererText::new();
     last_value_column.pack_start(&cell, true);
     last_value_column.add_attribute(&cell, "text", 2);
+
+    let cell = gtk::CellRendererText::new();
+    unit_column.pack_start(&cell, true);
+    unit_column.add_attribute(&cell, "text", 3);
+
+    let cell = gtk::CellRendererText::new();
+    memory_column.pack_start(&cell, true);
+    memory_column.add_attribute(&cell, "text", MEMORY_COLUMN);
+
+    let cell = gtk::CellRendererText::new();
+    rate_column.pack_start(&cell, true);
+    rate_column.add_attribute(&cell, "text", RATE_COLUMN);
+
+    let cell = gtk::CellRendererText::new();
+    jitter_column.pack_start(&cell, true);
+    jitter_column.add_attribute(&cell, "text", JITTER_COLUMN);
+
+    let cell = gtk::CellRendererText::new();
+    max_gap_column.pack_start(&cell, true);
+    max_gap_column.add_attribute(&cell, "text", MAX_GAP_COLUMN);
+
+    let cell = gtk::CellRendererText::new();
+    age_column.pack_start(&cell, true);
+    age_column.add_attribute(&cell, "text", AGE_COLUMN);
+
+    // Show signal description as tooltip:
+    let tree_view: gtk::TreeView = builder.object("signal_tree_view").unwrap();
+    tree_view.set_tooltip_column(4);
 }
 
 fn setup_filter_model(builder: &gtk::Builder, model: &gtk::TreeStore) {
@@ -173,21 +394,47 @@ fn setup_filter_model(builder: &gtk::Builder, model: &gtk::TreeStore) {
 
     filter_model.set_visible_func(clone!(@strong filter_edit => move |m, i| {
         let txt = filter_edit.text().to_string();
-        signal_filter_func(m, i, txt)
+        signal_filter_func(m, i, &txt)
     }));
 
     tree_view.set_model(Some(&filter_model));
 
-    filter_edit.connect_search_changed(move |_e| {
+    filter_edit.connect_search_changed(clone!(@strong tree_view => move |e| {
         filter_model.refilter();
-    });
+
+        // Show matching signals deep down in the tree:
+        if !e.text().is_empty() {
+            tree_view.expand_all();
+        }
+    }));
 }
 
-fn signal_filter_func(model: &gtk::TreeModel, iter: &gtk::TreeIter, filter_txt: String) -> bool {
-    if let Ok(name) = model.value(&iter, 0).get::<String>() {
-        filter_txt.is_empty() || name.contains(&filter_txt)
+/// Determine if a row is visible given the filter text.
+///
+/// The filter text is either a glob pattern, or a plain substring.
+/// Folders are visible when any of the signals below it is visible.
+fn signal_filter_func(model: &gtk::TreeModel, iter: &gtk::TreeIter, filter_txt: &str) -> bool {
+    if filter_txt.is_empty() {
+        return true;
+    }
+
+    let name = get_signal_name(model, iter);
+    if name.is_empty() {
+        if let Some(child) = model.iter_children(Some(iter)) {
+            loop {
+                if signal_filter_func(model, &child, filter_txt) {
+                    return true;
+                }
+                if !model.iter_next(&child) {
+                    break;
+                }
+            }
+        }
+        false
+    } else if is_glob(filter_txt) {
+        glob_match(filter_txt, &name)
     } else {
-        true
+        name.contains(filter_txt)
     }
 }
 
@@ -258,25 +505,55 @@ fn handle_drop_uri(uri: String, app_state: &GuiStateHandle) -> Result<(), String
     }
 }
 
+/// Get the names of the selected signals.
+///
+/// Selecting a folder selects all signals in it.
 fn get_selected_signal_names(w: &gtk::TreeView) -> Vec<String> {
     let selector = w.selection();
     let (selected_rows, tree_model) = selector.selected_rows();
     let mut selected_names: Vec<String> = vec![];
     for selected_row in selected_rows {
         if let Some(tree_iter) = tree_model.iter(&selected_row) {
-            let value = get_signal_name(&tree_model, &tree_iter);
-            selected_names.push(value);
+            collect_signal_names(&tree_model, &tree_iter, &mut selected_names);
         }
     }
+    selected_names.dedup();
     selected_names
 }
 
+/// Collect the names of the signal at the given row and all signals below it.
+fn collect_signal_names(model: &gtk::TreeModel, iter: &gtk::TreeIter, names: &mut Vec<String>) {
+    let name = get_signal_name(model, iter);
+    if !name.is_empty() && !names.contains(&name) {
+        names.push(name);
+    }
+
+    if let Some(child) = model.iter_children(Some(iter)) {
+        loop {
+            collect_signal_names(model, &child, names);
+            if !model.iter_next(&child) {
+                break;
+            }
+        }
+    }
+}
+
 fn setup_activate(tree_view: &gtk::TreeView, app_state: GuiStateHandle) {
     tree_view.connect_row_activated(move |tv, path, _| {
         let model = tv.model().unwrap();
         let iter = model.iter(path).unwrap();
         let value = get_signal_name(&model, &iter);
 
+        if value.is_empty() {
+            // Activated a folder, expand or collapse it:
+            if tv.row_expanded(path) {
+                tv.collapse_row(path);
+            } else {
+                tv.expand_row(path, false);
+            }
+            return;
+        }
+
         debug!("Signal activated: {}, adding to chart.", value);
         // Add activated signal to plot:
         app_state.borrow().add_curve(&value, None);
@@ -286,6 +563,16 @@ fn setup_activate(tree_view: &gtk::TreeView, app_state: GuiStateHandle) {
 fn setup_key_press_handler(tree_view: &gtk::TreeView, app_state: GuiStateHandle) {
     tree_view.connect_key_press_event(move |tv, key| {
         let selected_signals = get_selected_signal_names(&tv);
+
+        if key.keyval() == gdk::keys::constants::Delete {
+            let db = app_state.borrow().db.clone();
+            for signal_name in selected_signals {
+                info!("Deleting signal {}", signal_name);
+                db.delete(&signal_name);
+            }
+            return Inhibit(true);
+        }
+
         let chart_target = match key.keyval() {
             gdk::keys::constants::_1 => Some(1),
             gdk::keys::constants::_2 => Some(2),
@@ -319,6 +606,11 @@ fn setup_key_press_handler(tree_view: &gtk::TreeView, app_state: GuiStateHandle)
 }
 
 /// Given a model and an iterator get the signal name.
+///
+/// For folder rows, this is an empty string.
 fn get_signal_name(model: &gtk::TreeModel, iter: &gtk::TreeIter) -> String {
-    model.value(iter, 0).get::<String>().unwrap()
+    model
+        .value(iter, SIGNAL_NAME_COLUMN)
+        .get::<String>()
+        .unwrap_or_default()
 }
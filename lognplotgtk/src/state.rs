@@ -1,5 +1,6 @@
 use crate::chart_state::{ChartState, ChartStateHandle};
 use crate::session;
+use lognplot::time::TimeStamp;
 use lognplot::tracer::AnyTracer;
 use lognplot::tsdb::{DataChangeEvent, TsDbHandle};
 use std::cell::RefCell;
@@ -42,7 +43,7 @@ impl GuiState {
     #[cfg(feature = "hdf5")]
     pub fn save(&self, filename: &Path) -> Result<(), String> {
         info!("Save data to {:?}", filename);
-        super::io::export_data(self.db.clone(), filename).map_err(|e| e.to_string())
+        super::io::export_data(self.db.clone(), filename, "**").map_err(|e| e.to_string())
     }
 
     #[cfg(feature = "hdf5")]
@@ -79,8 +80,7 @@ impl GuiState {
     }
 
     pub fn delete_chart(&mut self, chart: &ChartStateHandle) {
-        self.charts
-            .retain(|e| e.borrow().id() != chart.borrow().id());
+        self.charts.retain(|e| !Rc::ptr_eq(e, chart));
     }
 
     pub fn num_charts(&self) -> usize {
@@ -155,6 +155,21 @@ impl GuiState {
         }
     }
 
+    /// Move the charts to a moment in time.
+    ///
+    /// With linked x axes, the other charts follow the first one.
+    pub fn show_timestamp(&self, timestamp: TimeStamp) {
+        if self.link_x_axis {
+            if let Some(chart) = self.charts.first() {
+                chart.borrow_mut().jump_to(timestamp);
+            }
+        } else {
+            for chart in &self.charts {
+                chart.borrow_mut().jump_to(timestamp.clone());
+            }
+        }
+    }
+
     /// Distribute cursor position over other charts.
     pub fn sync_cursor(&self, source_chart: &ChartState) {
         for chart in &self.charts {
@@ -0,0 +1,39 @@
+//! Dialog showing statistics of two signals.
+
+use gtk::prelude::*;
+use lognplot::tsdb::CrossStatistics;
+
+/// Show the correlation between two signals, as computed by `ChartState::cross_statistics`.
+pub fn show_cross_statistics(
+    top_level: Option<&gtk::Window>,
+    result: Option<(String, String, Option<CrossStatistics>)>,
+) {
+    let message = match result {
+        None => {
+            "Place cursor 1 and 2, and show at least two signals in the chart.".to_owned()
+        }
+        Some((a, b, None)) => format!("No values of {} and {} between the cursors.", a, b),
+        Some((a, b, Some(statistics))) => format!(
+            "a = {}\nb = {}\n\nCorrelation: {:.4}\nCovariance: {}\nRegression: b = {} * a + {}\n\nLag of b: {} s (correlation {:.4})",
+            a,
+            b,
+            statistics.correlation,
+            statistics.covariance,
+            statistics.slope,
+            statistics.intercept,
+            statistics.lag,
+            statistics.lag_correlation
+        ),
+    };
+
+    let dialog = gtk::MessageDialog::new(
+        top_level,
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Info,
+        gtk::ButtonsType::Ok,
+        &message,
+    );
+    dialog.set_title("Signal statistics");
+    dialog.run();
+    dialog.close();
+}
@@ -0,0 +1,93 @@
+//! Search box for texts in text signals.
+//!
+//! Hits are listed below the signal browser. Activating a hit moves
+//! the charts to the moment of the hit.
+
+use crate::error_dialog::show_error;
+use crate::GuiStateHandle;
+use gtk::prelude::*;
+use lognplot::time::{TimeSpan, TimeStamp};
+use lognplot::tsdb::TextQuery;
+
+/// Maximum amount of hits listed.
+const MAX_HITS: usize = 1000;
+
+/// Column holding the timestamp of a hit, as a number.
+const TIMESTAMP_COLUMN: i32 = 0;
+
+pub fn setup_text_search(builder: &gtk::Builder, app_state: GuiStateHandle) {
+    let entry: gtk::SearchEntry = builder.object("text_search_entry").unwrap();
+    let mode: gtk::ComboBoxText = builder.object("text_search_mode").unwrap();
+    let hits_view: gtk::TreeView = builder.object("text_hits_view").unwrap();
+    let top_level: gtk::Window = builder.object("top_unit").unwrap();
+
+    // Columns: timestamp, formatted time, signal name, text
+    let model = gtk::ListStore::new(&[
+        f64::static_type(),
+        String::static_type(),
+        String::static_type(),
+        String::static_type(),
+    ]);
+    hits_view.set_model(Some(&model));
+    setup_columns(builder);
+
+    let db = app_state.borrow().db.clone();
+    entry.connect_activate(move |entry| {
+        model.clear();
+        let text = entry.text().to_string();
+        if text.is_empty() {
+            return;
+        }
+
+        let query = match mode.active_id().as_deref() {
+            Some("regex") => match TextQuery::regex(&text) {
+                Ok(query) => query,
+                Err(err) => {
+                    show_error(&top_level, &format!("Invalid regular expression: {}", err));
+                    return;
+                }
+            },
+            Some("substring") => TextQuery::substring(&text),
+            _ => TextQuery::tokens(&text),
+        };
+
+        let all_time = TimeSpan::new(
+            TimeStamp::new(f64::NEG_INFINITY),
+            TimeStamp::new(f64::INFINITY),
+        );
+        let hits = db.search_text("**", &query, &all_time, MAX_HITS);
+        info!("Found {} texts matching {:?}", hits.len(), text);
+        for hit in hits {
+            model.insert_with_values(
+                None,
+                &[
+                    (TIMESTAMP_COLUMN as u32, &hit.timestamp.amount),
+                    (1, &format!("{:.3}", hit.timestamp.amount)),
+                    (2, &hit.name),
+                    (3, &hit.text),
+                ],
+            );
+        }
+    });
+
+    hits_view.connect_row_activated(move |tv, path, _| {
+        let model = tv.model().unwrap();
+        let iter = model.iter(path).unwrap();
+        if let Ok(amount) = model.value(&iter, TIMESTAMP_COLUMN).get::<f64>() {
+            app_state.borrow().show_timestamp(TimeStamp::new(amount));
+        }
+    });
+}
+
+fn setup_columns(builder: &gtk::Builder) {
+    for (column_id, index) in &[
+        ("column_hit_time", 1),
+        ("column_hit_signal", 2),
+        ("column_hit_text", 3),
+    ] {
+        let column: gtk::TreeViewColumn = builder.object(column_id).unwrap();
+        let cell = gtk::CellRendererText::new();
+        column.pack_start(&cell, true);
+        column.add_attribute(&cell, "text", *index);
+    }
+}
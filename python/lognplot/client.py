@@ -8,6 +8,7 @@ import datetime
 import time
 import cbor
 
+PROTOCOL_VERSION = 1
 
 class LognplotTcpClient:
     """ Use this client to transmit sample to the lognplot tool.
@@ -16,12 +17,35 @@ class LognplotTcpClient:
     def __init__(self, hostname="localhost", port=12345):
         self._hostname = hostname
         self._port = port
+        self._signal_ids = {}
+        self._epoch = 0.0
 
     def connect(self):
         """ Connect to the server.
         """
         self._sock = socket.create_connection((self._hostname, self._port))
 
+    def hello(self, client_name="python", features=(), token=None):
+        """ Introduce this client to the server.
+
+        Returns the welcome message with the agreed protocol version and
+        features. Servers from before the handshake never answer. Give
+        the token for servers which require one.
+        """
+        message = {
+            "type": "hello",
+            "version": PROTOCOL_VERSION,
+            "client": client_name,
+            "features": list(features),
+        }
+        if token is not None:
+            message["token"] = token
+        self._send_dict(message)
+        reply = self._receive_dict()
+        if reply.get("type") == "error":
+            raise ValueError(reply.get("message"))
+        return reply
+
     def send_sample(self, name: str, timestamp, value: float):
         """ Send a single timestamp / value pair to the trace with the given name.
         """
@@ -31,6 +55,29 @@ class LognplotTcpClient:
             {"name": name, "t": timestamp, "type": "sample", "value": value}
         )
 
+    def set_epoch(self, timestamp):
+        """ Set the time which timestamps of frames are relative to.
+        """
+        self._epoch = coerce_timestamp(timestamp)
+        self._send_dict({"type": "epoch", "t": self._epoch})
+
+    def send_frame(self, samples):
+        """ Send samples of many signals in one frame.
+
+        samples is a list of (name, timestamp, value) tuples. Each signal
+        name is sent once, afterwards the signal is referred to by an id.
+        The server must support the signal_ids feature.
+        """
+        frame = []
+        for name, timestamp, value in samples:
+            if name not in self._signal_ids:
+                signal = len(self._signal_ids)
+                self._signal_ids[name] = signal
+                self._send_dict({"type": "define", "signal": signal, "name": name})
+            timestamp = coerce_timestamp(timestamp) - self._epoch
+            frame.append([self._signal_ids[name], timestamp, float(value)])
+        self._send_dict({"type": "frame", "samples": frame})
+
     def send_sample_batch(self, name: str, samples):
         """ Send a batch of samples.
 
@@ -71,6 +118,15 @@ class LognplotTcpClient:
         timestamp = coerce_timestamp(timestamp)
         self._send_dict({"name": name, "t": timestamp, "type": "text", "text": text})
 
+    def send_annotation(self, title, timestamp, end=None, color=None):
+        """ Mark a moment, or the period from timestamp to end. """
+        data = {"name": title, "t": coerce_timestamp(timestamp), "type": "annotation"}
+        if end is not None:
+            data["end"] = coerce_timestamp(end)
+        if color is not None:
+            data["color"] = color
+        self._send_dict(data)
+
     def send_function_enter(self, name, timestamp, function_name):
         """ Trace function entry. """
         timestamp = coerce_timestamp(timestamp)
@@ -93,6 +149,19 @@ class LognplotTcpClient:
         data.extend(msg_data)
         self._sock.sendall(data)
 
+    def _receive_dict(self):
+        (size,) = struct.unpack(">I", self._receive_exactly(4))
+        return cbor.loads(self._receive_exactly(size))
+
+    def _receive_exactly(self, size):
+        data = bytearray()
+        while len(data) < size:
+            chunk = self._sock.recv(size - len(data))
+            if not chunk:
+                raise ConnectionError("Connection closed by server")
+            data.extend(chunk)
+        return bytes(data)
+
 
 def coerce_timestamp(timestamp) -> float:
     """ Convert a timestamp into a floating point number. """